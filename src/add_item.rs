@@ -1,12 +1,13 @@
 use reqwest::multipart::{Form, Part};
-use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use serde_urlencoded;
 use std::collections::HashSet;
 use std::path::PathBuf;
 
-use crate::parser::parse_response;
+use crate::{
+    client::ArcGISClient, parser::parse_response, retry::SendWithRetry, web_map::WebMapJson,
+};
 
 #[derive(Deserialize)]
 struct PointCollection {
@@ -84,12 +85,15 @@ pub fn points_to_csv(
 
 pub struct AddItemQuery {
     url: String,
+    /// `content/users/[userName]`, for the item status of async uploads.
+    user_url: String,
     params: AddItemParams,
 }
 
 #[derive(Default)]
 pub struct AddItemQueryBuilder {
     url: String,
+    folder: Option<String>,
     params: AddItemParams,
 }
 
@@ -293,6 +297,10 @@ impl AddItemParams {
 }
 
 impl AddItemParams {
+    /// The multipart body for uploads. reqwest picks the boundary: 67
+    /// lowercase hex digits and dashes, which stays within RFC 2046's 70
+    /// characters and needs no quoting, so WAFs that are strict about the
+    /// `Content-Type` accept it.
     pub fn to_multipart(&self) -> anyhow::Result<Form> {
         let mut form = Form::new();
 
@@ -313,6 +321,7 @@ impl AddItemParams {
         add_text!(destination_item_id, "destinationItemId");
         add_text!(filename, "filename");
         add_text!(title, "title");
+        add_text!(type_keywords, "typeKeywords");
         add_text!(thumbnail_url, "thumbnailUrl");
         add_text!(description, "description");
         add_text!(tags, "tags");
@@ -401,6 +410,136 @@ pub struct AddItemResponse {
     pub success: bool,
     pub id: String,
     pub folder: Option<String>,
+    /// Set for `async_upload(true)` queries: the upload is still being
+    /// processed, poll it with [`AddItemJob::wait`].
+    #[serde(skip)]
+    pub job: Option<AddItemJob>,
+}
+
+/// An item added with `async=true` that may still be processing.
+#[derive(Debug)]
+pub struct AddItemJob {
+    status_url: String,
+    token: Option<String>,
+    pub id: String,
+    pub folder: Option<String>,
+}
+
+/// `content/users/[userName]/items/[itemId]/status`
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ItemStatus {
+    pub status: JobStatus,
+    pub status_message: Option<String>,
+    pub item_id: Option<String>,
+}
+
+/// State of an item upload or publish job.
+///
+/// Item status reports `processing`, `partial`, `completed` or `failed`,
+/// while geoprocessing-style jobs use `esriJob*` names; both parse to the
+/// same variants. Unknown values are kept in `Other`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(from = "String")]
+pub enum JobStatus {
+    Submitted,
+    Executing,
+    Completed,
+    Failed,
+    TimedOut,
+    /// Cancellation was requested but the job has not stopped yet.
+    Cancelling,
+    Cancelled,
+    Other(String),
+}
+
+impl JobStatus {
+    /// Whether the job has stopped, successfully or not.
+    pub fn is_finished(&self) -> bool {
+        matches!(
+            self,
+            JobStatus::Completed | JobStatus::Failed | JobStatus::TimedOut | JobStatus::Cancelled
+        )
+    }
+}
+
+impl std::str::FromStr for JobStatus {
+    type Err = std::convert::Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "submitted" | "esriJobNew" | "esriJobSubmitted" | "esriJobWaiting" => {
+                JobStatus::Submitted
+            }
+            "processing" | "partial" | "executing" | "esriJobExecuting" => JobStatus::Executing,
+            "completed" | "esriJobSucceeded" => JobStatus::Completed,
+            "failed" | "esriJobFailed" => JobStatus::Failed,
+            "timedOut" | "esriJobTimedOut" => JobStatus::TimedOut,
+            "cancelling" | "esriJobCancelling" => JobStatus::Cancelling,
+            "cancelled" | "esriJobCancelled" => JobStatus::Cancelled,
+            other => JobStatus::Other(other.to_string()),
+        })
+    }
+}
+
+impl From<String> for JobStatus {
+    fn from(s: String) -> Self {
+        match s.parse() {
+            Ok(status) => status,
+            Err(never) => match never {},
+        }
+    }
+}
+
+impl AddItemJob {
+    pub async fn status(&self, client: impl Into<ArcGISClient>) -> anyhow::Result<ItemStatus> {
+        let client = client.into();
+        let mut params = vec![("f", "json")];
+        if let Some(token) = &self.token {
+            params.push(("token", token));
+        }
+        let response = client
+            .get(&self.status_url)
+            .query(&params)
+            .send_retrying(&client)
+            .await?;
+        parse_response::<ItemStatus>(response).await
+    }
+
+    /// Polls every `interval` until the upload completes, returning the
+    /// item id. Fails if ArcGIS reports the upload as failed or it is still
+    /// processing after `timeout`.
+    pub async fn wait(
+        &self,
+        client: impl Into<ArcGISClient>,
+        interval: std::time::Duration,
+        timeout: std::time::Duration,
+    ) -> anyhow::Result<String> {
+        let client = client.into();
+        let poll = async {
+            loop {
+                let status = self.status(&client).await?;
+                match status.status {
+                    JobStatus::Completed => return Ok(self.id.clone()),
+                    finished if finished.is_finished() => anyhow::bail!(
+                        "Async upload of item {} failed: {}",
+                        self.id,
+                        status.status_message.unwrap_or_default()
+                    ),
+                    _ => tokio::time::sleep(interval).await,
+                }
+            }
+        };
+        tokio::time::timeout(timeout, poll)
+            .await
+            .unwrap_or_else(|_| {
+                Err(anyhow::anyhow!(
+                    "Async upload of item {} did not finish within {:?}",
+                    self.id,
+                    timeout
+                ))
+            })
+    }
 }
 
 impl AddItemQuery {
@@ -408,11 +547,20 @@ impl AddItemQuery {
         AddItemQueryBuilder::new(root, user_name)
     }
 
-    pub async fn send(&self, client: &Client) -> anyhow::Result<AddItemResponse> {
+    /// Adds the item. For an `async_upload(true)` query ArcGIS answers as
+    /// soon as the item is created, while the upload is still processed;
+    /// the response then carries the [`AddItemJob`] to poll for the final
+    /// state.
+    pub async fn send(&self, client: impl Into<ArcGISClient>) -> anyhow::Result<AddItemResponse> {
+        let client = client.into();
         let response = if self.params.needs_multipart() {
             // ---- Multipart upload ----
             let form = self.params.to_multipart()?;
-            client.post(&self.url).multipart(form).send().await?
+            client
+                .post(&self.url)
+                .multipart(form)
+                .send_retrying(&client)
+                .await?
         } else {
             // ---- URL-encoded form ----
             let body = self.params.to_urlencoded()?;
@@ -420,11 +568,19 @@ impl AddItemQuery {
                 .post(&self.url)
                 .header("Content-Type", "application/x-www-form-urlencoded")
                 .body(body)
-                .send()
+                .send_retrying(&client)
                 .await?
         };
 
-        let body = parse_response::<AddItemResponse>(response).await?;
+        let mut body = parse_response::<AddItemResponse>(response).await?;
+        if self.params.async_upload == Some(true) {
+            body.job = Some(AddItemJob {
+                status_url: format!("{}/items/{}/status", self.user_url, body.id),
+                token: self.params.token.clone(),
+                id: body.id.clone(),
+                folder: body.folder.clone(),
+            });
+        }
 
         Ok(body)
     }
@@ -434,10 +590,15 @@ impl AddItemQueryBuilder {
     pub fn new(root: impl Into<String>, user_name: impl Into<String>) -> Self {
         // https://[root]/content/users/[userName]/addItem
 
-        let url = format!("{}/content/users/{}/addItem", root.into(), user_name.into());
+        let url = format!(
+            "{}/content/users/{}",
+            root.into(),
+            urlencoding::encode(&user_name.into())
+        );
         // TODO: validtate url
         Self {
             url,
+            folder: None,
             params: AddItemParams {
                 f: "json".into(),
                 ..Default::default()
@@ -470,6 +631,15 @@ impl AddItemQueryBuilder {
         self
     }
 
+    /// Sets the type to `Web Map` and the item's `text` to `web_map`, from
+    /// [`crate::web_map::WebMapBuilder::build`].
+    pub fn web_map(mut self, web_map: &WebMapJson) -> Self {
+        self.params.r#type = "Web Map".into();
+        self.params.text =
+            Some(serde_json::to_string(web_map).expect("web map JSON always serializes"));
+        self
+    }
+
     pub fn relationship_types(mut self, relationship_types: impl Into<String>) -> Self {
         self.params.relationship_types = Some(relationship_types.into());
         self
@@ -515,6 +685,26 @@ impl AddItemQueryBuilder {
         self
     }
 
+    pub fn type_keywords(mut self, type_keywords: Vec<String>) -> Self {
+        self.params.type_keywords = Some(type_keywords.join(","));
+        self
+    }
+
+    pub fn description(mut self, description: impl Into<String>) -> Self {
+        self.params.description = Some(description.into());
+        self
+    }
+
+    pub fn tags(mut self, tags: Vec<String>) -> Self {
+        self.params.tags = Some(tags.join(","));
+        self
+    }
+
+    pub fn snippet(mut self, snippet: impl Into<String>) -> Self {
+        self.params.snippet = Some(snippet.into());
+        self
+    }
+
     pub fn thumbnail(mut self, thumbnail: impl Into<PathBuf>) -> Self {
         self.params.thumbnail = Some(thumbnail.into());
         self
@@ -560,20 +750,32 @@ impl AddItemQueryBuilder {
         self
     }
 
+    /// Targets the user folder `folder_id` instead of the root folder,
+    /// i.e. `content/users/[userName]/[folderId]/addItem`.
+    pub fn folder(mut self, folder_id: impl Into<String>) -> Self {
+        self.folder = Some(folder_id.into());
+        self
+    }
+
     pub fn token(mut self, token: impl Into<String>) -> Self {
         self.params.token = Some(token.into());
         self
     }
 
     pub fn build(self) -> AddItemQuery {
+        let url = match &self.folder {
+            Some(folder) => format!("{}/{}/addItem", self.url, urlencoding::encode(folder)),
+            None => format!("{}/addItem", self.url),
+        };
         let url = if let Some(token) = &self.params.token {
-            format!("{}?token={}", self.url, token)
+            format!("{}?token={}", url, token)
         } else {
-            self.url
+            url
         };
 
         AddItemQuery {
             url,
+            user_url: self.url,
             params: self.params,
         }
     }
@@ -0,0 +1,165 @@
+use reqwest::multipart::{Form, Part};
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::HashMap;
+
+use crate::publish_item::FileType;
+use crate::{client::ArcGISClient, parser::parse_response, retry::SendWithRetry};
+
+/// Analyzes a local file with `/content/features/analyze` without adding
+/// it as an item first, to preview how ArcGIS will interpret it.
+pub struct AnalyzeQuery {
+    url: String,
+    params: HashMap<String, String>,
+    file: Vec<u8>,
+    file_type: FileType,
+}
+
+pub struct AnalyzeQueryBuilder {
+    url: String,
+    file: Vec<u8>,
+    file_type: FileType,
+    analyze_parameters: Option<Value>,
+    token: Option<String>,
+}
+
+/// What ArcGIS inferred from the file.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AnalyzeResponse {
+    /// Suggested `publishParameters`, including `layerInfo` with the
+    /// detected fields and geometry type.
+    pub publish_parameters: Value,
+    /// Sample records as parsed by the server.
+    #[serde(default)]
+    pub records: Vec<Value>,
+}
+
+/// The `layerInfo` of [`AnalyzeResponse::publish_parameters`].
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AnalyzedLayerInfo {
+    /// e.g. `esriGeometryPoint`; absent for tables.
+    pub geometry_type: Option<String>,
+    #[serde(default)]
+    pub fields: Vec<AnalyzedField>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AnalyzedField {
+    pub name: String,
+    /// e.g. `esriFieldTypeDouble`.
+    #[serde(rename = "type")]
+    pub r#type: String,
+    pub alias: Option<String>,
+    pub sql_type: Option<String>,
+    /// `latitude` or `longitude` for detected coordinate fields.
+    pub location_type: Option<String>,
+}
+
+impl AnalyzeResponse {
+    pub fn layer_info(&self) -> anyhow::Result<AnalyzedLayerInfo> {
+        let layer_info = self
+            .publish_parameters
+            .get("layerInfo")
+            .ok_or_else(|| anyhow::anyhow!("Analyze response has no layerInfo"))?;
+        Ok(serde_json::from_value(layer_info.clone())?)
+    }
+
+    /// The field detected as `location_type` (`latitude` or `longitude`).
+    pub fn coordinate_field(&self, location_type: &str) -> Option<String> {
+        self.layer_info()
+            .ok()?
+            .fields
+            .into_iter()
+            .find(|f| f.location_type.as_deref() == Some(location_type))
+            .map(|f| f.name)
+    }
+}
+
+impl AnalyzeQuery {
+    pub fn from_file(
+        root: impl Into<String>,
+        file: impl Into<Vec<u8>>,
+        file_type: FileType,
+    ) -> AnalyzeQueryBuilder {
+        AnalyzeQueryBuilder::new(root, file, file_type)
+    }
+
+    pub async fn send(&self, client: impl Into<ArcGISClient>) -> anyhow::Result<AnalyzeResponse> {
+        let client = client.into();
+        let file_name = format!("upload.{}", extension(self.file_type));
+        let mut form =
+            Form::new().part("file", Part::bytes(self.file.clone()).file_name(file_name));
+        for (key, value) in &self.params {
+            form = form.text(key.clone(), value.clone());
+        }
+
+        let response = client
+            .post(&self.url)
+            .multipart(form)
+            .send_retrying(&client)
+            .await?;
+        let body = parse_response::<AnalyzeResponse>(response).await?;
+        Ok(body)
+    }
+}
+
+fn extension(file_type: FileType) -> &'static str {
+    match file_type {
+        FileType::Csv => "csv",
+        FileType::Shapefile | FileType::FileGeodatabase => "zip",
+        FileType::GeoJson => "geojson",
+        FileType::ServiceDefinition => "sd",
+    }
+}
+
+impl AnalyzeQueryBuilder {
+    pub fn new(root: impl Into<String>, file: impl Into<Vec<u8>>, file_type: FileType) -> Self {
+        // https://[root]/content/features/analyze
+
+        let url = format!("{}/content/features/analyze", root.into());
+        Self {
+            url,
+            file: file.into(),
+            file_type,
+            analyze_parameters: None,
+            token: None,
+        }
+    }
+
+    /// `analyzeParameters`, e.g. `{"locationType": "coordinates"}` to skip
+    /// geocoding a CSV.
+    pub fn analyze_parameters(mut self, analyze_parameters: Value) -> Self {
+        self.analyze_parameters = Some(analyze_parameters);
+        self
+    }
+
+    pub fn token(mut self, token: impl Into<String>) -> Self {
+        self.token = Some(token.into());
+        self
+    }
+
+    pub fn build(self) -> AnalyzeQuery {
+        let mut params = HashMap::new();
+        params.insert("filetype".into(), self.file_type.as_str().into());
+        if let Some(analyze_parameters) = self.analyze_parameters {
+            params.insert("analyzeParameters".into(), analyze_parameters.to_string());
+        }
+        params.insert("f".into(), "json".into());
+
+        let url = if let Some(token) = &self.token {
+            format!("{}?token={}", self.url, token)
+        } else {
+            self.url
+        };
+
+        AnalyzeQuery {
+            url,
+            params,
+            file: self.file,
+            file_type: self.file_type,
+        }
+    }
+}
@@ -7,11 +7,28 @@ use std::{
 };
 use tokio::sync::{Mutex, RwLock};
 
-use crate::{config::Settings, parser::parse_response};
+use crate::{
+    client::{ArcGISClient, ArcGISClientBuilder},
+    config::Settings,
+    parser::{parse_response, EsriErrorResponse},
+    retry::SendWithRetry,
+};
 
 pub enum AuthType {
     TestToken,
     AppAuth,
+    /// A long-lived API key (e.g. for ArcGIS Location Platform). Sent like a
+    /// token, but never refreshed.
+    ApiKey(SecretString),
+    /// Acting on behalf of a signed-in user: access tokens are requested
+    /// with the refresh token from an authorization code sign-in, cached and
+    /// refreshed when they expire.
+    OAuthRefresh {
+        client_id: String,
+        refresh_token: SecretString,
+    },
+    /// Anonymous access to public content: no `X-Esri-Authorization` header
+    /// is sent and no token is ever requested.
     None,
 }
 
@@ -27,41 +44,161 @@ pub struct EsriTokenResponse {
     pub expires_in: u32,
 }
 
-pub async fn token_is_valid(client: &Client, portal: &str, token: &str) -> bool {
+pub async fn token_is_valid(client: impl Into<ArcGISClient>, portal: &str, token: &str) -> bool {
+    let client = client.into();
     let url = format!("{}/community/self?f=json&token={}", portal, token);
 
-    let resp = client
-        .get(url)
-        .send()
-        .await
-        .expect("failed to send request");
+    let Ok(resp) = client.get(url).send_retrying(&client).await else {
+        return false;
+    };
 
     let json = resp.json::<AuthResponse>().await;
 
     json.is_ok()
 }
 
+/// Exchanges a refresh token for a new access token (`grant_type=refresh_token`).
+///
+/// ArcGIS keeps the refresh token valid, so it can be reused until it expires;
+/// `expires_in` on the response is the lifetime of the access token only.
+pub async fn refresh_access_token(
+    client: impl Into<ArcGISClient>,
+    portal: &str,
+    client_id: &str,
+    refresh_token: &SecretString,
+) -> anyhow::Result<EsriTokenResponse> {
+    let client = client.into();
+    let token_endpoint = format!("{}/oauth2/token", portal);
+    let mut form: HashMap<&str, &str> = HashMap::new();
+    form.insert("f", "json");
+    form.insert("client_id", client_id);
+    form.insert("grant_type", "refresh_token");
+    form.insert("refresh_token", refresh_token.expose_secret());
+
+    let response = client
+        .send_unsigned(client.post(token_endpoint).form(&form), true)
+        .await?;
+    parse_response::<EsriTokenResponse>(response).await
+}
+
+/// Tokens from an authorization code exchange (`grant_type=authorization_code`).
+#[derive(Deserialize)]
+pub struct EsriCodeTokenResponse {
+    pub access_token: SecretString,
+    pub expires_in: u32,
+    /// Kept by [`EsriCodeTokenResponse::into_token_manager`] (or pass to
+    /// [`refresh_access_token`]) to get new access tokens without signing
+    /// in again.
+    pub refresh_token: SecretString,
+    pub username: Option<String>,
+}
+
+impl EsriCodeTokenResponse {
+    /// A token manager that starts with this access token and, once it
+    /// expires, refreshes it with the refresh token, so requests made
+    /// through [`ArcGISTokenManager::with_token`] stay signed in.
+    pub fn into_token_manager(
+        self,
+        client: &Client,
+        portal: &str,
+        client_id: &str,
+    ) -> ArcGISTokenManager {
+        let token = ArcGISAccessToken {
+            value: self.access_token.expose_secret().to_string(),
+            expires: Instant::now() + Duration::from_secs(self.expires_in.into()),
+        };
+        let manager = ArcGISTokenManager::oauth_refresh(OAuthRefreshProvider {
+            client: client.clone(),
+            portal: portal.to_string(),
+            client_id: client_id.to_string(),
+            refresh_token: self.refresh_token,
+        });
+        ArcGISTokenManager {
+            state: RwLock::new(ArcGISTokenState { token: Some(token) }),
+            ..manager
+        }
+    }
+}
+
+/// The `oauth2/authorize` URL to send a user to for signing in. ArcGIS
+/// redirects back to `redirect_uri` with a `code` for [`exchange_code`].
+/// `scopes` are sent space separated, and left out when empty.
+pub fn authorize_url(
+    portal: &str,
+    client_id: &str,
+    redirect_uri: &str,
+    scopes: &[impl AsRef<str>],
+) -> String {
+    let mut url = format!(
+        "{}/oauth2/authorize?client_id={}&response_type=code&redirect_uri={}",
+        portal,
+        urlencoding::encode(client_id),
+        urlencoding::encode(redirect_uri)
+    );
+    if !scopes.is_empty() {
+        let scopes: Vec<&str> = scopes.iter().map(AsRef::as_ref).collect();
+        url.push_str(&format!(
+            "&scope={}",
+            urlencoding::encode(&scopes.join(" "))
+        ));
+    }
+    url
+}
+
+/// Exchanges the `code` from an authorization code sign-in for an access and
+/// refresh token pair. `redirect_uri` must match the one used to authorize.
+pub async fn exchange_code(
+    client: impl Into<ArcGISClient>,
+    portal: &str,
+    client_id: &str,
+    redirect_uri: &str,
+    code: &str,
+) -> anyhow::Result<EsriCodeTokenResponse> {
+    let client = client.into();
+    let token_endpoint = format!("{}/oauth2/token", portal);
+    let mut form: HashMap<&str, &str> = HashMap::new();
+    form.insert("f", "json");
+    form.insert("client_id", client_id);
+    form.insert("grant_type", "authorization_code");
+    form.insert("code", code);
+    form.insert("redirect_uri", redirect_uri);
+
+    let response = client
+        .send_unsigned(client.post(token_endpoint).form(&form), false)
+        .await?;
+    parse_response::<EsriCodeTokenResponse>(response).await
+}
+
 impl Settings {
-    pub async fn generate_access_token(&self, client: &Client) -> anyhow::Result<SecretString> {
+    pub async fn generate_access_token(
+        &self,
+        client: impl Into<ArcGISClient>,
+    ) -> anyhow::Result<SecretString> {
+        let client = client.into();
         let token_endpoint = format!("{}/oauth2/token", self.portal_root);
         let mut form: HashMap<&str, &str> = HashMap::new();
         let _ = &form.insert("f", "json");
         let _ = &form.insert("client_id", &self.client_id);
-        let _ = &form.insert("client_secret", &self.client_secret.expose_secret());
+        let _ = &form.insert("client_secret", self.client_secret.expose_secret());
         let _ = &form.insert("grant_type", "client_credentials");
         let _ = &form.insert("expiration", &self.token_expiration);
 
-        let response = client.post(token_endpoint).form(&form).send().await?;
+        let response = client
+            .send_unsigned(client.post(token_endpoint).form(&form), true)
+            .await?;
         let result = parse_response::<EsriTokenResponse>(response).await?;
 
         //Ok(Secret::new(result.access_token))
         Ok(result.access_token)
     }
 
+    /// A client for this portal. The token or key is sent as
+    /// `X-Esri-Authorization` with requests to `portal_root` and
+    /// `services_root` only.
     pub async fn build_authorized_request_client(
         &self,
         app_auth: AuthType,
-    ) -> anyhow::Result<Client> {
+    ) -> anyhow::Result<ArcGISClient> {
         let token = match app_auth {
             AuthType::TestToken => {
                 let token = std::env::var("APP_TEST_TOKEN")
@@ -71,16 +208,44 @@ impl Settings {
                     .unwrap_or(SecretString::new(token.into()))
             }
             AuthType::AppAuth => self.generate_access_token(&Client::new()).await?,
-            AuthType::None => return Ok(Client::new()),
+            AuthType::ApiKey(key) => key,
+            AuthType::OAuthRefresh {
+                client_id,
+                refresh_token,
+            } => {
+                return Ok(self
+                    .client_builder()?
+                    .oauth_refresh_auth(client_id, refresh_token)
+                    .build())
+            }
+            AuthType::None => return Ok(self.client_builder()?.build()),
         };
 
+        Ok(self.client_builder()?.api_key(token).build())
+    }
+
+    fn client_builder(&self) -> anyhow::Result<ArcGISClientBuilder> {
         let mut headers = header::HeaderMap::new();
-        let mut auth_value =
-            header::HeaderValue::from_str(&format!("Bearer {}", token.expose_secret()))?;
-        auth_value.set_sensitive(true);
-        headers.insert("X-Esri-Authorization", auth_value.clone());
+        if let Some(language) = &self.accept_language {
+            headers.insert(
+                header::ACCEPT_LANGUAGE,
+                header::HeaderValue::from_str(language)?,
+            );
+        }
 
-        Ok(Client::builder().default_headers(headers).build()?)
+        let builder = Client::builder().default_headers(headers);
+        #[cfg(feature = "dangerous-config")]
+        let builder = if self.danger_accept_invalid_certs {
+            tracing::warn!("TLS certificate validation is disabled; do not use in production");
+            builder.danger_accept_invalid_certs(true)
+        } else {
+            builder
+        };
+
+        Ok(ArcGISClient::builder()
+            .http_client(builder.build()?)
+            .portal_root(&self.portal_root)
+            .services_root(&self.services_root))
     }
 }
 
@@ -170,11 +335,12 @@ impl ArcGISProvider {
         params.insert("expiration", self.expiration.to_string());
         params.insert("f", "json".to_string());
 
-        let response = self
+        let request = self
             .client
             .post(format!("{}/sharing/rest/generateToken", self.portal))
-            .form(&params)
-            .send()
+            .form(&params);
+        let response = ArcGISClient::from(&self.client)
+            .send_unsigned(request, true)
             .await?
             .json::<TokenResponse>()
             .await?;
@@ -198,6 +364,47 @@ impl ArcGISProvider {
     }
 }
 
+/// Gets access tokens for a signed-in user by exchanging the refresh token
+/// from an authorization code sign-in (`grant_type=refresh_token`).
+pub struct OAuthRefreshProvider {
+    pub client: reqwest::Client,
+    pub portal: String,
+    pub client_id: String,
+    pub refresh_token: SecretString,
+}
+
+impl OAuthRefreshProvider {
+    pub async fn fetch_token(&self) -> anyhow::Result<(String, Duration)> {
+        tracing::info!(portal = %self.portal, "Refreshing OAuth access token");
+        let response = refresh_access_token(
+            &self.client,
+            &self.portal,
+            &self.client_id,
+            &self.refresh_token,
+        )
+        .await?;
+        Ok((
+            response.access_token.expose_secret().to_string(),
+            Duration::from_secs(response.expires_in.into()),
+        ))
+    }
+}
+
+/// Where an [`ArcGISTokenManager`] gets its tokens.
+enum TokenSource {
+    GenerateToken(ArcGISProvider),
+    OAuthRefresh(OAuthRefreshProvider),
+}
+
+impl TokenSource {
+    async fn fetch_token(&self) -> anyhow::Result<(String, Duration)> {
+        match self {
+            TokenSource::GenerateToken(provider) => provider.fetch_token().await,
+            TokenSource::OAuthRefresh(provider) => provider.fetch_token().await,
+        }
+    }
+}
+
 #[derive(Default)]
 struct ArcGISTokenState {
     token: Option<ArcGISAccessToken>,
@@ -207,11 +414,22 @@ pub struct ArcGISTokenManager {
     state: RwLock<ArcGISTokenState>,
     refresh_gate: Mutex<()>,
     refresh_skew: Duration,
-    provider: ArcGISProvider,
+    provider: TokenSource,
 }
 
 impl ArcGISTokenManager {
     pub fn new(provider: ArcGISProvider) -> Self {
+        Self::with_source(TokenSource::GenerateToken(provider))
+    }
+
+    /// A manager acting on behalf of a signed-in user: access tokens come
+    /// from the refresh token and are cached and refreshed on expiry like
+    /// generated tokens.
+    pub fn oauth_refresh(provider: OAuthRefreshProvider) -> Self {
+        Self::with_source(TokenSource::OAuthRefresh(provider))
+    }
+
+    fn with_source(provider: TokenSource) -> Self {
         Self {
             state: RwLock::new(ArcGISTokenState::default()),
             refresh_gate: Mutex::new(()),
@@ -220,6 +438,14 @@ impl ArcGISTokenManager {
         }
     }
 
+    /// Like [`ArcGISTokenManager::new`], but fetches the first token up
+    /// front so bad credentials fail here instead of on the first request.
+    pub async fn authenticated(provider: ArcGISProvider) -> anyhow::Result<Self> {
+        let manager = Self::new(provider);
+        manager.warmup().await?;
+        Ok(manager)
+    }
+
     pub fn with_skew(mut self, skew: Duration) -> Self {
         self.refresh_skew = skew;
         self
@@ -285,21 +511,72 @@ impl ArcGISTokenManager {
         Ok(())
     }
 
+    /// Drops the cached token so the next [`ArcGISTokenManager::get`] fetches
+    /// a new one.
+    pub async fn invalidate(&self) {
+        tracing::info!("Invalidating cached ArcGIS token");
+        self.state.write().await.token = None;
+    }
+
+    /// Runs `request` with the current token. If ArcGIS rejects the token
+    /// (an HTTP 401, or `498`/`499` errors, which usually arrive with HTTP
+    /// 200), the token is invalidated and the request is retried once with a
+    /// fresh one.
+    pub async fn with_token<F, Fut, T>(&self, request: F) -> anyhow::Result<T>
+    where
+        F: Fn(String) -> Fut,
+        Fut: std::future::Future<Output = anyhow::Result<T>>,
+    {
+        let token = self.get().await?;
+        match request(token.clone()).await {
+            Err(e) if is_token_error(&e) => {
+                tracing::warn!(error = %e, "Token rejected, retrying with a fresh token");
+                self.invalidate_if_current(&token).await;
+                request(self.get().await?).await
+            }
+            result => result,
+        }
+    }
+
+    /// Only invalidates when `token` is still cached, so concurrent callers
+    /// that hit the same rejected token trigger a single refresh.
+    async fn invalidate_if_current(&self, token: &str) {
+        let mut w = self.state.write().await;
+        if w.token.as_ref().is_some_and(|t| t.value == token) {
+            w.token = None;
+        }
+    }
+
     /// Optional: warm-up at startup.
     pub async fn warmup(&self) -> anyhow::Result<()> {
         tracing::info!("Warming up token manager");
+        self.refresh().await
+    }
 
+    /// Fetches a new token now, even if the cached one is still valid, e.g.
+    /// so a long batch job starts with a token's full lifetime.
+    pub async fn refresh(&self) -> anyhow::Result<()> {
         let _gate = self.refresh_gate.lock().await;
         let (value, ttl) = self.provider.fetch_token().await?;
         let expires = Instant::now() + ttl;
 
-        tracing::info!(
-            ttl_seconds = ttl.as_secs(),
-            "Warmup complete, initial token cached"
-        );
+        tracing::info!(ttl_seconds = ttl.as_secs(), "Token refreshed and cached");
 
         let mut w = self.state.write().await;
         w.token = Some(ArcGISAccessToken { value, expires });
         Ok(())
     }
+
+    /// When the cached token expires, or `None` if no token is cached.
+    pub async fn token_expiration(&self) -> Option<SystemTime> {
+        let guard = self.state.read().await;
+        let tok = guard.token.as_ref()?;
+        Some(SystemTime::now() + tok.time_until_expiry())
+    }
+}
+
+fn is_token_error(error: &anyhow::Error) -> bool {
+    error
+        .downcast_ref::<EsriErrorResponse>()
+        .is_some_and(|e| e.error.is_token_error())
 }
@@ -0,0 +1,395 @@
+use std::sync::Arc;
+
+use reqwest::{
+    header::{HeaderName, HeaderValue},
+    Client, IntoUrl, Method, RequestBuilder, Response, Url,
+};
+use secrecy::{ExposeSecret, SecretString};
+use tokio::sync::RwLock;
+
+use crate::{
+    auth::{self, ArcGISTokenManager, OAuthRefreshProvider},
+    retry::RetryPolicy,
+};
+
+/// The header ArcGIS reads tokens and API keys from.
+pub const AUTHORIZATION_HEADER: HeaderName = HeaderName::from_static("x-esri-authorization");
+
+/// The HTTP client the crate's queries send through: a [`reqwest::Client`],
+/// the [`RetryPolicy`] its requests are retried with and, optionally, the
+/// credential it signs them with.
+///
+/// Queries take anything that converts into one, so a plain
+/// [`reqwest::Client`] still works and is retried with
+/// [`RetryPolicy::default`]. Build one with [`ArcGISClient::builder`] to
+/// choose the policy per client, e.g. [`RetryPolicy::none`] in tests.
+///
+/// The credential is attached per request as `X-Esri-Authorization`, and
+/// only to requests for the portal (or services root) the client was built
+/// for, so a basemap or third-party URL never sees it.
+#[derive(Debug, Clone)]
+pub struct ArcGISClient {
+    http: Client,
+    retry_policy: RetryPolicy,
+    auth: AuthState,
+    portal_root: String,
+    authorities: Vec<Url>,
+}
+
+/// How an [`ArcGISClient`] signs requests to its portal.
+#[derive(Clone, Default)]
+enum AuthState {
+    #[default]
+    None,
+    /// An API key, or any other token that is sent as is and never
+    /// refreshed.
+    ApiKey(SecretString),
+    /// Tokens from a manager, cached and refreshed when they expire.
+    Managed(Arc<ArcGISTokenManager>),
+    /// An authorization code sign-in; signed once the code is exchanged.
+    OAuth(Arc<OAuthState>),
+}
+
+struct OAuthState {
+    client_id: String,
+    redirect_uri: String,
+    scopes: Vec<String>,
+    /// Refreshes with the refresh token from [`ArcGISClient::exchange_code`].
+    manager: RwLock<Option<ArcGISTokenManager>>,
+}
+
+impl std::fmt::Debug for AuthState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AuthState::None => f.write_str("None"),
+            AuthState::ApiKey(_) => f.write_str("ApiKey([REDACTED])"),
+            AuthState::Managed(_) => f.write_str("Managed"),
+            AuthState::OAuth(oauth) => f
+                .debug_struct("OAuth")
+                .field("client_id", &oauth.client_id)
+                .field("redirect_uri", &oauth.redirect_uri)
+                .field("scopes", &oauth.scopes)
+                .finish_non_exhaustive(),
+        }
+    }
+}
+
+/// The portal a client signs requests for when none is given.
+pub const ARCGIS_ONLINE_ROOT: &str = "https://www.arcgis.com/sharing/rest";
+
+#[derive(Default)]
+pub struct ArcGISClientBuilder {
+    http: Option<Client>,
+    retry_policy: RetryPolicy,
+    auth: AuthConfig,
+    portal_root: Option<String>,
+    services_roots: Vec<String>,
+}
+
+/// The credential an [`ArcGISClientBuilder`] was given; OAuth needs the
+/// HTTP client and portal, so its token manager is created in `build`.
+#[derive(Default)]
+enum AuthConfig {
+    #[default]
+    None,
+    ApiKey(SecretString),
+    Managed(Arc<ArcGISTokenManager>),
+    OAuthRefresh {
+        client_id: String,
+        refresh_token: SecretString,
+    },
+    OAuth {
+        client_id: String,
+        redirect_uri: String,
+        scopes: Vec<String>,
+    },
+}
+
+impl ArcGISClient {
+    pub fn builder() -> ArcGISClientBuilder {
+        ArcGISClientBuilder::new()
+    }
+
+    pub fn http(&self) -> &Client {
+        &self.http
+    }
+
+    pub fn retry_policy(&self) -> &RetryPolicy {
+        &self.retry_policy
+    }
+
+    pub fn get<U: IntoUrl>(&self, url: U) -> RequestBuilder {
+        self.http.get(url)
+    }
+
+    pub fn post<U: IntoUrl>(&self, url: U) -> RequestBuilder {
+        self.http.post(url)
+    }
+
+    pub fn request<U: IntoUrl>(&self, method: Method, url: U) -> RequestBuilder {
+        self.http.request(method, url)
+    }
+
+    /// Sends `request` (built from this client), retrying it according to
+    /// the client's policy.
+    pub async fn execute(&self, request: RequestBuilder) -> anyhow::Result<Response> {
+        self.send(request, false).await
+    }
+
+    pub(crate) async fn send(
+        &self,
+        request: RequestBuilder,
+        idempotent: bool,
+    ) -> anyhow::Result<Response> {
+        let mut request = request.build()?;
+        if let Some(value) = self.credential(request.url()).await? {
+            request.headers_mut().insert(AUTHORIZATION_HEADER, value);
+        }
+        self.retry_policy
+            .send(&self.http, request, idempotent)
+            .await
+    }
+
+    /// Sends a token request. Those carry their credentials in the form, so
+    /// they are never signed (which would also have the token manager
+    /// request tokens through itself).
+    pub(crate) async fn send_unsigned(
+        &self,
+        request: RequestBuilder,
+        idempotent: bool,
+    ) -> anyhow::Result<Response> {
+        self.retry_policy
+            .send(&self.http, request.build()?, idempotent)
+            .await
+    }
+
+    /// [`ArcGISClient::execute`] for senders that hand back the raw
+    /// [`reqwest::Result`]. A token that can't be fetched is logged and the
+    /// request sent without it, so ArcGIS answers with its own token error.
+    pub(crate) async fn send_raw(&self, request: RequestBuilder) -> reqwest::Result<Response> {
+        let mut request = request.build()?;
+        match self.credential(request.url()).await {
+            Ok(Some(value)) => {
+                request.headers_mut().insert(AUTHORIZATION_HEADER, value);
+            }
+            Ok(None) => {}
+            Err(e) => tracing::warn!(error = %e, "Sending ArcGIS request without a token"),
+        }
+        self.retry_policy.execute(&self.http, request).await
+    }
+
+    /// The `oauth2/authorize` URL to send the user to, for a client built
+    /// with [`ArcGISClientBuilder::oauth_auth`]. ArcGIS redirects back to the
+    /// redirect URI with the `code` for [`ArcGISClient::exchange_code`].
+    pub fn authorize_url(&self) -> anyhow::Result<String> {
+        let oauth = self.oauth()?;
+        Ok(auth::authorize_url(
+            &self.portal_root,
+            &oauth.client_id,
+            &oauth.redirect_uri,
+            &oauth.scopes,
+        ))
+    }
+
+    /// Exchanges the `code` from the sign-in for an access and refresh token
+    /// pair. From then on requests are signed, and the access token is
+    /// refreshed with the refresh token when it expires.
+    pub async fn exchange_code(&self, code: &str) -> anyhow::Result<()> {
+        let oauth = self.oauth()?;
+        let response = auth::exchange_code(
+            self,
+            &self.portal_root,
+            &oauth.client_id,
+            &oauth.redirect_uri,
+            code,
+        )
+        .await?;
+        let manager = response.into_token_manager(&self.http, &self.portal_root, &oauth.client_id);
+        *oauth.manager.write().await = Some(manager);
+        Ok(())
+    }
+
+    fn oauth(&self) -> anyhow::Result<&OAuthState> {
+        match &self.auth {
+            AuthState::OAuth(oauth) => Ok(oauth),
+            _ => anyhow::bail!("The client was not built with oauth_auth"),
+        }
+    }
+
+    /// Whether `url` is on the portal (or services root) this client signs
+    /// requests for: same scheme, host and port.
+    pub fn is_trusted(&self, url: &Url) -> bool {
+        self.authorities.iter().any(|root| {
+            root.scheme() == url.scheme()
+                && root.host_str() == url.host_str()
+                && root.port_or_known_default() == url.port_or_known_default()
+        })
+    }
+
+    /// The `X-Esri-Authorization` value for a request to `url`, if it goes
+    /// to a trusted authority and the client has a credential.
+    async fn credential(&self, url: &Url) -> anyhow::Result<Option<HeaderValue>> {
+        if !self.is_trusted(url) {
+            return Ok(None);
+        }
+        let token = match &self.auth {
+            AuthState::None => return Ok(None),
+            AuthState::ApiKey(key) => key.expose_secret().to_string(),
+            AuthState::Managed(manager) => manager.get().await?,
+            AuthState::OAuth(oauth) => match oauth.manager.read().await.as_ref() {
+                Some(manager) => manager.get().await?,
+                None => anyhow::bail!("Not signed in yet; call ArcGISClient::exchange_code first"),
+            },
+        };
+        let mut value = HeaderValue::from_str(&format!("Bearer {}", token))?;
+        value.set_sensitive(true);
+        Ok(Some(value))
+    }
+}
+
+impl From<Client> for ArcGISClient {
+    fn from(http: Client) -> Self {
+        ArcGISClientBuilder::new().http_client(http).build()
+    }
+}
+
+impl From<&Client> for ArcGISClient {
+    fn from(http: &Client) -> Self {
+        http.clone().into()
+    }
+}
+
+impl From<&ArcGISClient> for ArcGISClient {
+    fn from(client: &ArcGISClient) -> Self {
+        client.clone()
+    }
+}
+
+impl ArcGISClientBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The underlying client, e.g. one with default headers or timeouts.
+    pub fn http_client(mut self, http: Client) -> Self {
+        self.http = Some(http);
+        self
+    }
+
+    /// How this client's requests are retried (default:
+    /// [`RetryPolicy::default`]).
+    pub fn retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// The portal requests are signed for (default: [`ARCGIS_ONLINE_ROOT`]).
+    /// Only its scheme, host and port are compared.
+    pub fn portal_root(mut self, root: impl Into<String>) -> Self {
+        self.portal_root = Some(root.into());
+        self
+    }
+
+    /// Another root the credential is sent to, e.g. the server hosting the
+    /// organization's feature services.
+    pub fn services_root(mut self, root: impl Into<String>) -> Self {
+        self.services_roots.push(root.into());
+        self
+    }
+
+    /// Signs portal requests with a long-lived API key, e.g. for ArcGIS
+    /// Location Platform. The key is never refreshed.
+    pub fn api_key(mut self, key: impl Into<SecretString>) -> Self {
+        self.auth = AuthConfig::ApiKey(key.into());
+        self
+    }
+
+    /// Signs portal requests with tokens from `manager`, refreshed when they
+    /// expire.
+    pub fn token_manager(mut self, manager: ArcGISTokenManager) -> Self {
+        self.auth = AuthConfig::Managed(Arc::new(manager));
+        self
+    }
+
+    /// Signs portal requests on behalf of a signed-in user: access tokens
+    /// come from the portal's `oauth2/token` with `grant_type=refresh_token`
+    /// and are cached until their `expires_in` runs out.
+    pub fn oauth_refresh_auth(
+        mut self,
+        client_id: impl Into<String>,
+        refresh_token: impl Into<SecretString>,
+    ) -> Self {
+        self.auth = AuthConfig::OAuthRefresh {
+            client_id: client_id.into(),
+            refresh_token: refresh_token.into(),
+        };
+        self
+    }
+
+    /// Signs portal requests as a user who signs in with the authorization
+    /// code flow: send them to [`ArcGISClient::authorize_url`], then pass the
+    /// code ArcGIS redirects back with to [`ArcGISClient::exchange_code`].
+    pub fn oauth_auth<S: Into<String>>(
+        mut self,
+        client_id: impl Into<String>,
+        redirect_uri: impl Into<String>,
+        scopes: impl IntoIterator<Item = S>,
+    ) -> Self {
+        self.auth = AuthConfig::OAuth {
+            client_id: client_id.into(),
+            redirect_uri: redirect_uri.into(),
+            scopes: scopes.into_iter().map(Into::into).collect(),
+        };
+        self
+    }
+
+    pub fn build(self) -> ArcGISClient {
+        let http = self.http.unwrap_or_default();
+        let portal_root = self
+            .portal_root
+            .unwrap_or_else(|| ARCGIS_ONLINE_ROOT.to_string());
+        let authorities = std::iter::once(&portal_root)
+            .chain(&self.services_roots)
+            .filter_map(|root| match Url::parse(root) {
+                Ok(url) => Some(url),
+                Err(e) => {
+                    tracing::warn!(%root, error = %e, "Ignoring invalid portal root");
+                    None
+                }
+            })
+            .collect();
+        let auth = match self.auth {
+            AuthConfig::None => AuthState::None,
+            AuthConfig::ApiKey(key) => AuthState::ApiKey(key),
+            AuthConfig::Managed(manager) => AuthState::Managed(manager),
+            AuthConfig::OAuthRefresh {
+                client_id,
+                refresh_token,
+            } => AuthState::Managed(Arc::new(ArcGISTokenManager::oauth_refresh(
+                OAuthRefreshProvider {
+                    client: http.clone(),
+                    portal: portal_root.clone(),
+                    client_id,
+                    refresh_token,
+                },
+            ))),
+            AuthConfig::OAuth {
+                client_id,
+                redirect_uri,
+                scopes,
+            } => AuthState::OAuth(Arc::new(OAuthState {
+                client_id,
+                redirect_uri,
+                scopes,
+                manager: RwLock::new(None),
+            })),
+        };
+        ArcGISClient {
+            http,
+            retry_policy: self.retry_policy,
+            auth,
+            portal_root,
+            authorities,
+        }
+    }
+}
@@ -12,6 +12,15 @@ pub struct Settings {
     pub token_expiration: String,
     pub test_token: Option<SecretString>,
     pub test_user_name: Option<String>,
+    /// Sent as `Accept-Language` so portal messages come back localized,
+    /// e.g. `es` or `fr-CA`.
+    pub accept_language: Option<String>,
+    /// Skips TLS certificate validation, for a local ArcGIS Enterprise with
+    /// a self-signed certificate. Development only: anyone on the network
+    /// can then impersonate the portal and read your tokens.
+    #[cfg(feature = "dangerous-config")]
+    #[serde(default)]
+    pub danger_accept_invalid_certs: bool,
 
     pub arcgis_portal: String,
     pub arcgis_api_root: String,
@@ -0,0 +1,129 @@
+use std::collections::HashMap;
+
+use serde::Deserialize;
+
+use crate::{
+    client::ArcGISClient,
+    parser::{parse_response, EsriErrorValue},
+    retry::SendWithRetry,
+};
+
+/// Deletes several items in a single `/content/users/[userName]/deleteItems`
+/// request. ArcGIS reports the outcome per item, so some items may be
+/// deleted while others (protected, not owned) survive.
+pub struct DeleteItemsQuery {
+    url: String,
+    params: HashMap<String, String>,
+}
+
+#[derive(Default)]
+pub struct DeleteItemsQueryBuilder {
+    url: String,
+    item_ids: Vec<String>,
+    permanent_delete: bool,
+    token: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DeleteItemsResponse {
+    pub results: Vec<DeleteItemResult>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeleteItemResult {
+    pub item_id: String,
+    pub success: bool,
+    pub error: Option<EsriErrorValue>,
+}
+
+/// The response of a single item's `/delete`.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeleteItemResponse {
+    pub success: bool,
+    pub item_id: String,
+    pub folder: Option<String>,
+}
+
+impl DeleteItemsResponse {
+    /// Items that were not deleted.
+    pub fn failed(&self) -> impl Iterator<Item = &DeleteItemResult> {
+        self.results.iter().filter(|r| !r.success)
+    }
+}
+
+impl DeleteItemsQuery {
+    pub fn builder(
+        root: impl Into<String>,
+        user_name: impl Into<String>,
+        item_ids: Vec<String>,
+    ) -> DeleteItemsQueryBuilder {
+        DeleteItemsQueryBuilder::new(root, user_name, item_ids)
+    }
+
+    pub async fn send(
+        &self,
+        client: impl Into<ArcGISClient>,
+    ) -> anyhow::Result<DeleteItemsResponse> {
+        let client = client.into();
+        let response = client
+            .post(&self.url)
+            .form(&self.params)
+            .send_retrying(&client)
+            .await?;
+        let body = parse_response::<DeleteItemsResponse>(response).await?;
+        Ok(body)
+    }
+}
+
+impl DeleteItemsQueryBuilder {
+    pub fn new(
+        root: impl Into<String>,
+        user_name: impl Into<String>,
+        item_ids: Vec<String>,
+    ) -> Self {
+        // https://[root]/content/users/[userName]/deleteItems
+
+        let url = format!(
+            "{}/content/users/{}/deleteItems",
+            root.into(),
+            urlencoding::encode(&user_name.into())
+        );
+        Self {
+            url,
+            item_ids,
+            ..Default::default()
+        }
+    }
+
+    /// Skips the recycle bin on organizations that have it enabled.
+    pub fn permanent_delete(mut self, permanent_delete: bool) -> Self {
+        self.permanent_delete = permanent_delete;
+        self
+    }
+
+    pub fn token(mut self, token: impl Into<String>) -> Self {
+        self.token = Some(token.into());
+        self
+    }
+
+    pub fn build(self) -> DeleteItemsQuery {
+        let mut params = HashMap::new();
+        params.insert("items".into(), self.item_ids.join(","));
+
+        if self.permanent_delete {
+            params.insert("permanentDelete".into(), "true".into());
+        }
+
+        params.insert("f".into(), "json".into());
+
+        let url = if let Some(token) = &self.token {
+            format!("{}?token={}", self.url, token)
+        } else {
+            self.url
+        };
+
+        DeleteItemsQuery { url, params }
+    }
+}
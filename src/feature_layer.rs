@@ -1,14 +1,19 @@
-use reqwest::Client;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 
-use crate::parser::parse_response;
+use crate::{
+    client::ArcGISClient,
+    feature_layer_attachments::Attachments,
+    feature_layer_query::{EsriCountResponse, Extent, QueryGeometry},
+    parser::parse_response,
+    retry::SendWithRetry,
+};
 
 #[derive(Debug, Clone)]
 pub struct FeatureLayer {
     //pub name: String,
     pub url: String,
     pub metadata: MetaData,
-    client: Client,
+    client: ArcGISClient,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -16,9 +21,169 @@ pub struct MetaData {
     pub r#type: String, // should be Feature Layer
     pub name: String,   // name of the layer
     pub fields: Vec<EsriField>,
+    #[serde(
+        default,
+        rename = "currentVersion",
+        deserialize_with = "crate::portal::deserialize_version"
+    )]
+    pub current_version: Option<(u32, u32)>,
+    /// `None` when missing or unreadable, e.g. an empty layer or a
+    /// spatial reference given only as WKT.
+    #[serde(default, deserialize_with = "deserialize_extent")]
+    pub extent: Option<Extent>,
+    #[serde(default, rename = "supportsCoordinatesQuantization")]
+    pub supports_coordinates_quantization: bool,
+    #[serde(default, rename = "supportsValidateSQL")]
+    pub supports_validate_sql: bool,
     //max_record_count: i32, // TODO: use this to dynamically handle page size
 }
 
+/// The `FeatureServer` root a layer belongs to.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct FeatureServiceInfo {
+    #[serde(default, deserialize_with = "crate::portal::deserialize_version")]
+    pub current_version: Option<(u32, u32)>,
+    pub service_description: Option<String>,
+    /// Portal the hosting server is federated with, e.g.
+    /// `https://gis.example.com/portal`.
+    pub owning_system_url: Option<String>,
+    /// Comma-separated, e.g. `Query,Create,Update,Delete,Sync`.
+    #[serde(default)]
+    pub capabilities: String,
+    #[serde(default)]
+    pub layers: Vec<ServiceLayer>,
+}
+
+/// `syncModel` of a replica: whether it is synchronized as a whole or
+/// layer by layer.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SyncModel {
+    /// A one-off extract that can't be synchronized.
+    None,
+    #[default]
+    PerReplica,
+    PerLayer,
+}
+
+impl SyncModel {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SyncModel::None => "none",
+            SyncModel::PerReplica => "perReplica",
+            SyncModel::PerLayer => "perLayer",
+        }
+    }
+}
+
+/// The result of `createReplica` in SQLite format: where to download the
+/// mobile geodatabase.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ReplicaResponse {
+    #[serde(rename = "replicaID")]
+    pub replica_id: Option<String>,
+    pub replica_name: Option<String>,
+    pub transport_type: Option<String>,
+    /// URL of the generated `.geodatabase`.
+    pub response_url: Option<String>,
+}
+
+/// What a clause passed to [`FeatureLayer::validate_sql`] is used as.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SqlType {
+    /// A `where` clause or `definitionExpression`.
+    #[default]
+    Where,
+    /// An expression such as the value of a calculated field.
+    Expression,
+    Statement,
+}
+
+impl SqlType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SqlType::Where => "where",
+            SqlType::Expression => "expression",
+            SqlType::Statement => "statement",
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ValidateSqlResponse {
+    #[serde(rename = "isValidSQL")]
+    pub is_valid_sql: bool,
+    #[serde(default)]
+    pub validation_errors: Vec<SqlValidationError>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SqlValidationError {
+    pub error_code: i64,
+    pub description: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ServiceLayer {
+    pub id: i64,
+    pub name: String,
+}
+
+impl FeatureServiceInfo {
+    pub async fn fetch(client: impl Into<ArcGISClient>, service_url: &str) -> anyhow::Result<Self> {
+        let client = client.into();
+        let response = client
+            .get(service_url)
+            .query(&[("f", "json")])
+            .send_retrying(&client)
+            .await?;
+        parse_response::<FeatureServiceInfo>(response).await
+    }
+
+    pub fn supports_sync(&self) -> bool {
+        self.capabilities
+            .split(',')
+            .any(|c| c.trim().eq_ignore_ascii_case("Sync"))
+    }
+
+    pub fn owning_system_url(&self) -> Option<&str> {
+        self.owning_system_url.as_deref()
+    }
+
+    /// Whether a token from `portal_root` (e.g. `https://www.arcgis.com/sharing/rest`)
+    /// is accepted by this service.
+    ///
+    /// Services on a server federated with another portal need a token from
+    /// that portal instead: an [`crate::auth::ArcGISProvider`] whose `portal`
+    /// is the owning system URL followed by `/sharing/rest`. Services without
+    /// an owning system (standalone servers) are assumed to accept it.
+    ///
+    /// ArcGIS Online services report `http://www.arcgis.com` as their owner,
+    /// so an organization URL like `https://acme.maps.arcgis.com` matches it.
+    pub fn is_owned_by(&self, portal_root: &str) -> bool {
+        let Some(owner) = &self.owning_system_url else {
+            return true;
+        };
+        let normalize = |url: &str| {
+            let url = url.to_ascii_lowercase();
+            let url = url
+                .trim_end_matches('/')
+                .trim_end_matches("/sharing/rest")
+                .trim_start_matches("https://")
+                .trim_start_matches("http://");
+            if url == "arcgis.com" || url.ends_with(".maps.arcgis.com") {
+                "www.arcgis.com".to_string()
+            } else {
+                url.to_string()
+            }
+        };
+        normalize(owner) == normalize(portal_root)
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct EsriField {
     pub name: String,
@@ -45,6 +210,17 @@ pub enum EsriType {
     EsriFieldTypeGeometry,
     EsriFieldTypeBigInteger,
     EsriFieldTypeSingle,
+    /// A type this crate doesn't model yet, e.g. `esriFieldTypeDateOnly`.
+    #[serde(untagged)]
+    Other(String),
+}
+
+fn deserialize_extent<'de, D>(deserializer: D) -> Result<Option<Extent>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let extent = Option::<serde_json::Value>::deserialize(deserializer)?;
+    Ok(extent.and_then(|e| serde_json::from_value(e).ok()))
 }
 
 // pub struct Feature {
@@ -93,12 +269,18 @@ pub struct UpdateResultError {
 }
 
 impl FeatureLayer {
-    pub async fn new(client: &Client, url: &str) -> anyhow::Result<FeatureLayer> {
-        let response = client.get(url).query(&[("f", "json")]).send().await?;
+    pub async fn new(client: impl Into<ArcGISClient>, url: &str) -> anyhow::Result<FeatureLayer> {
+        let client = client.into();
+        let response = client
+            .get(url)
+            .query(&[("f", "json")])
+            .send_retrying(&client)
+            .await?;
 
-        let metadata = parse_response::<MetaData>(response)
-            .await
-            .expect("Failed to fetch feature service metadata");
+        let metadata = parse_response::<MetaData>(response).await.map_err(|e| {
+            let message = format!("Failed to fetch feature service metadata: {}", e);
+            e.context(message)
+        })?;
 
         Ok(FeatureLayer {
             url: url.to_string(),
@@ -116,8 +298,122 @@ impl FeatureLayer {
             features,
             ..Default::default()
         };
-        let response = self.client.post(&url).json(&request).send().await?;
+        let response = self
+            .client
+            .post(&url)
+            .json(&request)
+            .send_retrying(&self.client)
+            .await?;
         let result = parse_response::<UpdateFeaturesResponse>(response).await?;
         Ok(result)
     }
+
+    /// Attachments of the feature with `object_id`. The layer must have
+    /// attachments enabled.
+    pub fn attachments(&self, object_id: i64) -> Attachments<'_> {
+        Attachments::new(&self.client, &self.url, object_id)
+    }
+
+    /// Fetches the info of the service hosting this layer, e.g. to check
+    /// its `owningSystemUrl` before sending a portal token to it.
+    pub async fn service_info(&self) -> anyhow::Result<FeatureServiceInfo> {
+        FeatureServiceInfo::fetch(&self.client, self.service_url()).await
+    }
+
+    /// Creates a replica of `layers` of this layer's service, clipped to
+    /// `geometry`, for offline editing. The service must have `Sync`
+    /// enabled.
+    ///
+    /// The replica is generated synchronously as a mobile geodatabase;
+    /// download it from [`ReplicaResponse::response_url`].
+    pub async fn create_replica(
+        &self,
+        layers: &[i64],
+        geometry: Option<QueryGeometry>,
+        sync_model: SyncModel,
+    ) -> anyhow::Result<ReplicaResponse> {
+        let info = self.service_info().await?;
+        if !info.supports_sync() {
+            anyhow::bail!("Service {} does not support Sync", self.service_url());
+        }
+
+        let layers = layers
+            .iter()
+            .map(|id| id.to_string())
+            .collect::<Vec<_>>()
+            .join(",");
+        let mut params = vec![
+            ("layers", layers),
+            ("syncModel", sync_model.as_str().to_string()),
+            ("dataFormat", "sqlite".to_string()),
+            ("transportType", "esriTransportTypeUrl".to_string()),
+            ("returnAttachments", "true".to_string()),
+            ("async", "false".to_string()),
+            ("f", "json".to_string()),
+        ];
+        if let Some(geometry) = geometry {
+            params.push(("geometry", geometry.geometry.to_string()));
+            params.push((
+                "geometryType",
+                geometry.geometry_type.to_esri_string().to_string(),
+            ));
+            if let Some(in_sr) = geometry.in_sr {
+                params.push(("inSR", in_sr.to_string()));
+            }
+        }
+
+        // https://[serviceUrl]/createReplica
+        let response = self
+            .client
+            .post(format!("{}/createReplica", self.service_url()))
+            .form(&params)
+            .send_retrying(&self.client)
+            .await?;
+        parse_response::<ReplicaResponse>(response).await
+    }
+
+    /// Checks `sql` against the layer's fields before it's used in a query,
+    /// so a malformed clause is reported with the reason instead of a
+    /// generic query failure. The layer must have `supports_validate_sql`.
+    pub async fn validate_sql(
+        &self,
+        sql: &str,
+        sql_type: SqlType,
+    ) -> anyhow::Result<ValidateSqlResponse> {
+        // https://[layerUrl]/validateSQL
+        let response = self
+            .client
+            .post(format!("{}/validateSQL", self.url))
+            .form(&[("sql", sql), ("sqlType", sql_type.as_str()), ("f", "json")])
+            .send_retrying(&self.client)
+            .await?;
+        parse_response::<ValidateSqlResponse>(response).await
+    }
+
+    fn service_url(&self) -> &str {
+        match self.url.trim_end_matches('/').rsplit_once('/') {
+            Some((service, layer)) if layer.parse::<i64>().is_ok() => service,
+            _ => self.url.as_str(),
+        }
+    }
+
+    /// Checks that the layer still answers queries.
+    ///
+    /// Returns `Ok(false)` when the service responds with an error (missing
+    /// layer, service stopped) and `Err` only when the request itself fails.
+    pub async fn ping(&self) -> anyhow::Result<bool> {
+        // A returnCountOnly query is the cheapest request that proves the
+        // layer exists and can be queried.
+        let response = self
+            .client
+            .get(format!("{}/query", self.url))
+            .query(&[("where", "1=1"), ("returnCountOnly", "true"), ("f", "json")])
+            .send_retrying(&self.client)
+            .await?;
+        if !response.status().is_success() {
+            return Ok(false);
+        }
+
+        Ok(parse_response::<EsriCountResponse>(response).await.is_ok())
+    }
 }
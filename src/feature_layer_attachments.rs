@@ -0,0 +1,139 @@
+use reqwest::multipart::{Form, Part};
+use serde::Deserialize;
+
+use crate::{
+    client::ArcGISClient, feature_layer_update::ApplyEditsResponseResult, parser::parse_response,
+    retry::SendWithRetry,
+};
+
+/// The attachments of one feature, from [`crate::feature_layer::FeatureLayer::attachments`].
+pub struct Attachments<'a> {
+    client: &'a ArcGISClient,
+    url: String,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct AttachmentInfo {
+    pub id: i64,
+    pub name: String,
+    pub content_type: String,
+    pub size: u64,
+    pub global_id: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AttachmentInfosResponse {
+    attachment_infos: Vec<AttachmentInfo>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AddAttachmentResponse {
+    add_attachment_result: ApplyEditsResponseResult,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DeleteAttachmentsResponse {
+    delete_attachment_results: Vec<ApplyEditsResponseResult>,
+}
+
+impl<'a> Attachments<'a> {
+    pub(crate) fn new(client: &'a ArcGISClient, layer_url: &str, object_id: i64) -> Self {
+        // https://[layerUrl]/[objectId]/attachments
+        Self {
+            client,
+            url: format!("{}/{}", layer_url.trim_end_matches('/'), object_id),
+        }
+    }
+
+    /// Uploads `bytes` as a new attachment, e.g. a photo taken in the field.
+    pub async fn add(
+        &self,
+        bytes: Vec<u8>,
+        file_name: impl Into<String>,
+        content_type: impl Into<String>,
+    ) -> anyhow::Result<AttachmentInfo> {
+        let name = file_name.into();
+        let content_type = content_type.into();
+        let size = bytes.len() as u64;
+        let part = Part::bytes(bytes)
+            .file_name(name.clone())
+            .mime_str(&content_type)?;
+        let form = Form::new().text("f", "json").part("attachment", part);
+
+        let response = self
+            .client
+            .post(format!("{}/addAttachment", self.url))
+            .multipart(form)
+            .send_retrying(self.client)
+            .await?;
+        let result = parse_response::<AddAttachmentResponse>(response)
+            .await?
+            .add_attachment_result;
+
+        match (result.success, result.object_id) {
+            (true, Some(id)) => Ok(AttachmentInfo {
+                id,
+                name,
+                content_type,
+                size,
+                global_id: result.global_id,
+            }),
+            _ => Err(anyhow::anyhow!(
+                "Failed to add attachment {}: {}",
+                name,
+                result
+                    .error
+                    .map(|e| e.description)
+                    .unwrap_or_else(|| "no error given".to_string())
+            )),
+        }
+    }
+
+    pub async fn list(&self) -> anyhow::Result<Vec<AttachmentInfo>> {
+        let response = self
+            .client
+            .get(format!("{}/attachments", self.url))
+            .query(&[("f", "json")])
+            .send_retrying(self.client)
+            .await?;
+        Ok(parse_response::<AttachmentInfosResponse>(response)
+            .await?
+            .attachment_infos)
+    }
+
+    /// Downloads the content of one attachment.
+    pub async fn download(&self, attachment_id: i64) -> anyhow::Result<Vec<u8>> {
+        let response = self
+            .client
+            .get(format!("{}/attachments/{}", self.url, attachment_id))
+            .send_retrying(self.client)
+            .await?
+            .error_for_status()?;
+        Ok(response.bytes().await?.to_vec())
+    }
+
+    /// Deletes attachments by id. The outcome is reported per attachment.
+    pub async fn delete(
+        &self,
+        attachment_ids: &[i64],
+    ) -> anyhow::Result<Vec<ApplyEditsResponseResult>> {
+        let ids = attachment_ids
+            .iter()
+            .map(|id| id.to_string())
+            .collect::<Vec<_>>()
+            .join(",");
+        let response = self
+            .client
+            .post(format!("{}/deleteAttachments", self.url))
+            .form(&[("attachmentIds", ids.as_str()), ("f", "json")])
+            .send_retrying(self.client)
+            .await?;
+        Ok(parse_response::<DeleteAttachmentsResponse>(response)
+            .await?
+            .delete_attachment_results)
+    }
+}
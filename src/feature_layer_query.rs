@@ -1,7 +1,15 @@
+use futures_util::{stream, Stream, TryStreamExt};
 use log::info;
-use reqwest::{Client, Response};
-use serde::Deserialize;
+use reqwest::Response;
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use std::time::Duration;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+
+use crate::{
+    client::ArcGISClient, feature_layer::EsriField, parser::parse_response,
+    publish_item::SpatialReference,
+};
 
 /// Geometry types supported by ArcGIS REST API
 #[derive(Debug, Clone)]
@@ -25,6 +33,27 @@ impl GeometryType {
     }
 }
 
+/// `f` of a query: the response format.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ResponseFormat {
+    #[default]
+    Json,
+    GeoJson,
+    /// Esri protocol buffers, much smaller than JSON for large queries.
+    /// Decoding requires the `pbf` feature.
+    Pbf,
+}
+
+impl ResponseFormat {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ResponseFormat::Json => "json",
+            ResponseFormat::GeoJson => "geojson",
+            ResponseFormat::Pbf => "pbf",
+        }
+    }
+}
+
 /// Spatial relationship operators for geometry queries
 #[derive(Debug, Clone)]
 pub enum SpatialRelationship {
@@ -59,6 +88,29 @@ impl SpatialRelationship {
     }
 }
 
+/// `statisticType` of an `outStatistics` entry
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StatisticType {
+    Count,
+    Sum,
+    Min,
+    Max,
+    Avg,
+    Stddev,
+    Var,
+}
+
+/// One `outStatistics` entry: `statistic_type` of `on_field`, returned as
+/// the attribute `out_field`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct OutStatistic {
+    statistic_type: StatisticType,
+    on_statistic_field: String,
+    out_statistic_field_name: String,
+}
+
 /// Geometry wrapper for spatial queries
 #[derive(Debug, Clone)]
 pub struct QueryGeometry {
@@ -140,17 +192,83 @@ impl QueryGeometry {
     }
 }
 
+/// An envelope, as used by `extent` on layers and items.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Extent {
+    pub xmin: f64,
+    pub ymin: f64,
+    pub xmax: f64,
+    pub ymax: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub spatial_reference: Option<SpatialReference>,
+}
+
+/// `mode` of `quantizationParameters`
+#[derive(Serialize, Debug, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+pub enum QuantizationMode {
+    /// Generalizes geometry for display at the given tolerance.
+    View,
+    /// Snaps coordinates to the tolerance grid without generalizing.
+    Edit,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+struct QuantizationParameters {
+    mode: QuantizationMode,
+    origin_position: &'static str,
+    tolerance: f64,
+    extent: Extent,
+}
+
+/// `transform` returned with quantized geometry.
+///
+/// Quantized coordinates are integers on a grid; map them back with
+/// `x = translate[0] + x * scale[0]` and
+/// `y = translate[1] - y * scale[1]` (origin is upper left).
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct QuantizationTransform {
+    pub origin_position: String,
+    pub scale: Vec<f64>,
+    pub translate: Vec<f64>,
+}
+
 #[derive(Deserialize)]
 pub struct EsriCountResponse {
     pub count: i32,
 }
 
+/// `returnExtentOnly` response.
+#[derive(Deserialize, Debug)]
+pub struct EsriExtentResponse {
+    /// `None` when no features match.
+    pub extent: Option<Extent>,
+}
+
 #[derive(Deserialize, Debug)]
 pub struct EsriQueryResponse {
+    /// e.g. `esriGeometryPoint`; absent when geometry wasn't returned.
+    #[serde(rename = "geometryType")]
+    pub geometry_type: Option<String>,
+    #[serde(rename = "spatialReference")]
+    pub spatial_reference: Option<SpatialReference>,
+    /// Fields of the returned attributes; `f=pbf` responses leave this
+    /// empty.
+    #[serde(default)]
+    pub fields: Vec<EsriField>,
     pub features: Vec<EsriFeature>,
+    /// Set when the layer has more features than fit in one page.
+    #[serde(default, rename = "exceededTransferLimit")]
+    pub exceeded_transfer_limit: bool,
+    /// Present when the query set `quantizationParameters`; the geometry in
+    /// `features` is then quantized and must be dequantized by the caller.
+    pub transform: Option<QuantizationTransform>,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct EsriFeature {
     pub attributes: Value,
     pub geometry: Option<Value>,
@@ -158,6 +276,7 @@ pub struct EsriFeature {
 
 pub struct FeatureLayerQuery {
     count_only: String,
+    extent_only: String,
     offset: String,
     out_fields: String,
     return_geometry: String,
@@ -166,6 +285,12 @@ pub struct FeatureLayerQuery {
     geometry_type: Option<String>,
     spatial_rel: Option<String>,
     in_sr: Option<String>,
+    quantization_parameters: Option<String>,
+    record_count: String,
+    order_by_fields: Option<String>,
+    out_statistics: Option<String>,
+    group_by_fields: Option<String>,
+    format: ResponseFormat,
 }
 
 impl FeatureLayerQuery {
@@ -173,7 +298,12 @@ impl FeatureLayerQuery {
         FeatureLayerQueryBuilder::new()
     }
 
-    pub async fn send(&self, client: &Client, url: &str) -> Result<Response, reqwest::Error> {
+    pub async fn send(
+        &self,
+        client: impl Into<ArcGISClient>,
+        url: &str,
+    ) -> Result<Response, reqwest::Error> {
+        let client = client.into();
         let url = format!("{}/query", url);
 
         let mut query: Vec<(&str, &str)> = vec![
@@ -185,8 +315,8 @@ impl FeatureLayerQuery {
             ("returnGeometry", &self.return_geometry),
             ("returnDistinctValues", "false"),
             ("returnIdsOnly", "false"),
-            ("resultRecordCount", "2000"),
-            ("returnExtentOnly", "false"),
+            ("resultRecordCount", &self.record_count),
+            ("returnExtentOnly", &self.extent_only),
             ("returnZ", "false"),
             ("returnM", "false"),
             ("returnTrueCurves", "false"),
@@ -195,7 +325,7 @@ impl FeatureLayerQuery {
             ("timeReferenceUnknownClient", "false"),
             ("lodType", "geohash"),
             ("cacheHint", "false"),
-            ("f", "json"),
+            ("f", self.format.as_str()),
         ];
 
         // TODO: Set defaults
@@ -212,6 +342,18 @@ impl FeatureLayerQuery {
         if let Some(ref in_sr) = self.in_sr {
             query.push(("inSR", in_sr));
         }
+        if let Some(ref quantization_parameters) = self.quantization_parameters {
+            query.push(("quantizationParameters", quantization_parameters));
+        }
+        if let Some(ref order_by_fields) = self.order_by_fields {
+            query.push(("orderByFields", order_by_fields));
+        }
+        if let Some(ref out_statistics) = self.out_statistics {
+            query.push(("outStatistics", out_statistics));
+        }
+        if let Some(ref group_by_fields) = self.group_by_fields {
+            query.push(("groupByFieldsForStatistics", group_by_fields));
+        }
 
         info!(
             "[FeatureLayerQuery] sending query: {} : offset {} : countOnly {} : geometry {}",
@@ -224,13 +366,41 @@ impl FeatureLayerQuery {
                 "none"
             }
         );
-        client.get(url).query(&query).send().await
+        client.send_raw(client.get(url).query(&query)).await
+    }
+}
+
+impl FeatureLayerQuery {
+    /// Sends the query and decodes the features, from JSON or, with the
+    /// `pbf` feature, from protocol buffers. GeoJSON responses are not
+    /// decoded here; use [`FeatureLayerQuery::send`] for those.
+    pub async fn send_features(
+        &self,
+        client: impl Into<ArcGISClient>,
+        url: &str,
+    ) -> anyhow::Result<EsriQueryResponse> {
+        let client = client.into();
+        let response = self.send(client, url).await?;
+        match self.format {
+            ResponseFormat::Json => parse_response::<EsriQueryResponse>(response).await,
+            #[cfg(feature = "pbf")]
+            ResponseFormat::Pbf => {
+                let bytes = response.error_for_status()?.bytes().await?;
+                crate::pbf::decode_query_response(&bytes)
+            }
+            format => Err(anyhow::anyhow!(
+                "Decoding f={} responses is not supported",
+                format.as_str()
+            )),
+        }
     }
 }
 
 //#[derive(Default)]
+#[derive(Clone)]
 pub struct FeatureLayerQueryBuilder {
     count_only: String,
+    extent_only: bool,
     offset: String,
     out_fields: String,
     return_geometry: bool,
@@ -240,12 +410,26 @@ pub struct FeatureLayerQueryBuilder {
     geometry_type: Option<GeometryType>,
     spatial_rel: Option<SpatialRelationship>,
     in_sr: Option<u32>,
+    quantization: Option<QuantizationParameters>,
+    record_count: i32,
+    order_by_fields: Option<String>,
+    out_statistics: Vec<OutStatistic>,
+    group_by_fields: Vec<String>,
+    format: ResponseFormat,
+    page_delay: Duration,
+}
+
+impl Default for FeatureLayerQueryBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl FeatureLayerQueryBuilder {
     pub fn new() -> FeatureLayerQueryBuilder {
         FeatureLayerQueryBuilder {
             count_only: "false".to_string(),
+            extent_only: false,
             offset: "0".to_string(),
             out_fields: "*".to_string(),
             return_geometry: false,
@@ -254,6 +438,13 @@ impl FeatureLayerQueryBuilder {
             geometry_type: None,
             spatial_rel: None,
             in_sr: None,
+            quantization: None,
+            record_count: 2000,
+            order_by_fields: None,
+            out_statistics: vec![],
+            group_by_fields: vec![],
+            format: ResponseFormat::Json,
+            page_delay: Duration::ZERO,
         }
     }
 
@@ -268,7 +459,7 @@ impl FeatureLayerQueryBuilder {
     }
 
     pub fn set_out_fields_vec(mut self, out_fields: Vec<String>) -> FeatureLayerQueryBuilder {
-        let o: &str = if out_fields.len() == 0 {
+        let o: &str = if out_fields.is_empty() {
             "*"
         } else {
             &out_fields.join(",")
@@ -298,6 +489,35 @@ impl FeatureLayerQueryBuilder {
         self
     }
 
+    /// Sets `geometry`, `geometryType`, `spatialRel` and, when given,
+    /// `inSR` from `geometry`.
+    pub fn set_query_geometry(mut self, geometry: QueryGeometry) -> FeatureLayerQueryBuilder {
+        self.geometry = Some(geometry.geometry.to_string());
+        self.geometry_type = Some(geometry.geometry_type);
+        self.spatial_rel = Some(geometry.spatial_rel);
+        if geometry.in_sr.is_some() {
+            self.in_sr = geometry.in_sr;
+        }
+        self
+    }
+
+    /// `resultRecordCount`: the page size, capped by the layer's
+    /// `maxRecordCount` (default 2000).
+    pub fn set_record_count(mut self, record_count: i32) -> FeatureLayerQueryBuilder {
+        self.record_count = record_count;
+        self
+    }
+
+    /// `orderByFields`, e.g. `"STATE_NAME ASC, POP DESC"`. Paging with
+    /// `set_offset` needs a stable order to not skip or repeat features.
+    pub fn set_order_by_fields(
+        mut self,
+        order_by_fields: impl Into<String>,
+    ) -> FeatureLayerQueryBuilder {
+        self.order_by_fields = Some(order_by_fields.into());
+        self
+    }
+
     // /// Set geometry for spatial queries
     // pub fn set_geometry(mut self, geometry: QueryGeometry) -> FeatureLayerQueryBuilder {
     //     self.geometry = Some(geometry);
@@ -386,7 +606,7 @@ impl FeatureLayerQueryBuilder {
     //     self
     // }
     //
-    /// Set spatial relationship for existing geometry (if any)
+    // /// Set spatial relationship for existing geometry (if any)
     // pub fn set_spatial_relationship(
     //     mut self,
     //     spatial_rel: SpatialRelationship,
@@ -416,6 +636,207 @@ impl FeatureLayerQueryBuilder {
         self
     }
 
+    /// Requests quantized geometry snapped to a `tolerance` grid over
+    /// `extent`, which greatly shrinks payloads for drawing.
+    ///
+    /// Only layers with `supportsCoordinatesQuantization` honour this. The
+    /// response carries a [`QuantizationTransform`]; geometry is returned in
+    /// grid units and consumers must dequantize it.
+    pub fn quantization(
+        mut self,
+        extent: Extent,
+        tolerance: f64,
+        mode: QuantizationMode,
+    ) -> FeatureLayerQueryBuilder {
+        self.quantization = Some(QuantizationParameters {
+            mode,
+            origin_position: "upperLeft",
+            tolerance,
+            extent,
+        });
+        self
+    }
+
+    pub fn set_format(mut self, format: ResponseFormat) -> FeatureLayerQueryBuilder {
+        self.format = format;
+        self
+    }
+
+    /// Requests `f=pbf`; decode with [`FeatureLayerQuery::send_features`].
+    #[cfg(feature = "pbf")]
+    pub fn as_pbf(self) -> FeatureLayerQueryBuilder {
+        self.set_format(ResponseFormat::Pbf)
+    }
+
+    /// Adds an `outStatistics` entry computing `statistic_type` over `field`,
+    /// returned as the attribute `out_field`. With statistics the query
+    /// returns one row per group instead of features.
+    pub fn add_statistic(
+        mut self,
+        statistic_type: StatisticType,
+        field: impl Into<String>,
+        out_field: impl Into<String>,
+    ) -> FeatureLayerQueryBuilder {
+        self.out_statistics.push(OutStatistic {
+            statistic_type,
+            on_statistic_field: field.into(),
+            out_statistic_field_name: out_field.into(),
+        });
+        self
+    }
+
+    /// `groupByFieldsForStatistics`: computes the statistics per distinct
+    /// combination of `fields`.
+    pub fn group_by(mut self, fields: Vec<String>) -> FeatureLayerQueryBuilder {
+        self.group_by_fields = fields;
+        self
+    }
+
+    /// Sends a statistics query, returning the attributes of each row: the
+    /// group by fields and the statistic out fields.
+    pub async fn statistics(
+        self,
+        client: impl Into<ArcGISClient>,
+        url: &str,
+    ) -> anyhow::Result<Vec<serde_json::Map<String, Value>>> {
+        let client = client.into();
+        if self.out_statistics.is_empty() {
+            anyhow::bail!("A statistics query needs at least one add_statistic");
+        }
+        let response = self
+            .set_return_geometry(false)
+            .set_format(ResponseFormat::Json)
+            .build()
+            .send_features(client, url)
+            .await?;
+
+        Ok(response
+            .features
+            .into_iter()
+            .filter_map(|feature| match feature.attributes {
+                Value::Object(attributes) => Some(attributes),
+                _ => None,
+            })
+            .collect())
+    }
+
+    /// Number of features matching the query (`returnCountOnly`), without
+    /// fetching any of them.
+    pub async fn count(self, client: impl Into<ArcGISClient>, url: &str) -> anyhow::Result<i32> {
+        let client = client.into();
+        let response = self
+            .set_format(ResponseFormat::Json)
+            .set_count_only(true)
+            .build()
+            .send(client, url)
+            .await?;
+        let body = parse_response::<EsriCountResponse>(response).await?;
+        Ok(body.count)
+    }
+
+    /// Bounding extent of the features matching the query
+    /// (`returnExtentOnly`), without fetching any of them.
+    pub async fn extent(
+        self,
+        client: impl Into<ArcGISClient>,
+        url: &str,
+    ) -> anyhow::Result<Option<Extent>> {
+        let client = client.into();
+        let mut builder = self.set_format(ResponseFormat::Json);
+        builder.extent_only = true;
+        let response = builder.build().send(client, url).await?;
+        let body = parse_response::<EsriExtentResponse>(response).await?;
+        Ok(body.extent)
+    }
+
+    /// Pause between page requests made by [`FeatureLayerQueryBuilder::stream`],
+    /// to stay under a service's rate limits.
+    pub fn set_page_delay(mut self, page_delay: Duration) -> FeatureLayerQueryBuilder {
+        self.page_delay = page_delay;
+        self
+    }
+
+    /// Pages through the query results with `resultOffset`, yielding each
+    /// feature, until the service stops reporting `exceededTransferLimit`.
+    ///
+    /// The page size is [`FeatureLayerQueryBuilder::set_record_count`]. A
+    /// failed page ends the stream with its error.
+    pub fn stream(
+        self,
+        client: impl Into<ArcGISClient>,
+        url: &str,
+    ) -> impl Stream<Item = anyhow::Result<EsriFeature>> {
+        let client = client.into();
+        // The offset is only ever set from an i32.
+        let start = self.offset.parse::<i32>().unwrap_or(0);
+        let state = (self, client.clone(), url.to_string(), Some(start));
+
+        stream::try_unfold(state, move |(builder, client, url, next)| async move {
+            let Some(offset) = next else {
+                return Ok::<_, anyhow::Error>(None);
+            };
+            if offset != start && !builder.page_delay.is_zero() {
+                tokio::time::sleep(builder.page_delay).await;
+            }
+
+            let page = builder
+                .clone()
+                .set_offset(offset)
+                .build()
+                .send_features(&client, &url)
+                .await?;
+            let next = (page.exceeded_transfer_limit && !page.features.is_empty())
+                .then(|| offset + page.features.len() as i32);
+
+            let features = stream::iter(page.features.into_iter().map(Ok));
+            Ok(Some((features, (builder, client, url, next))))
+        })
+        .try_flatten()
+    }
+
+    /// Pages through the query results and writes each feature to `writer`
+    /// as one line of JSON (NDJSON), returning the number of features written.
+    ///
+    /// Only one page is held in memory at a time, so this works for layers
+    /// far larger than `resultRecordCount`.
+    pub async fn export_ndjson<W>(
+        self,
+        client: impl Into<ArcGISClient>,
+        url: &str,
+        writer: &mut W,
+    ) -> anyhow::Result<usize>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        let client = client.into();
+        let mut offset: i32 = self.offset.parse()?;
+        let mut written = 0;
+
+        loop {
+            let page = self
+                .clone()
+                .set_offset(offset)
+                .build()
+                .send_features(&client, url)
+                .await?;
+
+            for feature in &page.features {
+                let mut line = serde_json::to_vec(feature)?;
+                line.push(b'\n');
+                writer.write_all(&line).await?;
+            }
+            written += page.features.len();
+
+            if !page.exceeded_transfer_limit || page.features.is_empty() {
+                break;
+            }
+            offset += page.features.len() as i32;
+        }
+
+        writer.flush().await?;
+        Ok(written)
+    }
+
     pub fn build(self) -> FeatureLayerQuery {
         // TODO: add validation
         // if self.out_fields.trim().is_empty() {
@@ -424,6 +845,7 @@ impl FeatureLayerQueryBuilder {
 
         FeatureLayerQuery {
             count_only: self.count_only,
+            extent_only: self.extent_only.to_string(),
             offset: self.offset,
             out_fields: self.out_fields,
             return_geometry: self.return_geometry.to_string(),
@@ -432,6 +854,17 @@ impl FeatureLayerQueryBuilder {
             geometry_type: self.geometry_type.map(|t| t.to_esri_string().to_string()),
             spatial_rel: self.spatial_rel.map(|r| r.to_esri_string()),
             in_sr: self.in_sr.map(|sr| sr.to_string()),
+            quantization_parameters: self
+                .quantization
+                .map(|q| serde_json::to_string(&q).expect("quantization parameters serialize")),
+            record_count: self.record_count.to_string(),
+            order_by_fields: self.order_by_fields,
+            out_statistics: (!self.out_statistics.is_empty()).then(|| {
+                serde_json::to_string(&self.out_statistics).expect("statistics serialize")
+            }),
+            group_by_fields: (!self.group_by_fields.is_empty())
+                .then(|| self.group_by_fields.join(",")),
+            format: self.format,
         }
     }
 }
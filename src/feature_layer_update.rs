@@ -1,8 +1,9 @@
-use reqwest::Client;
 use serde::Deserialize;
 use std::collections::HashMap;
 
-#[derive(Deserialize)]
+use crate::{client::ArcGISClient, feature_layer_query::EsriFeature, parser::parse_response};
+
+#[derive(Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct ApplyEditsResponse {
     pub add_results: Vec<ApplyEditsResponseResult>,
@@ -10,16 +11,98 @@ pub struct ApplyEditsResponse {
     pub delete_results: Vec<ApplyEditsResponseResult>,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct ApplyEditsResponseResult {
     pub success: bool,
     pub global_id: Option<String>,
     pub object_id: Option<i64>,
+    /// Why this edit failed, when `success` is false.
+    pub error: Option<ApplyEditsError>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ApplyEditsError {
+    pub code: i64,
+    pub description: String,
+}
+
+impl ApplyEditsResponse {
+    /// Edits that failed, across adds, updates and deletes.
+    pub fn failed(&self) -> impl Iterator<Item = &ApplyEditsResponseResult> {
+        self.add_results
+            .iter()
+            .chain(&self.update_results)
+            .chain(&self.delete_results)
+            .filter(|r| !r.success)
+    }
+}
+
+/// A `rollbackOnFailure` request of [`ApplyEditsQuery::send_edits`] had a
+/// failing edit, so ArcGIS rolled it back and the requests after it were not
+/// sent. The edits of the earlier requests stay applied.
+#[derive(Debug)]
+pub struct ApplyEditsRolledBack {
+    /// The rolled back request, counting from 1.
+    pub request: usize,
+    pub requests: usize,
+    /// Edits in the requests that were not sent.
+    pub unsent: usize,
+    /// Results of the earlier requests and of the rolled back one.
+    pub results: ApplyEditsResponse,
 }
 
+impl std::fmt::Display for ApplyEditsRolledBack {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "applyEdits request {} of {} was rolled back; edits not sent: {}",
+            self.request, self.requests, self.unsent
+        )
+    }
+}
+
+impl std::error::Error for ApplyEditsRolledBack {}
+
+/// How `applyEdits` parameters are encoded in the request body.
+///
+/// ArcGIS Online and ArcGIS Server both document `applyEdits` as
+/// form-encoded, which is the default. Use `Json` only for on-premises
+/// servers (or gateways in front of them) that are set up to require an
+/// `application/json` body.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BodyFormat {
+    #[default]
+    Form,
+    Json,
+}
+
+/// Edits per `applyEdits` request in [`ApplyEditsQuery::send_edits`],
+/// counting adds, updates and deletes together.
+pub const DEFAULT_MAX_EDITS_PER_REQUEST: usize = 1000;
+
 pub struct ApplyEditsQuery {
-    params: HashMap<String, String>,
+    adds: Vec<serde_json::Value>,
+    updates: Vec<serde_json::Value>,
+    deletes: Vec<serde_json::Value>,
+    return_edit_results: bool,
+    rollback_on_failure: bool,
+    use_global_ids: bool,
+    body_format: BodyFormat,
+    max_edits_per_request: usize,
+}
+
+/// One request's share of the edits.
+struct EditsChunk<'a> {
+    adds: &'a [serde_json::Value],
+    updates: &'a [serde_json::Value],
+    deletes: &'a [serde_json::Value],
+}
+
+impl EditsChunk<'_> {
+    fn len(&self) -> usize {
+        self.adds.len() + self.updates.len() + self.deletes.len()
+    }
 }
 
 impl ApplyEditsQuery {
@@ -27,14 +110,177 @@ impl ApplyEditsQuery {
         ApplyEditsQueryBuilder::new()
     }
 
+    /// Sends all edits in a single request, however many there are.
     pub async fn send(
         &self,
-        client: &Client,
+        client: impl Into<ArcGISClient>,
+        url: &str,
+    ) -> Result<reqwest::Response, reqwest::Error> {
+        let client = client.into();
+        let chunk = EditsChunk {
+            adds: &self.adds,
+            updates: &self.updates,
+            deletes: &self.deletes,
+        };
+        self.send_chunk(&client, url, &chunk).await
+    }
+
+    /// Sends the edits and parses the per-feature results.
+    ///
+    /// Large edit sets are split into requests of at most
+    /// `max_edits_per_request` edits (adds first, then updates, then
+    /// deletes) and the results are concatenated in that order.
+    /// `rollbackOnFailure` only covers one request: when a request fails or
+    /// is rolled back ([`ApplyEditsRolledBack`]), no further requests are
+    /// sent, but the edits of earlier requests stay applied.
+    pub async fn send_edits(
+        &self,
+        client: impl Into<ArcGISClient>,
+        url: &str,
+    ) -> anyhow::Result<ApplyEditsResponse> {
+        let client = client.into();
+        let chunks = self.chunks();
+        let mut edits = ApplyEditsResponse {
+            add_results: vec![],
+            update_results: vec![],
+            delete_results: vec![],
+        };
+
+        for (i, chunk) in chunks.iter().enumerate() {
+            let unsent = chunks[i + 1..].iter().map(EditsChunk::len).sum::<usize>();
+            let result = match self.send_chunk(&client, url, chunk).await {
+                Ok(response) => parse_response::<ApplyEditsResponse>(response).await,
+                Err(e) => Err(e.into()),
+            };
+            let response = match result {
+                Ok(response) => response,
+                Err(e) if i == 0 => return Err(e),
+                Err(e) => {
+                    return Err(e.context(format!(
+                        "applyEdits request {} of {} failed; the edits of the earlier requests were applied, edits not sent: {}",
+                        i + 1,
+                        chunks.len(),
+                        unsent
+                    )))
+                }
+            };
+
+            // A rolled back last request is reported through its results,
+            // as when everything fits in one request.
+            let rolled_back =
+                self.rollback_on_failure && unsent > 0 && response.failed().next().is_some();
+            edits.add_results.extend(response.add_results);
+            edits.update_results.extend(response.update_results);
+            edits.delete_results.extend(response.delete_results);
+            if rolled_back {
+                return Err(ApplyEditsRolledBack {
+                    request: i + 1,
+                    requests: chunks.len(),
+                    unsent,
+                    results: edits,
+                }
+                .into());
+            }
+        }
+        Ok(edits)
+    }
+
+    fn chunks(&self) -> Vec<EditsChunk<'_>> {
+        let max = self.max_edits_per_request.max(1);
+        let (mut adds, mut updates, mut deletes) = (
+            self.adds.as_slice(),
+            self.updates.as_slice(),
+            self.deletes.as_slice(),
+        );
+        let mut chunks = vec![];
+        loop {
+            let mut room = max;
+            let chunk = EditsChunk {
+                adds: take_edits(&mut adds, &mut room),
+                updates: take_edits(&mut updates, &mut room),
+                deletes: take_edits(&mut deletes, &mut room),
+            };
+            chunks.push(chunk);
+            if adds.is_empty() && updates.is_empty() && deletes.is_empty() {
+                return chunks;
+            }
+        }
+    }
+
+    async fn send_chunk(
+        &self,
+        client: &ArcGISClient,
         url: &str,
+        chunk: &EditsChunk<'_>,
     ) -> Result<reqwest::Response, reqwest::Error> {
         let url = format!("{}/applyEdits", url);
-        //println!("params: {:?}", self.params);
-        client.post(url).form(&self.params).send().await
+        match self.body_format {
+            BodyFormat::Form => {
+                client
+                    .send_raw(client.post(url).form(&self.params(chunk)))
+                    .await
+            }
+            BodyFormat::Json => {
+                client
+                    .send_raw(
+                        client
+                            .post(url)
+                            .query(&[("f", "json")])
+                            .json(&self.body(chunk)),
+                    )
+                    .await
+            }
+        }
+    }
+
+    fn body(&self, chunk: &EditsChunk) -> serde_json::Value {
+        serde_json::json!({
+            "adds": chunk.adds,
+            "updates": chunk.updates,
+            "deletes": chunk.deletes,
+            "returnEditResults": self.return_edit_results,
+            "rollbackOnFailure": self.rollback_on_failure,
+            "useGlobalIds": self.use_global_ids,
+            "returnEditMoment": false,
+            "trueCurveClient": true,
+            "timeReferenceUnknownClient": false,
+            "async": false,
+        })
+    }
+
+    fn params(&self, chunk: &EditsChunk) -> HashMap<String, String> {
+        let mut params: HashMap<String, String> = HashMap::new();
+        params.insert(
+            "adds".into(),
+            serde_json::to_string(chunk.adds).unwrap_or("".to_string()),
+        );
+        params.insert(
+            "updates".into(),
+            serde_json::to_string(chunk.updates).unwrap_or("".to_string()),
+        );
+        params.insert(
+            "deletes".into(),
+            serde_json::to_string(chunk.deletes).unwrap_or("".to_string()),
+        );
+        params.insert(
+            "returnEditResults".into(),
+            self.return_edit_results.to_string(),
+        );
+        params.insert("gdbVersion".into(), "".into());
+        params.insert(
+            "rollbackOnFailure".into(),
+            self.rollback_on_failure.to_string(),
+        );
+        params.insert("useGlobalIds".into(), self.use_global_ids.to_string());
+        params.insert("returnEditMoment".into(), "false".into());
+        params.insert("trueCurveClient".into(), "true".into());
+        params.insert("attachments".into(), "".into());
+        params.insert("timeReferenceUnknownClient".into(), "false".into());
+        params.insert("datumTransformation".into(), "".into());
+        params.insert("editsUploadId".into(), "".into());
+        params.insert("async".into(), "false".into());
+        params.insert("f".into(), "json".into());
+        params
     }
 }
 
@@ -43,6 +289,16 @@ pub struct ApplyEditsQueryBuilder {
     updates: Vec<serde_json::Value>,
     deletes: Vec<serde_json::Value>,
     return_edit_results: bool,
+    rollback_on_failure: bool,
+    use_global_ids: bool,
+    body_format: BodyFormat,
+    max_edits_per_request: usize,
+}
+
+impl Default for ApplyEditsQueryBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl ApplyEditsQueryBuilder {
@@ -52,6 +308,10 @@ impl ApplyEditsQueryBuilder {
             updates: vec![],
             deletes: vec![],
             return_edit_results: true,
+            rollback_on_failure: true,
+            use_global_ids: false,
+            body_format: BodyFormat::Form,
+            max_edits_per_request: DEFAULT_MAX_EDITS_PER_REQUEST,
         }
     }
 
@@ -70,44 +330,77 @@ impl ApplyEditsQueryBuilder {
         self
     }
 
+    pub fn set_add_features(self, adds: Vec<EsriFeature>) -> ApplyEditsQueryBuilder {
+        self.set_adds(features_to_values(adds))
+    }
+
+    pub fn set_update_features(self, updates: Vec<EsriFeature>) -> ApplyEditsQueryBuilder {
+        self.set_updates(features_to_values(updates))
+    }
+
+    /// Deletes by object id, or by global id with `set_use_global_ids`.
+    pub fn set_delete_ids<T: Into<serde_json::Value>>(self, ids: Vec<T>) -> ApplyEditsQueryBuilder {
+        self.set_deletes(ids.into_iter().map(Into::into).collect())
+    }
+
+    /// When true (the default), no edit is applied unless all succeed.
+    pub fn set_rollback_on_failure(mut self, rollback_on_failure: bool) -> ApplyEditsQueryBuilder {
+        self.rollback_on_failure = rollback_on_failure;
+        self
+    }
+
+    /// Identifies updated and deleted features by `GlobalID` instead of
+    /// object id.
+    pub fn set_use_global_ids(mut self, use_global_ids: bool) -> ApplyEditsQueryBuilder {
+        self.use_global_ids = use_global_ids;
+        self
+    }
+
     pub fn set_return_edit_results(mut self, return_edit_results: bool) -> ApplyEditsQueryBuilder {
         self.return_edit_results = return_edit_results;
         self
     }
 
-    pub fn build(self) -> ApplyEditsQuery {
-        let mut params: HashMap<String, String> = HashMap::new();
-        params.insert(
-            "adds".into(),
-            serde_json::to_string(&self.adds).unwrap_or("".to_string()), //.replace("\"", "")
-                                                                         //.replace(" ", ""),
-        );
-        params.insert(
-            "updates".into(),
-            serde_json::to_string(&self.updates).unwrap_or("".to_string()), //.replace("\"", "")
-                                                                            //.replace(" ", ""),
-        );
-        params.insert(
-            "deletes".into(),
-            serde_json::to_string(&self.deletes).unwrap_or("".to_string()), //.replace("\"", "")
-                                                                            //.replace(" ", ""),
-        );
-        params.insert(
-            "returnEditResults".into(),
-            self.return_edit_results.to_string(),
-        );
-        params.insert("gdbVersion".into(), "".into());
-        params.insert("rollbackOnFailure".into(), "true".into());
-        params.insert("useGlobalIds".into(), "false".into());
-        params.insert("returnEditMoment".into(), "false".into());
-        params.insert("trueCurveClient".into(), "true".into());
-        params.insert("attachments".into(), "".into());
-        params.insert("timeReferenceUnknownClient".into(), "false".into());
-        params.insert("datumTransformation".into(), "".into());
-        params.insert("editsUploadId".into(), "".into());
-        params.insert("async".into(), "false".into());
-        params.insert("f".into(), "json".into());
+    pub fn set_body_format(mut self, body_format: BodyFormat) -> ApplyEditsQueryBuilder {
+        self.body_format = body_format;
+        self
+    }
 
-        ApplyEditsQuery { params }
+    /// Caps the edits sent per request by [`ApplyEditsQuery::send_edits`],
+    /// see there for what splitting means for `rollbackOnFailure`.
+    pub fn set_max_edits_per_request(mut self, max_edits: usize) -> ApplyEditsQueryBuilder {
+        self.max_edits_per_request = max_edits;
+        self
     }
+
+    pub fn build(self) -> ApplyEditsQuery {
+        ApplyEditsQuery {
+            adds: self.adds,
+            updates: self.updates,
+            deletes: self.deletes,
+            return_edit_results: self.return_edit_results,
+            rollback_on_failure: self.rollback_on_failure,
+            use_global_ids: self.use_global_ids,
+            body_format: self.body_format,
+            max_edits_per_request: self.max_edits_per_request,
+        }
+    }
+}
+
+fn features_to_values(features: Vec<EsriFeature>) -> Vec<serde_json::Value> {
+    features
+        .into_iter()
+        .map(|f| serde_json::to_value(f).expect("features serialize"))
+        .collect()
+}
+
+/// Splits off up to `room` edits from the front of `edits`.
+fn take_edits<'a>(
+    edits: &mut &'a [serde_json::Value],
+    room: &mut usize,
+) -> &'a [serde_json::Value] {
+    let (head, tail) = edits.split_at((*room).min(edits.len()));
+    *room -= head.len();
+    *edits = tail;
+    head
 }
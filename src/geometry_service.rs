@@ -0,0 +1,121 @@
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::{
+    client::ArcGISClient, feature_layer_query::GeometryType, parser::parse_response,
+    retry::SendWithRetry,
+};
+
+/// A `GeometryServer`, e.g. `https://utility.arcgisonline.com/arcgis/rest/services/Geometry/GeometryServer`.
+#[derive(Debug, Clone)]
+pub struct GeometryService {
+    pub url: String,
+    client: ArcGISClient,
+}
+
+/// Linear units for [`GeometryService::buffer`] distances.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinearUnit {
+    Meter,
+    Kilometer,
+    Foot,
+    UsSurveyFoot,
+    StatuteMile,
+}
+
+impl LinearUnit {
+    /// The `esriSRUnit_*` well-known id.
+    pub fn wkid(&self) -> u32 {
+        match self {
+            LinearUnit::Meter => 9001,
+            LinearUnit::Kilometer => 9036,
+            LinearUnit::Foot => 9002,
+            LinearUnit::UsSurveyFoot => 9003,
+            LinearUnit::StatuteMile => 9093,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct GeometriesResponse {
+    geometries: Vec<Value>,
+}
+
+impl GeometryService {
+    pub fn new(client: impl Into<ArcGISClient>, url: impl Into<String>) -> Self {
+        let client = client.into();
+        Self {
+            url: url.into(),
+            client: client.clone(),
+        }
+    }
+
+    /// Reprojects `geometries` of `geometry_type` from `in_sr` to `out_sr`,
+    /// returned in the same order.
+    pub async fn project(
+        &self,
+        geometry_type: GeometryType,
+        geometries: &[Value],
+        in_sr: u32,
+        out_sr: u32,
+    ) -> anyhow::Result<Vec<Value>> {
+        // https://[serviceUrl]/project
+        let params = [
+            ("geometries", geometries_param(geometry_type, geometries)),
+            ("inSR", in_sr.to_string()),
+            ("outSR", out_sr.to_string()),
+            ("f", "json".to_string()),
+        ];
+        self.post("project", &params).await
+    }
+
+    /// Buffers each of `geometries` (in `in_sr`) by the matching distance
+    /// in `distances`, or by a single distance for all of them, returning
+    /// polygons in `out_sr`.
+    pub async fn buffer(
+        &self,
+        geometry_type: GeometryType,
+        geometries: &[Value],
+        in_sr: u32,
+        distances: &[f64],
+        unit: LinearUnit,
+        out_sr: u32,
+    ) -> anyhow::Result<Vec<Value>> {
+        // https://[serviceUrl]/buffer
+        let distances = distances
+            .iter()
+            .map(|d| d.to_string())
+            .collect::<Vec<_>>()
+            .join(",");
+        let params = [
+            ("geometries", geometries_param(geometry_type, geometries)),
+            ("inSR", in_sr.to_string()),
+            ("outSR", out_sr.to_string()),
+            ("distances", distances),
+            ("unit", unit.wkid().to_string()),
+            ("unionResults", "false".to_string()),
+            ("f", "json".to_string()),
+        ];
+        self.post("buffer", &params).await
+    }
+
+    async fn post(&self, operation: &str, params: &[(&str, String)]) -> anyhow::Result<Vec<Value>> {
+        let response = self
+            .client
+            .post(format!("{}/{}", self.url, operation))
+            .form(params)
+            .send_retrying(&self.client)
+            .await?;
+        Ok(parse_response::<GeometriesResponse>(response)
+            .await?
+            .geometries)
+    }
+}
+
+fn geometries_param(geometry_type: GeometryType, geometries: &[Value]) -> String {
+    serde_json::json!({
+        "geometryType": geometry_type.to_esri_string(),
+        "geometries": geometries,
+    })
+    .to_string()
+}
@@ -1,9 +1,8 @@
 use std::collections::HashMap;
 
-use reqwest::Client;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
-use crate::parser::parse_response;
+use crate::{client::ArcGISClient, parser::parse_response, retry::SendWithRetry};
 
 pub struct CreateGroupQuery {
     url: String,
@@ -39,8 +38,13 @@ impl CreateGroupQuery {
         CreateGroupQueryBuilder::new(root, title)
     }
 
-    pub async fn send(&self, client: &Client) -> anyhow::Result<GroupResponse> {
-        let response = client.post(&self.url).form(&self.params).send().await?;
+    pub async fn send(&self, client: impl Into<ArcGISClient>) -> anyhow::Result<GroupResponse> {
+        let client = client.into();
+        let response = client
+            .post(&self.url)
+            .form(&self.params)
+            .send_retrying(&client)
+            .await?;
         let body = parse_response::<GroupResponse>(response).await?;
         Ok(body)
     }
@@ -150,7 +154,7 @@ impl CreateGroupQueryBuilder {
     pub fn build(self) -> CreateGroupQuery {
         let mut params = HashMap::new();
         params.insert("title".into(), self.title);
-        params.insert("access".into(), format!("{:?}", self.access).to_lowercase());
+        params.insert("access".into(), self.access.as_str().to_string());
 
         if let Some(desc) = self.description {
             params.insert("description".into(), desc);
@@ -173,11 +177,11 @@ impl CreateGroupQueryBuilder {
         }
 
         if let Some(sf) = self.sort_field {
-            params.insert("sortField".into(), format!("{:?}", sf).to_lowercase());
+            params.insert("sortField".into(), sf.as_str().into());
         }
 
         if let Some(so) = self.sort_order {
-            params.insert("sortOrder".into(), format!("{:?}", so).to_lowercase());
+            params.insert("sortOrder".into(), so.as_str().into());
         }
 
         if self.is_view_only {
@@ -271,7 +275,7 @@ pub struct Group {
     #[serde(rename = "membershipAccess")]
     pub membership_access: String,
 
-    pub access: String,
+    pub access: AccessLevel,
     pub owner: String,
 
     pub description: Option<String>,
@@ -384,21 +388,33 @@ pub struct GroupInput {
     pub contribute: Option<Contributors>,
 }
 
-#[derive(Debug, Deserialize)]
+/// Who can see an item or group. `Shared` only applies to items shared
+/// with groups; unknown values are kept in `Other`.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum AccessLevel {
+    #[default]
     Private,
     Org,
     Public,
+    Shared,
+    #[serde(untagged)]
+    Other(String),
 }
 
-impl Default for AccessLevel {
-    fn default() -> Self {
-        AccessLevel::Private
+impl AccessLevel {
+    pub fn as_str(&self) -> &str {
+        match self {
+            AccessLevel::Private => "private",
+            AccessLevel::Org => "org",
+            AccessLevel::Public => "public",
+            AccessLevel::Shared => "shared",
+            AccessLevel::Other(other) => other,
+        }
     }
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum SortField {
     Title,
@@ -409,13 +425,35 @@ pub enum SortField {
     Modified,
 }
 
-#[derive(Debug, Deserialize)]
+impl SortField {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SortField::Title => "title",
+            SortField::Owner => "owner",
+            SortField::Avgrating => "avgrating",
+            SortField::Numviews => "numviews",
+            SortField::Created => "created",
+            SortField::Modified => "modified",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum SortOrder {
     Asc,
     Desc,
 }
 
+impl SortOrder {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SortOrder::Asc => "asc",
+            SortOrder::Desc => "desc",
+        }
+    }
+}
+
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub enum Capability {
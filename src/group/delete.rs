@@ -2,7 +2,9 @@ use std::collections::HashMap;
 
 use serde::Deserialize;
 
-use crate::{group::create::Group, parser::parse_response};
+use crate::{
+    client::ArcGISClient, group::create::Group, parser::parse_response, retry::SendWithRetry,
+};
 
 #[derive(Deserialize)]
 pub struct DeleteGroupResponse {
@@ -15,9 +17,10 @@ impl Group {
     pub async fn delete(
         &self,
         portal: impl Into<String>,
-        client: &reqwest::Client,
+        client: impl Into<ArcGISClient>,
         group_id: impl Into<String>,
     ) -> anyhow::Result<DeleteGroupResponse> {
+        let client = client.into();
         let url = format!(
             "{}/community/groups/{}/delete",
             portal.into(),
@@ -27,7 +30,11 @@ impl Group {
         let mut params: HashMap<String, String> = HashMap::new();
         params.insert("f".into(), "json".into());
 
-        let response = client.post(url).form(&params).send().await?;
+        let response = client
+            .post(url)
+            .form(&params)
+            .send_retrying(&client)
+            .await?;
         let body = parse_response::<DeleteGroupResponse>(response).await?;
         Ok(body)
     }
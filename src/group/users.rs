@@ -0,0 +1,34 @@
+use serde::Deserialize;
+
+use crate::{
+    client::ArcGISClient, group::create::Group, parser::parse_response, retry::SendWithRetry,
+};
+
+#[derive(Debug, Deserialize)]
+pub struct GroupUsers {
+    pub owner: String,
+    #[serde(default)]
+    pub admins: Vec<String>,
+    #[serde(default)]
+    pub users: Vec<String>,
+}
+
+impl Group {
+    /// Lists the group's owner, managers (`admins`) and members (`users`).
+    pub async fn users(
+        &self,
+        portal: impl Into<String>,
+        client: impl Into<ArcGISClient>,
+    ) -> anyhow::Result<GroupUsers> {
+        let client = client.into();
+        let url = format!("{}/community/groups/{}/users", portal.into(), self.id);
+
+        let response = client
+            .get(url)
+            .query(&[("f", "json")])
+            .send_retrying(&client)
+            .await?;
+        let body = parse_response::<GroupUsers>(response).await?;
+        Ok(body)
+    }
+}
@@ -0,0 +1,137 @@
+use reqwest::header::CONTENT_TYPE;
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::{
+    client::ArcGISClient,
+    feature_layer_query::Extent,
+    parser::{parse_response, EsriErrorResponse},
+    retry::SendWithRetry,
+};
+
+/// An `ImageServer`, e.g. found by searching for `Image Service` items.
+#[derive(Debug, Clone)]
+pub struct ImageService {
+    pub url: String,
+    pub info: ImageServiceInfo,
+    client: ArcGISClient,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ImageServiceInfo {
+    pub name: Option<String>,
+    pub description: Option<String>,
+    #[serde(default, deserialize_with = "crate::portal::deserialize_version")]
+    pub current_version: Option<(u32, u32)>,
+    pub extent: Option<Extent>,
+    pub band_count: Option<u32>,
+    /// e.g. `U8`, `F32`.
+    pub pixel_type: Option<String>,
+    pub pixel_size_x: Option<f64>,
+    pub pixel_size_y: Option<f64>,
+}
+
+/// The pixel value at a point, from `/identify`.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct IdentifyResponse {
+    pub object_id: Option<i64>,
+    pub name: Option<String>,
+    /// Band values separated by spaces, or `NoData`.
+    pub value: String,
+    pub location: Option<Value>,
+    pub properties: Option<Value>,
+}
+
+impl ImageService {
+    pub async fn new(client: impl Into<ArcGISClient>, url: &str) -> anyhow::Result<ImageService> {
+        let client = client.into();
+        let response = client
+            .get(url)
+            .query(&[("f", "json")])
+            .send_retrying(&client)
+            .await?;
+
+        let info = parse_response::<ImageServiceInfo>(response)
+            .await
+            .map_err(|e| {
+                let message = format!("Failed to fetch image service info: {}", e);
+                e.context(message)
+            })?;
+
+        Ok(ImageService {
+            url: url.to_string(),
+            info,
+            client: client.clone(),
+        })
+    }
+
+    pub fn info(&self) -> &ImageServiceInfo {
+        &self.info
+    }
+
+    /// Renders `bbox` as a `width` x `height` image in `format` (`png`,
+    /// `jpgpng`, `tiff`, ...), returning the image bytes.
+    pub async fn export_image(
+        &self,
+        bbox: &Extent,
+        size: (u32, u32),
+        format: &str,
+    ) -> anyhow::Result<Vec<u8>> {
+        // https://[serviceUrl]/exportImage
+        let mut params = vec![
+            (
+                "bbox",
+                format!("{},{},{},{}", bbox.xmin, bbox.ymin, bbox.xmax, bbox.ymax),
+            ),
+            ("size", format!("{},{}", size.0, size.1)),
+            ("format", format.to_string()),
+            ("f", "image".to_string()),
+        ];
+        if let Some(sr) = &bbox.spatial_reference {
+            params.push(("bboxSR", sr.wkid.to_string()));
+        }
+
+        let response = self
+            .client
+            .get(format!("{}/exportImage", self.url))
+            .query(&params)
+            .send_retrying(&self.client)
+            .await?
+            .error_for_status()?;
+
+        // Errors come back as JSON with a 200 status.
+        let is_json = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|v| v.contains("json"));
+        let bytes = response.bytes().await?;
+        if is_json {
+            if let Ok(error) = serde_json::from_slice::<EsriErrorResponse>(&bytes) {
+                return Err(error.into());
+            }
+        }
+        Ok(bytes.to_vec())
+    }
+
+    /// Samples the pixel value at (`x`, `y`) in spatial reference `wkid`.
+    pub async fn identify(&self, x: f64, y: f64, wkid: i32) -> anyhow::Result<IdentifyResponse> {
+        // https://[serviceUrl]/identify
+        let geometry = serde_json::json!({ "x": x, "y": y, "spatialReference": { "wkid": wkid } });
+        let response = self
+            .client
+            .get(format!("{}/identify", self.url))
+            .query(&[
+                ("geometry", geometry.to_string().as_str()),
+                ("geometryType", "esriGeometryPoint"),
+                ("returnGeometry", "false"),
+                ("returnCatalogItems", "false"),
+                ("f", "json"),
+            ])
+            .send_retrying(&self.client)
+            .await?;
+        parse_response::<IdentifyResponse>(response).await
+    }
+}
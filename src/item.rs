@@ -1,11 +1,16 @@
-use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
 
 use crate::{
-    add_item::{points_to_csv, AddItemQuery},
-    parser::parse_response,
-    publish_item::PublishItemQuery,
+    add_item::{points_to_csv, AddItemQuery, ItemStatus, JobStatus},
+    client::ArcGISClient,
+    delete_items::DeleteItemResponse,
+    feature_layer_query::Extent,
+    group::create::AccessLevel,
+    parser::{parse_response, EsriErrorResponse},
+    publish_item::{PublishItemQuery, SpatialReference},
+    retry::{NetworkError, SendWithRetry},
+    share_items::{ShareItemResult, ShareItemsQuery},
     update_item::UpdateItemQueryBuilder,
 };
 
@@ -32,12 +37,13 @@ fn validate_points(input: &[PointWithData]) -> Result<(), &'static str> {
 pub async fn create_web_map(
     portal_root: &str,
     portal_apps_root: &str,
-    client: &Client,
+    client: impl Into<ArcGISClient>,
     title: &str,
     user_name: &str,
     input_points: Vec<PointWithData>,
     token: String,
 ) -> anyhow::Result<String> {
+    let client = client.into();
     validate_points(&input_points).unwrap();
     let csv = points_to_csv(&input_points).unwrap();
 
@@ -64,6 +70,7 @@ pub async fn create_web_map(
 
     let publish_item_response = PublishItemQuery::builder(portal_root, user_name, item_id.clone())
         .name(title.to_string())
+        .source_item_type("CSV")
         // TODO: remove this and add dynamic field detection from uploaded CSV
         .additional_fields(field_names.clone())
         .token(token.clone())
@@ -213,7 +220,7 @@ pub async fn create_web_map(
         .text(map_json.to_string())
         .token(token)
         .build()
-        .send(&client)
+        .send(client)
         .await?;
 
     let map_url = format!(
@@ -224,29 +231,15 @@ pub async fn create_web_map(
     Ok(map_url)
 }
 
-// TODO: check job status
-// async fn check_job_status(config: &Settings, client: &Client, job_id: &str) -> anyhow::Result<()> {
-//     let mut status = String::new();
-//     loop {
-//         let job_status = JobStatusQuery::builder(&config.portal_root, job_id)
-//             .build()
-//             .send(&client)
-//             .await?;
-//         status = job_status.status;
-//         if status == "succeeded" {
-//             break;
-//         }
-//         tokio::time::sleep(std::time::Duration::from_secs(1)).await;
-//     }
-//     Ok(())
-// }
-
 #[derive(Default, Serialize, Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct ItemData {
     pub id: String,
     pub owner: String,
     pub title: String,
+    #[serde(rename = "type")]
+    pub r#type: String,
+    pub snippet: Option<String>,
     pub created: u64,
     pub modified: u64,
     pub type_keywords: Vec<String>,
@@ -256,11 +249,118 @@ pub struct ItemData {
     pub categories: Vec<String>,
     pub url: Option<String>,
     pub thumbnail: Option<String>,
+    /// `[[xmin, ymin], [xmax, ymax]]` in WGS84; empty when not set.
+    #[serde(default)]
+    pub extent: Vec<Vec<f64>>,
+    /// Name or wkid of the item's native spatial reference, e.g.
+    /// `WGS_1984_Web_Mercator_Auxiliary_Sphere`.
+    pub spatial_reference: Option<String>,
+    #[serde(default)]
+    pub num_views: u64,
+    #[serde(default)]
+    pub num_ratings: u64,
+    #[serde(default)]
+    pub avg_rating: f64,
+    #[serde(default)]
+    pub num_comments: u64,
+    /// Delete protection; see [`Item::protect`].
+    #[serde(default)]
+    pub protected: bool,
+    pub access: Option<AccessLevel>,
+    /// Size of the item's data in bytes.
+    pub size: Option<i64>,
+    pub name: Option<String>,
+    /// Id of the owner's folder holding the item; `None` for the root folder.
+    pub owner_folder: Option<String>,
+    pub org_id: Option<String>,
+    pub guid: Option<String>,
+    pub access_information: Option<String>,
+    pub license_info: Option<String>,
+    pub culture: Option<String>,
+    pub properties: Option<serde_json::Value>,
+    pub proxy_filter: Option<serde_json::Value>,
+    pub documentation: Option<String>,
+    pub banner: Option<String>,
+    pub large_thumbnail: Option<String>,
+    #[serde(default)]
+    pub screenshots: Vec<String>,
+    #[serde(default)]
+    pub app_categories: Vec<String>,
+    #[serde(default)]
+    pub industries: Vec<String>,
+    #[serde(default)]
+    pub languages: Vec<String>,
+    pub listed: Option<bool>,
+    pub comments_enabled: Option<bool>,
+    /// e.g. `org_authoritative` or `deprecated`.
+    pub content_status: Option<String>,
+    pub group_designations: Option<String>,
+    /// Milliseconds since the epoch.
+    pub last_viewed: Option<i64>,
+}
+
+impl ItemData {
+    /// Whether the item is a hosted service, as opposed to one registered
+    /// by URL, which ArcGIS marks with the `Hosted Service` type keyword.
+    pub fn is_hosted(&self) -> bool {
+        self.has_type_keyword("Hosted Service")
+    }
+
+    pub fn is_feature_service(&self) -> bool {
+        self.r#type == "Feature Service"
+    }
+
+    pub fn is_web_map(&self) -> bool {
+        self.r#type == "Web Map"
+    }
+
+    fn has_type_keyword(&self, keyword: &str) -> bool {
+        self.type_keywords
+            .iter()
+            .any(|k| k.eq_ignore_ascii_case(keyword))
+    }
+}
+
+/// An item `type`, e.g. `Feature Service`. Types without a variant go in
+/// `Other` with their exact name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ItemType {
+    FeatureService,
+    MapService,
+    ImageService,
+    VectorTileService,
+    WebMap,
+    WebMappingApplication,
+    Csv,
+    Shapefile,
+    FileGeodatabase,
+    GeoJson,
+    ServiceDefinition,
+    Other(String),
+}
+
+impl ItemType {
+    pub fn as_str(&self) -> &str {
+        match self {
+            ItemType::FeatureService => "Feature Service",
+            ItemType::MapService => "Map Service",
+            ItemType::ImageService => "Image Service",
+            ItemType::VectorTileService => "Vector Tile Service",
+            ItemType::WebMap => "Web Map",
+            ItemType::WebMappingApplication => "Web Mapping Application",
+            ItemType::Csv => "CSV",
+            ItemType::Shapefile => "Shapefile",
+            ItemType::FileGeodatabase => "File Geodatabase",
+            ItemType::GeoJson => "GeoJson",
+            ItemType::ServiceDefinition => "Service Definition",
+            ItemType::Other(item_type) => item_type,
+        }
+    }
 }
 
 pub struct Item {
     root: String,
-    client: Client,
+    client: ArcGISClient,
     pub id: String,
     pub data: ItemData,
 }
@@ -268,14 +368,15 @@ pub struct Item {
 impl Item {
     pub async fn new(
         root: impl Into<String>,
-        client: &Client,
+        client: impl Into<ArcGISClient>,
         id: impl Into<String>,
     ) -> anyhow::Result<Self> {
+        let client = client.into();
         // Fetch item
 
         let root = root.into();
         let id = id.into();
-        let item_data = Self::fetch_item_data(&root, &id, client).await?;
+        let item_data = Self::fetch_item_data(&root, &id, &client).await?;
 
         Ok(Self {
             root,
@@ -285,23 +386,40 @@ impl Item {
         })
     }
 
-    async fn fetch_item_data(root: &str, id: &str, client: &Client) -> anyhow::Result<ItemData> {
+    async fn fetch_item_data(
+        root: &str,
+        id: &str,
+        client: &ArcGISClient,
+    ) -> anyhow::Result<ItemData> {
         let url = format!("{}/content/items/{}?f=json", &root, &id);
-        let response = client.get(url).send().await?;
+        let response = client.get(url).send_retrying(client).await?;
         let item_data = parse_response::<ItemData>(response).await?;
         Ok(item_data)
     }
 
+    /// The item extent as an envelope. Item extents are always WGS84.
+    pub fn extent_bbox(&self) -> Option<Extent> {
+        match self.data.extent.as_slice() {
+            [min, max] if min.len() == 2 && max.len() == 2 => Some(Extent {
+                xmin: min[0],
+                ymin: min[1],
+                xmax: max[0],
+                ymax: max[1],
+                spatial_reference: Some(SpatialReference {
+                    wkid: 4326,
+                    latest_wkid: Some(4326),
+                }),
+            }),
+            _ => None,
+        }
+    }
+
     pub fn update_builder(&self) -> UpdateItemQueryBuilder {
         UpdateItemQueryBuilder::new(&self.root, &self.data.owner, &self.id)
     }
 
     pub async fn update(&mut self, builder: UpdateItemQueryBuilder) -> anyhow::Result<()> {
-        let response = builder
-            .build()
-            .send(&self.client)
-            .await
-            .expect("Failed to send update item query");
+        let response = builder.build().send(&self.client).await?;
 
         if !response.success {
             return Err(anyhow::anyhow!("Failed to update item"));
@@ -313,4 +431,408 @@ impl Item {
 
         Ok(())
     }
+
+    /// Controls who can see the item: everyone, the organization, and/or
+    /// `groups`. Items are private until shared.
+    pub async fn share(
+        &self,
+        everyone: bool,
+        org: bool,
+        groups: Vec<String>,
+    ) -> anyhow::Result<ShareItemResult> {
+        let response =
+            ShareItemsQuery::builder(&self.root, &self.data.owner, vec![self.id.clone()])
+                .everyone(everyone)
+                .org(org)
+                .groups(groups)
+                .build()
+                .send(&self.client)
+                .await?;
+        response
+            .results
+            .into_iter()
+            .find(|r| r.item_id == self.id)
+            .ok_or_else(|| anyhow::anyhow!("shareItems returned no result for item {}", self.id))
+    }
+
+    /// Moves the item into the owner's folder `folder_id`, or back to the
+    /// root folder with `/`.
+    pub async fn move_to_folder(&self, folder_id: &str) -> anyhow::Result<()> {
+        // https://[root]/content/users/[userName]/moveItems
+        let url = format!(
+            "{}/content/users/{}/moveItems",
+            self.root,
+            urlencoding::encode(&self.data.owner)
+        );
+        let response = self
+            .client
+            .post(url)
+            .form(&[
+                ("items", self.id.as_str()),
+                ("folder", folder_id),
+                ("f", "json"),
+            ])
+            .send_retrying(&self.client)
+            .await?;
+        let response = parse_response::<UpdateItemsResponse>(response).await?;
+
+        if !response
+            .results
+            .iter()
+            .any(|r| r.item_id == self.id && r.success)
+        {
+            return Err(anyhow::anyhow!(
+                "Failed to move {} to folder {}",
+                self.id,
+                folder_id
+            ));
+        }
+        Ok(())
+    }
+
+    /// Protects the item from deletion until [`Item::unprotect`] is called,
+    /// e.g. for reference datasets a cleanup job must not remove.
+    pub async fn protect(&self) -> anyhow::Result<ProtectItemResponse> {
+        self.set_protection("protect").await
+    }
+
+    pub async fn unprotect(&self) -> anyhow::Result<ProtectItemResponse> {
+        self.set_protection("unprotect").await
+    }
+
+    async fn set_protection(&self, operation: &str) -> anyhow::Result<ProtectItemResponse> {
+        // https://[root]/content/users/[userName]/items/[itemID]/protect
+        let url = format!(
+            "{}/content/users/{}/items/{}/{}",
+            self.root,
+            urlencoding::encode(&self.data.owner),
+            self.id,
+            operation
+        );
+        let response = self
+            .client
+            .post(url)
+            .form(&[("f", "json")])
+            .send_retrying(&self.client)
+            .await?;
+        parse_response::<ProtectItemResponse>(response).await
+    }
+
+    /// Transfers the item to `target_user_name`, into `target_folder` (a
+    /// folder name) or their root folder. Requires administrator
+    /// privileges; without them the portal's permission error is returned
+    /// as an [`crate::parser::EsriErrorResponse`].
+    pub async fn reassign(
+        &self,
+        target_user_name: &str,
+        target_folder: Option<&str>,
+    ) -> anyhow::Result<ReassignItemResponse> {
+        // https://[root]/content/users/[userName]/items/[itemID]/reassign
+        let url = format!(
+            "{}/content/users/{}/items/{}/reassign",
+            self.root,
+            urlencoding::encode(&self.data.owner),
+            self.id
+        );
+        let mut params = vec![("targetUsername", target_user_name), ("f", "json")];
+        if let Some(folder) = target_folder {
+            params.push(("targetFoldername", folder));
+        }
+        let response = self
+            .client
+            .post(url)
+            .form(&params)
+            .send_retrying(&self.client)
+            .await?;
+        parse_response::<ReassignItemResponse>(response).await
+    }
+
+    /// Status of the publish job `job_id` (from
+    /// [`crate::publish_item::PublishItemService::job_id`]); call on the
+    /// service item.
+    pub async fn job_status(&self, job_id: &str) -> anyhow::Result<ItemStatus> {
+        // https://[root]/content/users/[userName]/items/[itemID]/status
+        let url = format!(
+            "{}/content/users/{}/items/{}/status",
+            self.root,
+            urlencoding::encode(&self.data.owner),
+            self.id
+        );
+        let response = self
+            .client
+            .get(url)
+            .query(&[("jobId", job_id), ("jobType", "publish"), ("f", "json")])
+            .send_retrying(&self.client)
+            .await?;
+        // Gateways answer 5xx with HTML, so keep the status rather than a
+        // JSON decode error.
+        if let Err(e) = response.error_for_status_ref() {
+            if response.status().is_server_error() {
+                return Err(e.into());
+            }
+        }
+        parse_response::<ItemStatus>(response).await
+    }
+
+    /// Polls every `poll_interval` until the publish job completes, so the
+    /// service isn't queried before it's ready. Fails if the job fails or
+    /// is still running after `timeout`. A status request that fails
+    /// transiently (a 502 while the service is provisioning, a dropped
+    /// connection) is logged and polled again; other errors, such as an
+    /// invalid token, a missing permission or an unknown job id, end the
+    /// wait at once.
+    pub async fn wait_for_publish(
+        &self,
+        job_id: &str,
+        poll_interval: std::time::Duration,
+        timeout: std::time::Duration,
+    ) -> anyhow::Result<()> {
+        let mut last_error = None;
+        let poll = async {
+            loop {
+                match self.job_status(job_id).await {
+                    Ok(status) if status.status == JobStatus::Completed => return Ok(()),
+                    Ok(status) if status.status.is_finished() => anyhow::bail!(
+                        "Publishing {} failed: {}",
+                        self.id,
+                        status.status_message.unwrap_or_default()
+                    ),
+                    Ok(_) => {}
+                    Err(e) if is_transient(&e) => {
+                        tracing::warn!(item_id = %self.id, error = %e, "Failed to poll publish job");
+                        last_error = Some(e);
+                    }
+                    Err(e) => return Err(e),
+                }
+                tokio::time::sleep(poll_interval).await;
+            }
+        };
+        match tokio::time::timeout(timeout, poll).await {
+            Ok(result) => result,
+            Err(_) => {
+                let error =
+                    anyhow::anyhow!("Publishing {} did not finish within {:?}", self.id, timeout);
+                Err(match last_error {
+                    Some(e) => e.context(error),
+                    None => error,
+                })
+            }
+        }
+    }
+
+    /// Deletes the item, e.g. to clean up a temporary CSV or web map.
+    /// Delete-protected items are refused by the portal.
+    pub async fn delete(&self) -> anyhow::Result<DeleteItemResponse> {
+        // https://[root]/content/users/[userName]/items/[itemID]/delete
+        let url = format!(
+            "{}/content/users/{}/items/{}/delete",
+            self.root,
+            urlencoding::encode(&self.data.owner),
+            self.id
+        );
+        let response = self
+            .client
+            .post(url)
+            .form(&[("f", "json")])
+            .send_retrying(&self.client)
+            .await?;
+        parse_response::<DeleteItemResponse>(response).await
+    }
+
+    /// Replaces the thumbnail with the image in `bytes`.
+    pub async fn set_thumbnail(
+        &mut self,
+        bytes: Vec<u8>,
+        file_name: impl Into<String>,
+    ) -> anyhow::Result<()> {
+        let builder = self.update_builder().thumbnail_file(bytes, file_name);
+        self.update(builder).await
+    }
+
+    /// Replaces the thumbnail with the image at `url`, which the portal
+    /// downloads itself.
+    pub async fn set_thumbnail_from_url(&mut self, url: impl Into<String>) -> anyhow::Result<()> {
+        let builder = self.update_builder().thumbnail_url(url);
+        self.update(builder).await
+    }
+
+    /// Copies the item to another portal under `user_name`, returning the new
+    /// item id.
+    ///
+    /// The item data is downloaded and re-added with the same type, title,
+    /// description, snippet, tags and type keywords. Only self-contained
+    /// types are supported: `CSV`, and `Web Map` when its layers use
+    /// absolute urls that the target can reach. Services, and items that
+    /// depend on other items (hosted layers, views, apps), are rejected,
+    /// since their dependencies would still point at the source portal.
+    /// The thumbnail, metadata, sharing and categories are not copied.
+    pub async fn clone_to(
+        &self,
+        target_root: &str,
+        target_client: impl Into<ArcGISClient>,
+        user_name: &str,
+    ) -> anyhow::Result<String> {
+        let target_client = target_client.into();
+        let data = match self.data.r#type.as_str() {
+            "CSV" | "Web Map" => self.fetch_data().await?,
+            other => {
+                return Err(anyhow::anyhow!(
+                    "Cloning {} items to another portal is not supported",
+                    other
+                ))
+            }
+        };
+
+        let builder = AddItemQuery::builder(target_root, user_name)
+            .set_type(self.data.r#type.clone())
+            .title(self.data.title.clone())
+            .tags(self.data.tags.clone())
+            .type_keywords(self.data.type_keywords.clone());
+        let builder = match self.data.r#type.as_str() {
+            "CSV" => builder.file(data),
+            _ => builder.text(data),
+        };
+        let builder = match &self.data.description {
+            Some(description) => builder.description(description.clone()),
+            None => builder,
+        };
+        let builder = match &self.data.snippet {
+            Some(snippet) => builder.snippet(snippet.clone()),
+            None => builder,
+        };
+
+        let response = builder.build().send(target_client).await?;
+        if !response.success {
+            return Err(anyhow::anyhow!("Failed to clone item {}", self.id));
+        }
+
+        Ok(response.id)
+    }
+
+    async fn fetch_data(&self) -> anyhow::Result<String> {
+        // https://[root]/content/items/[itemID]/data
+        let url = format!("{}/content/items/{}/data", self.root, self.id);
+        let response = self
+            .client
+            .get(url)
+            .send_retrying(&self.client)
+            .await?
+            .error_for_status()?;
+        Ok(response.text().await?)
+    }
+
+    /// Replaces the item's content categories with `categories`.
+    ///
+    /// Categories are full paths such as `/Categories/Water/Wells`.
+    pub async fn set_categories(&mut self, categories: Vec<String>) -> anyhow::Result<()> {
+        validate_categories(&categories)?;
+        self.assign_categories(categories).await
+    }
+
+    /// Adds `categories` to the ones already assigned.
+    pub async fn add_categories(&mut self, categories: Vec<String>) -> anyhow::Result<()> {
+        validate_categories(&categories)?;
+
+        let mut assigned = self.data.categories.clone();
+        for category in categories {
+            if !assigned.contains(&category) {
+                assigned.push(category);
+            }
+        }
+
+        self.assign_categories(assigned).await
+    }
+
+    /// Removes `categories` from the ones already assigned.
+    pub async fn remove_categories(&mut self, categories: Vec<String>) -> anyhow::Result<()> {
+        validate_categories(&categories)?;
+
+        let assigned = self
+            .data
+            .categories
+            .iter()
+            .filter(|c| !categories.contains(c))
+            .cloned()
+            .collect();
+
+        self.assign_categories(assigned).await
+    }
+
+    async fn assign_categories(&mut self, categories: Vec<String>) -> anyhow::Result<()> {
+        // https://[root]/content/updateItems
+        let url = format!("{}/content/updateItems", self.root);
+        let items = serde_json::json!([{ &self.id: { "categories": categories } }]);
+
+        let response = self
+            .client
+            .post(url)
+            .form(&[("items", items.to_string()), ("f", "json".into())])
+            .send_retrying(&self.client)
+            .await?;
+        let response = parse_response::<UpdateItemsResponse>(response).await?;
+
+        if !response
+            .results
+            .iter()
+            .any(|r| r.item_id == self.id && r.success)
+        {
+            return Err(anyhow::anyhow!(
+                "Failed to assign categories to {}",
+                self.id
+            ));
+        }
+
+        let item_data = Self::fetch_item_data(&self.root, &self.id, &self.client).await?;
+        self.data = item_data;
+
+        Ok(())
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ReassignItemResponse {
+    pub success: bool,
+    pub item_id: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ProtectItemResponse {
+    pub success: bool,
+}
+
+#[derive(Deserialize, Debug)]
+struct UpdateItemsResponse {
+    results: Vec<UpdateItemsResult>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct UpdateItemsResult {
+    item_id: String,
+    success: bool,
+}
+
+fn validate_categories(categories: &[String]) -> anyhow::Result<()> {
+    match categories.iter().find(|c| !c.starts_with("/Categories/")) {
+        Some(category) => Err(anyhow::anyhow!(
+            "Category paths must start with /Categories/: {}",
+            category
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Network failures and 5xx answers, which are worth polling again.
+fn is_transient(error: &anyhow::Error) -> bool {
+    if NetworkError::classify(error).is_some() {
+        return true;
+    }
+    if let Some(esri) = error.downcast_ref::<EsriErrorResponse>() {
+        return (500..600).contains(&esri.error.code);
+    }
+    error
+        .downcast_ref::<reqwest::Error>()
+        .and_then(|e| e.status())
+        .is_some_and(|status| status.is_server_error())
 }
@@ -1,19 +1,35 @@
 #![recursion_limit = "256"]
 
 pub mod auth;
+pub mod client;
 pub mod config;
 pub mod feature_layer;
+pub mod feature_layer_attachments;
 pub mod feature_layer_query;
 pub mod feature_layer_update;
+pub mod geometry_service;
 pub mod group;
+pub mod image_service;
 pub mod oauth;
 pub mod token;
 //pub mod oauth;
 pub mod add_item;
+pub mod analyze;
+pub mod delete_items;
 pub mod item;
 pub mod parser;
+#[cfg(feature = "pbf")]
+pub mod pbf;
+pub mod portal;
 pub mod publish_item;
+pub mod reassign_content;
+pub mod redact;
+pub mod retry;
+pub mod search;
+pub mod share_items;
 pub mod update_item;
+pub mod user_content;
+pub mod web_map;
 
 // pub trait Query {
 //     pub fn new() -> impl QueryBuilder;
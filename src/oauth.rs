@@ -5,10 +5,11 @@ use oauth2::{
     reqwest::async_http_client, AuthUrl, AuthorizationCode, ClientId, ClientSecret, RedirectUrl,
     TokenResponse, TokenUrl,
 };
+use secrecy::{ExposeSecret, SecretString};
 use serde::{Deserialize, Serialize};
 use tiny_http::{Header, Response};
 
-use crate::token::SpecialClient;
+use crate::{auth::refresh_access_token, client::ArcGISClient, token::SpecialClient};
 
 const HTML: &str = r#"
             <html>
@@ -27,6 +28,24 @@ pub struct TokenStore {
     pub expires_in: u64,
 }
 
+impl TokenStore {
+    /// Replaces the cached access token using the stored refresh token.
+    pub async fn refresh(
+        &mut self,
+        client: impl Into<ArcGISClient>,
+        portal: &str,
+        client_id: &str,
+    ) -> anyhow::Result<()> {
+        let client = client.into();
+        let refresh_token = SecretString::new(self.refresh_token.clone().into());
+        let response = refresh_access_token(client, portal, client_id, &refresh_token).await?;
+
+        self.access_token = response.access_token.expose_secret().to_string();
+        self.expires_in = response.expires_in.into();
+        Ok(())
+    }
+}
+
 pub async fn get_token() -> anyhow::Result<String> {
     // TODO: add to config
 
@@ -1,27 +1,74 @@
 use reqwest::Response;
 use serde::{de::DeserializeOwned, Deserialize};
 use serde_json::Value;
+use std::fmt;
+
+use crate::retry::CorrelationId;
 
 #[derive(Deserialize, Debug)]
 pub struct EsriErrorResponse {
     pub error: EsriErrorValue,
+    /// The id the request was tagged with, see
+    /// [`crate::retry::RetryPolicy::with_correlation_id`].
+    #[serde(skip)]
+    pub correlation_id: Option<String>,
 }
 
 #[derive(Deserialize, Debug)]
 pub struct EsriErrorValue {
     pub code: i32,
     pub message: String,
-    //details: Vec<String>,
+    /// Often the actionable part, e.g. which row or field failed to publish.
+    pub details: Option<Vec<String>>,
 }
 
+impl EsriErrorValue {
+    pub fn details(&self) -> &[String] {
+        self.details.as_deref().unwrap_or_default()
+    }
+
+    /// `498 Invalid token` or `499 Token required`, which ArcGIS often
+    /// returns with HTTP 200 rather than a 401.
+    pub fn is_token_error(&self) -> bool {
+        matches!(self.code, 498 | 499)
+    }
+}
+
+impl fmt::Display for EsriErrorValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ArcGIS error {}: {}", self.code, self.message)?;
+        if !self.details().is_empty() {
+            write!(f, " ({})", self.details().join("; "))?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for EsriErrorResponse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.error.fmt(f)?;
+        if let Some(id) = &self.correlation_id {
+            write!(f, " (correlation id {})", id)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for EsriErrorResponse {}
+
 // This is from arcgis-api-rs
 pub async fn parse_response<T: DeserializeOwned>(response: Response) -> anyhow::Result<T> {
+    let correlation_id = response
+        .extensions()
+        .get::<CorrelationId>()
+        .map(|id| id.0.clone());
     let json = response.json::<Value>().await?;
 
     if let Ok(result) = serde_json::from_value::<T>(json.clone()) {
         Ok(result)
-    } else if let Ok(error) = serde_json::from_value::<EsriErrorResponse>(json.clone()) {
-        Err(anyhow::anyhow!("{:?}", error))
+    } else if let Ok(mut error) = serde_json::from_value::<EsriErrorResponse>(json.clone()) {
+        error.correlation_id = correlation_id;
+        Err(error.into())
     } else {
         Err(anyhow::anyhow!("Failed to parse response: {:?}", json))
     }
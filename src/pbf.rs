@@ -0,0 +1,305 @@
+//! Decoding of `f=pbf` query responses (Esri `FeatureCollectionPBuffer`).
+//!
+//! The message definitions mirror Esri's `FeatureCollection.proto`; only the
+//! parts needed to rebuild features are kept. Geometry comes back quantized
+//! and delta encoded, and is dequantized here so the result matches an
+//! `f=json` response.
+
+use prost::Message;
+use serde_json::{json, Map, Number, Value};
+
+use crate::feature_layer_query::{EsriFeature, EsriQueryResponse, GeometryType};
+
+#[derive(Clone, PartialEq, Message)]
+pub struct FeatureCollectionPBuffer {
+    #[prost(string, tag = "1")]
+    pub version: String,
+    #[prost(message, optional, tag = "2")]
+    pub query_result: Option<QueryResult>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct QueryResult {
+    #[prost(oneof = "Results", tags = "1, 2")]
+    pub results: Option<Results>,
+}
+
+#[derive(Clone, PartialEq, prost::Oneof)]
+pub enum Results {
+    #[prost(message, tag = "1")]
+    FeatureResult(FeatureResult),
+    #[prost(message, tag = "2")]
+    CountResult(CountResult),
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct CountResult {
+    #[prost(uint64, tag = "1")]
+    pub count: u64,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct FeatureResult {
+    #[prost(string, tag = "1")]
+    pub object_id_field_name: String,
+    #[prost(int32, tag = "7")]
+    pub geometry_type: i32,
+    #[prost(message, optional, tag = "8")]
+    pub spatial_reference: Option<SpatialReference>,
+    #[prost(bool, tag = "9")]
+    pub exceeded_transfer_limit: bool,
+    #[prost(bool, tag = "10")]
+    pub has_z: bool,
+    #[prost(bool, tag = "11")]
+    pub has_m: bool,
+    #[prost(message, optional, tag = "12")]
+    pub transform: Option<Transform>,
+    #[prost(message, repeated, tag = "13")]
+    pub fields: Vec<Field>,
+    #[prost(message, repeated, tag = "15")]
+    pub features: Vec<Feature>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct SpatialReference {
+    #[prost(uint32, tag = "1")]
+    pub wkid: u32,
+    #[prost(uint32, tag = "2")]
+    pub lastest_wkid: u32,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Field {
+    #[prost(string, tag = "1")]
+    pub name: String,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Transform {
+    /// 0 = upper left, 1 = lower left
+    #[prost(int32, tag = "1")]
+    pub quantize_origin_postion: i32,
+    #[prost(message, optional, tag = "2")]
+    pub scale: Option<Scale>,
+    #[prost(message, optional, tag = "3")]
+    pub translate: Option<Translate>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Scale {
+    #[prost(double, tag = "1")]
+    pub x_scale: f64,
+    #[prost(double, tag = "2")]
+    pub y_scale: f64,
+    #[prost(double, tag = "3")]
+    pub m_scale: f64,
+    #[prost(double, tag = "4")]
+    pub z_scale: f64,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Translate {
+    #[prost(double, tag = "1")]
+    pub x_translate: f64,
+    #[prost(double, tag = "2")]
+    pub y_translate: f64,
+    #[prost(double, tag = "3")]
+    pub m_translate: f64,
+    #[prost(double, tag = "4")]
+    pub z_translate: f64,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Feature {
+    #[prost(message, repeated, tag = "1")]
+    pub attributes: Vec<AttributeValue>,
+    #[prost(message, optional, tag = "2")]
+    pub geometry: Option<Geometry>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Geometry {
+    #[prost(uint32, repeated, tag = "2")]
+    pub lengths: Vec<u32>,
+    #[prost(sint64, repeated, tag = "3")]
+    pub coords: Vec<i64>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct AttributeValue {
+    #[prost(oneof = "ValueType", tags = "1, 2, 3, 4, 5, 6, 7, 8, 9")]
+    pub value_type: Option<ValueType>,
+}
+
+#[derive(Clone, PartialEq, prost::Oneof)]
+pub enum ValueType {
+    #[prost(string, tag = "1")]
+    StringValue(String),
+    #[prost(float, tag = "2")]
+    FloatValue(f32),
+    #[prost(double, tag = "3")]
+    DoubleValue(f64),
+    #[prost(sint32, tag = "4")]
+    SintValue(i32),
+    #[prost(uint32, tag = "5")]
+    UintValue(u32),
+    #[prost(int64, tag = "6")]
+    Int64Value(i64),
+    #[prost(uint64, tag = "7")]
+    Uint64Value(u64),
+    #[prost(sint64, tag = "8")]
+    Sint64Value(i64),
+    #[prost(bool, tag = "9")]
+    BoolValue(bool),
+}
+
+const GEOMETRY_POINT: i32 = 0;
+const GEOMETRY_MULTIPOINT: i32 = 1;
+const GEOMETRY_POLYLINE: i32 = 2;
+const GEOMETRY_POLYGON: i32 = 3;
+
+/// Decodes a `f=pbf` query response body into the same shape as `f=json`.
+pub fn decode_query_response(bytes: &[u8]) -> anyhow::Result<EsriQueryResponse> {
+    let collection = FeatureCollectionPBuffer::decode(bytes)?;
+
+    let result = match collection.query_result.and_then(|q| q.results) {
+        Some(Results::FeatureResult(result)) => result,
+        Some(Results::CountResult(_)) => {
+            return Err(anyhow::anyhow!(
+                "PBF response holds a count, not features; use returnCountOnly with f=json"
+            ))
+        }
+        None => return Err(anyhow::anyhow!("PBF response holds no query result")),
+    };
+
+    let features = result
+        .features
+        .iter()
+        .map(|feature| EsriFeature {
+            attributes: attributes(&result.fields, feature),
+            geometry: feature.geometry.as_ref().map(|g| geometry(&result, g)),
+        })
+        .collect();
+
+    let geometry_type = match result.geometry_type {
+        GEOMETRY_POINT => Some(GeometryType::Point),
+        GEOMETRY_MULTIPOINT => Some(GeometryType::Multipoint),
+        GEOMETRY_POLYLINE => Some(GeometryType::Polyline),
+        GEOMETRY_POLYGON => Some(GeometryType::Polygon),
+        _ => None,
+    };
+
+    Ok(EsriQueryResponse {
+        geometry_type: geometry_type.map(|t| t.to_esri_string().to_string()),
+        spatial_reference: result.spatial_reference.as_ref().map(|sr| {
+            crate::publish_item::SpatialReference {
+                wkid: sr.wkid as i32,
+                latest_wkid: Some(sr.lastest_wkid as i32).filter(|wkid| *wkid != 0),
+            }
+        }),
+        fields: vec![],
+        features,
+        exceeded_transfer_limit: result.exceeded_transfer_limit,
+        transform: None,
+    })
+}
+
+fn attributes(fields: &[Field], feature: &Feature) -> Value {
+    let attributes: Map<String, Value> = fields
+        .iter()
+        .zip(&feature.attributes)
+        .map(|(field, value)| (field.name.clone(), attribute(value)))
+        .collect();
+    Value::Object(attributes)
+}
+
+fn attribute(value: &AttributeValue) -> Value {
+    match &value.value_type {
+        Some(ValueType::StringValue(v)) => json!(v),
+        Some(ValueType::FloatValue(v)) => {
+            Number::from_f64(*v as f64).map_or(Value::Null, Value::Number)
+        }
+        Some(ValueType::DoubleValue(v)) => Number::from_f64(*v).map_or(Value::Null, Value::Number),
+        Some(ValueType::SintValue(v)) => json!(v),
+        Some(ValueType::UintValue(v)) => json!(v),
+        Some(ValueType::Int64Value(v)) => json!(v),
+        Some(ValueType::Uint64Value(v)) => json!(v),
+        Some(ValueType::Sint64Value(v)) => json!(v),
+        Some(ValueType::BoolValue(v)) => json!(v),
+        None => Value::Null,
+    }
+}
+
+fn geometry(result: &FeatureResult, geometry: &Geometry) -> Value {
+    let stride = 2 + result.has_z as usize + result.has_m as usize;
+    let lengths = if geometry.lengths.is_empty() {
+        vec![(geometry.coords.len() / stride) as u32]
+    } else {
+        geometry.lengths.clone()
+    };
+
+    // Each part starts with an absolute position followed by deltas.
+    let mut parts = vec![];
+    let mut start = 0;
+    for length in lengths {
+        let end = (start + length as usize * stride).min(geometry.coords.len());
+        let mut previous = vec![0i64; stride];
+        let mut part = vec![];
+        for (i, vertex) in geometry.coords[start..end].chunks(stride).enumerate() {
+            for (p, c) in previous.iter_mut().zip(vertex) {
+                *p = if i == 0 { *c } else { *p + c };
+            }
+            part.push(dequantize(result, &previous));
+        }
+        parts.push(part);
+        start = end;
+    }
+
+    match result.geometry_type {
+        GEOMETRY_POINT => {
+            let point = parts
+                .first()
+                .and_then(|p| p.first())
+                .cloned()
+                .unwrap_or_default();
+            let mut object = json!({ "x": point.first(), "y": point.get(1) });
+            if result.has_z {
+                object["z"] = json!(point.get(2));
+            }
+            object
+        }
+        GEOMETRY_MULTIPOINT => json!({ "points": parts.concat() }),
+        GEOMETRY_POLYLINE => json!({ "paths": parts }),
+        GEOMETRY_POLYGON => json!({ "rings": parts }),
+        _ => Value::Null,
+    }
+}
+
+fn dequantize(result: &FeatureResult, vertex: &[i64]) -> Vec<f64> {
+    let Some(transform) = &result.transform else {
+        return vertex.iter().map(|v| *v as f64).collect();
+    };
+    let scale = transform.scale.clone().unwrap_or_default();
+    let translate = transform.translate.clone().unwrap_or_default();
+
+    let x = translate.x_translate + vertex[0] as f64 * scale.x_scale;
+    let y = if transform.quantize_origin_postion == 0 {
+        translate.y_translate - vertex[1] as f64 * scale.y_scale
+    } else {
+        translate.y_translate + vertex[1] as f64 * scale.y_scale
+    };
+
+    let mut out = vec![x, y];
+    let mut rest = vertex[2..].iter();
+    if result.has_z {
+        if let Some(z) = rest.next() {
+            out.push(translate.z_translate + *z as f64 * scale.z_scale);
+        }
+    }
+    if result.has_m {
+        if let Some(m) = rest.next() {
+            out.push(translate.m_translate + *m as f64 * scale.m_scale);
+        }
+    }
+    out
+}
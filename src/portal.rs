@@ -0,0 +1,218 @@
+use reqwest::Client;
+use serde::{Deserialize, Deserializer};
+use serde_json::Value;
+use tokio::sync::OnceCell;
+
+use crate::{
+    client::ArcGISClient, parser::parse_response, retry::SendWithRetry, user_content::UserContent,
+};
+
+/// `portals/self` for the signed-in user's organization.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PortalSelf {
+    pub id: Option<String>,
+    pub name: Option<String>,
+    pub url_key: Option<String>,
+    #[serde(default)]
+    pub is_portal: bool,
+    #[serde(default, deserialize_with = "deserialize_version")]
+    pub current_version: Option<(u32, u32)>,
+    /// ArcGIS Online only; Enterprise portals don't use credits.
+    pub subscription_info: Option<SubscriptionInfo>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SubscriptionInfo {
+    pub available_credits: Option<f64>,
+}
+
+/// The organization has fewer credits left than an operation asked for.
+#[derive(Debug)]
+pub struct InsufficientCredits {
+    pub available: f64,
+    pub required: f64,
+}
+
+impl std::fmt::Display for InsufficientCredits {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Insufficient credits: {} required, {} available",
+            self.required, self.available
+        )
+    }
+}
+
+impl std::error::Error for InsufficientCredits {}
+
+/// `community/self`: the signed-in user.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CommunitySelf {
+    pub username: String,
+    pub full_name: Option<String>,
+    pub email: Option<String>,
+    pub org_id: Option<String>,
+    /// e.g. `org_admin`, `org_publisher`, `org_user`.
+    pub role: Option<String>,
+    #[serde(default)]
+    pub privileges: Vec<String>,
+}
+
+pub struct Portal {
+    root: String,
+    client: ArcGISClient,
+    pub data: PortalSelf,
+    community_self: OnceCell<CommunitySelf>,
+}
+
+impl Portal {
+    pub async fn new(
+        root: impl Into<String>,
+        client: impl Into<ArcGISClient>,
+    ) -> anyhow::Result<Self> {
+        let client = client.into();
+        let root = root.into();
+        let data = Self::fetch_self(&root, &client).await?;
+
+        Ok(Self {
+            root,
+            client: client.clone(),
+            data,
+            community_self: OnceCell::new(),
+        })
+    }
+
+    /// Connects to `root` without credentials, for reading public items and
+    /// services. Equivalent to [`Portal::new`] with a client built from
+    /// [`crate::auth::AuthType::None`].
+    pub async fn anonymous(root: impl Into<String>) -> anyhow::Result<Self> {
+        Self::new(root, &Client::new()).await
+    }
+
+    async fn fetch_self(root: &str, client: &ArcGISClient) -> anyhow::Result<PortalSelf> {
+        // https://[root]/portals/self
+        let url = format!("{}/portals/self?f=json", root);
+        let response = client.get(url).send_retrying(client).await?;
+        parse_response::<PortalSelf>(response).await
+    }
+
+    /// The signed-in user, fetched from `community/self` on first use and
+    /// cached, since it's needed for every user content url.
+    pub async fn community_self(&self) -> anyhow::Result<&CommunitySelf> {
+        self.community_self
+            .get_or_try_init(|| async {
+                // https://[root]/community/self
+                let url = format!("{}/community/self?f=json", self.root);
+                let response = self.client.get(url).send_retrying(&self.client).await?;
+                parse_response::<CommunitySelf>(response).await
+            })
+            .await
+    }
+
+    /// The signed-in user's content, without having to know their username.
+    pub async fn content(&self) -> anyhow::Result<UserContent> {
+        let user_name = self.community_self().await?.username.clone();
+        Ok(self.content_for(user_name))
+    }
+
+    /// `user_name`'s content, e.g. for an administrator managing other
+    /// users' items.
+    pub fn content_for(&self, user_name: impl Into<String>) -> UserContent {
+        UserContent::new(&self.root, &self.client, user_name)
+    }
+
+    /// Re-fetches `portals/self` and drops the cached `community/self`.
+    /// The client's credentials are fixed, so this is only needed when the
+    /// organization or user changes underneath it, e.g. a role change.
+    pub async fn refresh_self(&mut self) -> anyhow::Result<()> {
+        self.data = Self::fetch_self(&self.root, &self.client).await?;
+        self.community_self = OnceCell::new();
+        Ok(())
+    }
+
+    pub fn root(&self) -> &str {
+        &self.root
+    }
+
+    pub fn client(&self) -> &ArcGISClient {
+        &self.client
+    }
+
+    /// `currentVersion` as `(major, minor)`, e.g. `(11, 1)` for Enterprise
+    /// 11.1. ArcGIS Online reports calendar versions such as `(2025, 1)`.
+    pub fn portal_version(&self) -> Option<(u32, u32)> {
+        self.data.current_version
+    }
+
+    /// Fails with a clear error when the portal is older than `min_version`
+    /// (`(major, minor)`), rather than letting the request fail in a
+    /// confusing way.
+    ///
+    /// An unknown version is let through.
+    pub fn require_version(&self, min_version: (u32, u32), feature: &str) -> anyhow::Result<()> {
+        match self.portal_version() {
+            Some(version) if version < min_version => Err(anyhow::anyhow!(
+                "{} requires portal version {}.{} or later, found {}.{}",
+                feature,
+                min_version.0,
+                min_version.1,
+                version.0,
+                version.1
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// The organization's remaining credits, fetched fresh since they drain
+    /// as the org works. Fails on portals that don't report credits.
+    pub async fn credits_remaining(&self) -> anyhow::Result<f64> {
+        let data = Self::fetch_self(&self.root, &self.client).await?;
+        data.subscription_info
+            .and_then(|s| s.available_credits)
+            .ok_or_else(|| anyhow::anyhow!("Portal does not report available credits"))
+    }
+
+    /// Fails with [`InsufficientCredits`] when fewer than `min` credits
+    /// remain, to check before credit-consuming work such as geocoding a
+    /// publish or generating tiles.
+    pub async fn require_credits(&self, min: f64) -> anyhow::Result<()> {
+        let available = self.credits_remaining().await?;
+        if available < min {
+            return Err(InsufficientCredits {
+                available,
+                required: min,
+            }
+            .into());
+        }
+        Ok(())
+    }
+}
+
+/// Reads `currentVersion`, which is a string on portals (`"11.1"`, `"2025.1"`)
+/// and a number on services (`11.1`), keeping only `(major, minor)`.
+///
+/// The parts are compared as integers, so `"11.10"` is newer than `"11.9"`.
+pub(crate) fn deserialize_version<'de, D>(deserializer: D) -> Result<Option<(u32, u32)>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let version = match Option::<Value>::deserialize(deserializer)? {
+        Some(Value::Number(n)) => parse_version(&n.to_string()),
+        Some(Value::String(s)) => parse_version(&s),
+        _ => None,
+    };
+    Ok(version)
+}
+
+fn parse_version(version: &str) -> Option<(u32, u32)> {
+    let mut parts = version.split('.');
+    let major = parts.next()?.trim().parse().ok()?;
+    let minor = match parts.next() {
+        Some(minor) => minor.trim().parse().ok()?,
+        None => 0,
+    };
+    Some((major, minor))
+}
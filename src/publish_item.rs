@@ -1,19 +1,26 @@
-use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use serde_urlencoded;
 use std::collections::HashMap;
 
-use crate::parser::parse_response;
+use crate::{
+    analyze::AnalyzeResponse, client::ArcGISClient, parser::parse_response, retry::SendWithRetry,
+};
 
 pub struct PublishItemQuery {
+    root: String,
     url: String,
     params: PublishItemQueryParams,
+    source_item_type: Option<String>,
 }
 
 #[derive(Default)]
 pub struct PublishItemQueryBuilder {
+    root: String,
     url: String,
+    folder: Option<String>,
+    source_item_type: Option<String>,
+    location_type: Option<LocationType>,
     params: PublishItemQueryParams,
 }
 
@@ -25,6 +32,51 @@ pub enum PublishType {
     Csv,
 }
 
+/// `fileType` of the source item being published
+///
+/// Excel items are left out: ArcGIS publishes them with CSV-style location
+/// parameters per sheet, which neither [`CsvPublishParameters`] nor
+/// [`FilePublishParameters`] model.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq)]
+pub enum FileType {
+    #[serde(rename = "csv")]
+    #[default]
+    Csv,
+    #[serde(rename = "shapefile")]
+    Shapefile,
+    #[serde(rename = "fileGeodatabase")]
+    FileGeodatabase,
+    #[serde(rename = "geojson")]
+    GeoJson,
+    #[serde(rename = "serviceDefinition")]
+    ServiceDefinition,
+}
+
+impl FileType {
+    /// The `fileType` for publishing an item of the given item `type`
+    /// (e.g. `"File Geodatabase"`), if it is publishable by this crate.
+    pub fn from_item_type(item_type: &str) -> Option<Self> {
+        match item_type {
+            "CSV" => Some(FileType::Csv),
+            "Shapefile" => Some(FileType::Shapefile),
+            "File Geodatabase" => Some(FileType::FileGeodatabase),
+            "GeoJson" => Some(FileType::GeoJson),
+            "Service Definition" => Some(FileType::ServiceDefinition),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FileType::Csv => "csv",
+            FileType::Shapefile => "shapefile",
+            FileType::FileGeodatabase => "fileGeodatabase",
+            FileType::GeoJson => "geojson",
+            FileType::ServiceDefinition => "serviceDefinition",
+        }
+    }
+}
+
 /// `locationType`: how to interpret the CSV (coords, address, lookup, none)
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, Default)]
 pub enum LocationType {
@@ -195,11 +247,19 @@ pub struct CsvPublishParameters {
     pub qualifier: Option<String>,
 
     /// Spatial reference of the input coordinates (default WKID 4326).
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(
+        rename = "sourceSR",
+        alias = "sourceSr",
+        skip_serializing_if = "Option::is_none"
+    )]
     pub source_sr: Option<SpatialReference>,
 
     /// Target spatial reference for storage (default WKID 102100 / 3857).
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(
+        rename = "targetSR",
+        alias = "targetSr",
+        skip_serializing_if = "Option::is_none"
+    )]
     pub target_sr: Option<SpatialReference>,
 
     /// Editor tracking configuration.
@@ -240,9 +300,50 @@ impl CsvPublishParameters {
         Ok(serde_urlencoded::to_string(self)?)
     }
 
+    /// The parameters as `publishParameters` JSON, e.g. to store and reuse
+    /// them when overwriting the service later.
+    pub fn to_json_value(&self) -> Value {
+        serde_json::to_value(self).expect("publish parameters always serialize")
+    }
+
+    pub fn to_json_string(&self) -> String {
+        self.to_json_value().to_string()
+    }
+
+    fn is_latitude_longitude(&self) -> bool {
+        matches!(self.location_type, LocationType::Coordinates)
+            && self.coordinate_field_name.is_none()
+    }
+
+    /// Checks that the fields required by `location_type` are set.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if self.name.is_empty() {
+            anyhow::bail!("Publish parameters require a service name");
+        }
+
+        let missing = match self.location_type {
+            LocationType::Coordinates => {
+                self.coordinate_field_name.is_none()
+                    && (self.latitude_field_name.is_none() || self.longitude_field_name.is_none())
+            }
+            LocationType::Address => {
+                self.address_fields.is_none() && self.address_template.is_none()
+            }
+            LocationType::Lookup => self.lookup_type.is_none() || self.lookup_fields.is_none(),
+            LocationType::None => false,
+        };
+        if missing {
+            anyhow::bail!(
+                "Publish parameters with locationType {:?} are missing their location fields",
+                self.location_type
+            );
+        }
+        Ok(())
+    }
+
     pub fn json(&self, name: impl Into<String>, additional_fields: Vec<String>) -> String {
         let name = name.into();
-        
+
         // Build fields array: always include Longitude and Latitude, then add additional fields
         let mut fields = serde_json::json!([
             {
@@ -260,7 +361,7 @@ impl CsvPublishParameters {
                 "sqlType": "sqlTypeDouble"
             }
         ]);
-        
+
         // Add additional fields as strings
         if let Some(fields_array) = fields.as_array_mut() {
             for field_name in &additional_fields {
@@ -273,19 +374,19 @@ impl CsvPublishParameters {
                 }));
             }
         }
-        
+
         // Build template attributes: include all fields with null values
         let mut attributes = serde_json::json!({
             "Longitude": null,
             "Latitude": null
         });
-        
+
         if let Some(attrs_obj) = attributes.as_object_mut() {
             for field_name in &additional_fields {
                 attrs_obj.insert(field_name.clone(), serde_json::Value::Null);
             }
         }
-        
+
         let json_obj = serde_json::json!({
             "type": "csv",
             "name": name,
@@ -393,11 +494,59 @@ impl CsvPublishParameters {
                 "timeZone": "UTC"
             }
         });
-        
+
         json_obj.to_string()
     }
 }
 
+/// `publishParameters` for file types that carry their own geometry
+/// (shapefile, file geodatabase, GeoJSON).
+///
+/// Only `name` is required; the schema is read from the uploaded file.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct FilePublishParameters {
+    /// (Required) Name of the service to be created.
+    pub name: String,
+
+    /// Description for the published dataset.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+
+    /// Max record count for queries.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_record_count: Option<f64>,
+
+    /// Copyright text.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub copyright_text: Option<String>,
+
+    /// Whether the resulting layer has static data (no edits).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub has_static_data: Option<bool>,
+
+    /// Target spatial reference for storage.
+    #[serde(
+        rename = "targetSR",
+        alias = "targetSr",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub target_sr: Option<SpatialReference>,
+}
+
+impl From<&CsvPublishParameters> for FilePublishParameters {
+    fn from(params: &CsvPublishParameters) -> Self {
+        Self {
+            name: params.name.clone(),
+            description: params.description.clone(),
+            max_record_count: params.max_record_count,
+            copyright_text: params.copyright_text.clone(),
+            has_static_data: params.has_static_data,
+            target_sr: params.target_sr.clone(),
+        }
+    }
+}
+
 // impl Default for CsvPublishParameters {
 //     fn default() -> Self {
 //     }
@@ -409,7 +558,7 @@ pub struct PublishItemQueryParams {
     pub item_id: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub file: Option<String>,
-    pub file_type: String,
+    pub file_type: FileType,
     pub publish_parameters: CsvPublishParameters,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub build_initial_cache: Option<bool>,
@@ -419,6 +568,14 @@ pub struct PublishItemQueryParams {
     pub token: Option<String>,
     #[serde(skip)]
     pub additional_fields: Vec<String>,
+    /// `publishParameters` suggested by `/content/features/analyze`, used
+    /// for CSVs instead of the generated coordinate-only parameters.
+    #[serde(skip)]
+    pub analyzed_parameters: Option<Value>,
+    /// Overrides the parameters derived from `publish_parameters` for
+    /// non-CSV file types.
+    #[serde(skip)]
+    pub file_publish_parameters: Option<FilePublishParameters>,
 }
 
 impl PublishItemQueryParams {
@@ -426,9 +583,29 @@ impl PublishItemQueryParams {
         let mut params = serde_json::to_value(self)?;
         // let publish_parameters_json = serde_json::to_string(&self.publish_parameters)?;
         // params["publishParameters"] = serde_json::json!(publish_parameters_json);
-        params["publishParameters"] = serde_json::json!(self
-            .publish_parameters
-            .json(self.publish_parameters.name.clone(), self.additional_fields.clone()));
+        params["publishParameters"] = match self.file_type {
+            FileType::Csv => match &self.analyzed_parameters {
+                Some(analyzed) => {
+                    let mut parameters = analyzed.clone();
+                    parameters["name"] = serde_json::json!(self.publish_parameters.name);
+                    serde_json::json!(parameters.to_string())
+                }
+                None if self.publish_parameters.is_latitude_longitude() => {
+                    serde_json::json!(self.publish_parameters.json(
+                        self.publish_parameters.name.clone(),
+                        self.additional_fields.clone()
+                    ))
+                }
+                None => serde_json::json!(self.publish_parameters.to_json_string()),
+            },
+            _ => {
+                let parameters = match &self.file_publish_parameters {
+                    Some(parameters) => parameters.clone(),
+                    None => FilePublishParameters::from(&self.publish_parameters),
+                };
+                serde_json::json!(serde_json::to_string(&parameters)?)
+            }
+        };
         Ok(serde_urlencoded::to_string(params)?)
     }
 }
@@ -451,7 +628,61 @@ pub struct PublishItemService {
     pub r#type: String,
 }
 
+/// Returned by [`PublishItemQuery::send`] when the `fileType` being published
+/// does not match the source item's type, e.g. `csv` for a `Shapefile` item.
+#[derive(Debug)]
+pub struct PublishTypeMismatch {
+    pub file_type: FileType,
+    pub item_type: String,
+}
+
+impl std::fmt::Display for PublishTypeMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Cannot publish a \"{}\" item with fileType \"{}\"",
+            self.item_type,
+            self.file_type.as_str()
+        )
+    }
+}
+
+impl std::error::Error for PublishTypeMismatch {}
+
+/// A built query always carries a complete [`CsvPublishParameters`], so
+/// converting back is lossless.
+impl From<&PublishItemQuery> for CsvPublishParameters {
+    fn from(query: &PublishItemQuery) -> Self {
+        query.params.publish_parameters.clone()
+    }
+}
+
 impl PublishItemQuery {
+    fn validate(&self) -> anyhow::Result<()> {
+        let params = &self.params.publish_parameters;
+        match (self.params.file_type, &self.params.analyzed_parameters) {
+            (FileType::Csv, None) => {
+                params.validate()?;
+                if params.layer_info["fields"]
+                    .as_array()
+                    .is_some_and(|fields| fields.is_empty())
+                {
+                    anyhow::bail!(
+                        "CSVs with locationType {:?} need additional_fields or analyzed \
+                         parameters to describe their fields",
+                        params.location_type
+                    );
+                }
+            }
+            // The analysis or the file itself describes the layer.
+            _ if params.name.is_empty() => {
+                anyhow::bail!("Publish parameters require a service name")
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
     pub fn builder(
         root: impl Into<String>,
         user_name: impl Into<String>,
@@ -460,7 +691,31 @@ impl PublishItemQuery {
         PublishItemQueryBuilder::new(root, user_name, item_id)
     }
 
-    pub async fn send(&self, client: &Client) -> anyhow::Result<PublishItemResponse> {
+    /// Publishes the item, failing before the request if the source item's
+    /// type doesn't match the `fileType` or the CSV parameters are
+    /// incomplete for their location type (see
+    /// [`CsvPublishParameters::validate`]).
+    ///
+    /// The item's type is fetched from the portal unless it was given with
+    /// [`PublishItemQueryBuilder::source_item_type`].
+    pub async fn send(
+        &self,
+        client: impl Into<ArcGISClient>,
+    ) -> anyhow::Result<PublishItemResponse> {
+        let client = client.into();
+        let item_type = match &self.source_item_type {
+            Some(item_type) => item_type.clone(),
+            None => self.fetch_item_type(&client).await?,
+        };
+        if FileType::from_item_type(&item_type) != Some(self.params.file_type) {
+            return Err(PublishTypeMismatch {
+                file_type: self.params.file_type,
+                item_type,
+            }
+            .into());
+        }
+        self.validate()?;
+
         let body = self
             .params
             .to_urlencoded()
@@ -469,11 +724,57 @@ impl PublishItemQuery {
             .post(&self.url)
             .header("Content-Type", "application/x-www-form-urlencoded")
             .body(body)
-            .send()
+            .send_retrying(&client)
             .await?;
         let body = parse_response::<PublishItemResponse>(response).await?;
         Ok(body)
     }
+
+    async fn fetch_item_type(&self, client: &ArcGISClient) -> anyhow::Result<String> {
+        // https://[root]/content/items/[itemID]
+
+        #[derive(Deserialize)]
+        struct SourceItem {
+            r#type: String,
+        }
+
+        let url = format!(
+            "{}/content/items/{}",
+            self.root,
+            urlencoding::encode(&self.params.item_id)
+        );
+        let mut query = vec![("f", "json")];
+        if let Some(token) = &self.params.token {
+            query.push(("token", token));
+        }
+        let response = client.get(url).query(&query).send_retrying(client).await?;
+        let item = parse_response::<SourceItem>(response).await?;
+        Ok(item.r#type)
+    }
+}
+
+/// Seeds a builder from an existing [`CsvPublishParameters`], e.g. ones
+/// stored from an earlier publish; pick the item to publish with
+/// [`PublishItemQueryBuilder::item`].
+///
+/// All parameters are carried over, including the location type and its
+/// address, lookup or coordinate fields. A `null` `layer_info` is generated
+/// by `build`, and latitude/longitude CSVs without
+/// [`PublishItemQueryBuilder::analyzed`] parameters are still published with
+/// the generated point layer template.
+impl From<CsvPublishParameters> for PublishItemQueryBuilder {
+    fn from(parameters: CsvPublishParameters) -> Self {
+        Self {
+            location_type: Some(parameters.location_type),
+            params: PublishItemQueryParams {
+                publish_parameters: parameters,
+                f: "json".into(),
+                file_type: FileType::Csv,
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
 }
 
 impl PublishItemQueryBuilder {
@@ -482,19 +783,35 @@ impl PublishItemQueryBuilder {
         user_name: impl Into<String>,
         item_id: impl Into<String>,
     ) -> Self {
-        // https://[root]/content/users/[userName]/publish
-
-        let url = format!("{}/content/users/{}/publish", root.into(), user_name.into());
-        // TODO: validtate url
         Self {
-            url,
             params: PublishItemQueryParams {
-                item_id: item_id.into(),
                 f: "json".into(),
-                file_type: "csv".into(),
+                file_type: FileType::Csv,
                 ..Default::default()
             },
+            ..Default::default()
         }
+        .item(root, user_name, item_id)
+    }
+
+    /// The item to publish and its owner.
+    pub fn item(
+        mut self,
+        root: impl Into<String>,
+        user_name: impl Into<String>,
+        item_id: impl Into<String>,
+    ) -> Self {
+        // https://[root]/content/users/[userName]/publish
+
+        self.root = root.into();
+        self.url = format!(
+            "{}/content/users/{}",
+            self.root,
+            urlencoding::encode(&user_name.into())
+        );
+        // TODO: validtate url
+        self.params.item_id = item_id.into();
+        self
     }
 
     pub fn name(mut self, name: impl Into<String>) -> Self {
@@ -502,6 +819,24 @@ impl PublishItemQueryBuilder {
         self
     }
 
+    /// Sets the `fileType` of the source item (defaults to CSV).
+    ///
+    /// Non-CSV types are published with [`FilePublishParameters`], so the
+    /// latitude/longitude and additional field settings only apply to CSV.
+    pub fn file_type(mut self, file_type: FileType) -> Self {
+        self.params.file_type = file_type;
+        self
+    }
+
+    /// The source item's `type` (e.g. `ItemData::type`). `send` fails with
+    /// [`PublishTypeMismatch`] before publishing if it does not match the
+    /// `fileType`, instead of leaving ArcGIS to reject the job. Setting it
+    /// saves `send` from fetching the item to find out.
+    pub fn source_item_type(mut self, item_type: impl Into<String>) -> Self {
+        self.source_item_type = Some(item_type.into());
+        self
+    }
+
     pub fn latitude_field_name(mut self, name: impl Into<String>) -> Self {
         self.params.publish_parameters.latitude_field_name = Some(name.into());
         self
@@ -517,6 +852,41 @@ impl PublishItemQueryBuilder {
         self
     }
 
+    /// How the CSV is located (defaults to latitude/longitude coordinates).
+    /// Address and lookup CSVs are geocoded, which consumes credits; describe
+    /// their fields with `additional_fields` or
+    /// [`PublishItemQueryBuilder::analyzed`].
+    pub fn location_type(mut self, location_type: LocationType) -> Self {
+        self.location_type = Some(location_type);
+        self
+    }
+
+    /// Geocodes by address, e.g. `{Street}, {City}, {State} {Zip}`.
+    pub fn address_template(mut self, template: impl Into<String>) -> Self {
+        self.location_type = Some(LocationType::Address);
+        self.params.publish_parameters.address_template = Some(template.into());
+        self
+    }
+
+    /// Locates by a single coordinate field, e.g. an MGRS or USNG string.
+    pub fn coordinate_field(
+        mut self,
+        field_name: impl Into<String>,
+        field_type: CoordinateFieldType,
+    ) -> Self {
+        self.location_type = Some(LocationType::Coordinates);
+        self.params.publish_parameters.coordinate_field_name = Some(field_name.into());
+        self.params.publish_parameters.coordinate_field_type = Some(field_type);
+        self
+    }
+
+    /// Targets the user folder `folder_id` instead of the root folder,
+    /// i.e. `content/users/[userName]/[folderId]/publish`.
+    pub fn folder(mut self, folder_id: impl Into<String>) -> Self {
+        self.folder = Some(folder_id.into());
+        self
+    }
+
     pub fn token(mut self, token: impl Into<String>) -> Self {
         self.params.token = Some(token.into());
         self
@@ -527,45 +897,114 @@ impl PublishItemQueryBuilder {
         self
     }
 
-    pub fn build(mut self) -> PublishItemQuery {
-        let params = CsvPublishParameters {
-            r#type: PublishType::Csv,
-            name: self.params.publish_parameters.name,
-            location_type: LocationType::Coordinates,
+    /// Publish parameters for shapefile, GeoJSON, file geodatabase and
+    /// service definition items, used instead of the CSV settings. Set
+    /// the matching [`FileType`] with `file_type`.
+    pub fn file_publish_parameters(mut self, parameters: FilePublishParameters) -> Self {
+        self.params.publish_parameters.name = parameters.name.clone();
+        self.params.file_publish_parameters = Some(parameters);
+        self
+    }
 
-            latitude_field_name: self.params.publish_parameters.latitude_field_name.clone(),
-            longitude_field_name: self.params.publish_parameters.longitude_field_name.clone(),
-            coordinate_field_type: Some(CoordinateFieldType::LatitudeAndLongitude),
+    /// Publishes a CSV with the parameters `/content/features/analyze`
+    /// suggested, so the layer gets the field types detected in the file
+    /// instead of generated ones. The service `name` still comes from the
+    /// builder.
+    pub fn analyzed(mut self, analysis: &AnalyzeResponse) -> Self {
+        self.params.analyzed_parameters = Some(analysis.publish_parameters.clone());
+        self
+    }
 
-            // Required: layerInfo from /analyze
-            layer_info: serde_json::json!({
-                "fields": [
-                    { "name": self.params.publish_parameters.longitude_field_name, "type": "esriFieldTypeDouble" },
-                    { "name": self.params.publish_parameters.latitude_field_name,  "type": "esriFieldTypeDouble" }
-                ],
-                "geometryType": "esriGeometryPoint"
-            }),
+    /// Locates by a lookup against boundary layers, e.g. `"Country"` with
+    /// `{"Country": "Nation"}` mapping lookup fields to CSV fields.
+    pub fn lookup(
+        mut self,
+        lookup_type: impl Into<String>,
+        lookup_fields: HashMap<String, String>,
+    ) -> Self {
+        self.location_type = Some(LocationType::Lookup);
+        self.params.publish_parameters.lookup_type = Some(lookup_type.into());
+        self.params.publish_parameters.lookup_fields = Some(lookup_fields);
+        self
+    }
 
-            description: self.params.publish_parameters.description,
-            max_record_count: None,
-            source_sr: Some(SpatialReference {
-                wkid: 4326,
-                latest_wkid: None,
-            }),
+    /// Builds the query. Incomplete CSV parameters are reported by
+    /// [`PublishItemQuery::send`].
+    pub fn build(mut self) -> PublishItemQuery {
+        let mut params = std::mem::take(&mut self.params.publish_parameters);
+        params.r#type = PublishType::Csv;
+        params.location_type = self.location_type.unwrap_or(LocationType::Coordinates);
+        match params.location_type {
+            LocationType::Coordinates => {
+                params
+                    .coordinate_field_type
+                    .get_or_insert(CoordinateFieldType::LatitudeAndLongitude);
+            }
+            _ => params.coordinate_field_type = None,
+        }
+        if params.is_latitude_longitude() {
+            // The generated point layer template uses these field names.
+            params
+                .latitude_field_name
+                .get_or_insert_with(|| "Latitude".into());
+            params
+                .longitude_field_name
+                .get_or_insert_with(|| "Longitude".into());
+        }
+        if params.layer_info.is_null() {
+            params.layer_info = self.layer_info(&params);
+        }
+        params.source_sr.get_or_insert(SpatialReference {
+            wkid: 4326,
+            latest_wkid: None,
+        });
 
-            ..Default::default()
-        };
         self.params.publish_parameters = params;
 
+        let url = match &self.folder {
+            Some(folder) => format!("{}/{}/publish", self.url, urlencoding::encode(folder)),
+            None => format!("{}/publish", self.url),
+        };
         let url = if let Some(token) = &self.params.token {
-            format!("{}?token={}", self.url, token)
+            format!("{}?token={}", url, token)
         } else {
-            self.url
+            url
         };
 
         PublishItemQuery {
+            root: self.root,
             url,
             params: self.params,
+            source_item_type: self.source_item_type,
+        }
+    }
+
+    /// `layerInfo` for a CSV without analyzed parameters: the coordinate
+    /// fields, or the additional fields as strings for the other location
+    /// types.
+    fn layer_info(&self, params: &CsvPublishParameters) -> Value {
+        if params.is_latitude_longitude() {
+            return serde_json::json!({
+                "fields": [
+                    { "name": params.longitude_field_name, "type": "esriFieldTypeDouble" },
+                    { "name": params.latitude_field_name,  "type": "esriFieldTypeDouble" }
+                ],
+                "geometryType": "esriGeometryPoint"
+            });
         }
+        let fields: Vec<Value> = params
+            .coordinate_field_name
+            .iter()
+            .chain(&self.params.additional_fields)
+            .map(|name| {
+                serde_json::json!({
+                    "name": name,
+                    "type": "esriFieldTypeString",
+                    "alias": name,
+                    "length": 256
+                })
+            })
+            .collect();
+        serde_json::json!({ "fields": fields, "geometryType": "esriGeometryPoint" })
     }
 }
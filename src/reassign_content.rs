@@ -0,0 +1,130 @@
+use std::collections::HashMap;
+
+use serde::Deserialize;
+
+use crate::{
+    client::ArcGISClient,
+    parser::parse_response,
+    retry::SendWithRetry,
+    search::{SearchFilter, SearchQuery},
+};
+
+/// Reassigns all of a user's content to another user in a single
+/// `/community/users/[userName]/reassign` request, e.g. when offboarding.
+/// Requires administrator privileges.
+pub struct ReassignContentQuery {
+    root: String,
+    user_name: String,
+    url: String,
+    params: HashMap<String, String>,
+    token: Option<String>,
+}
+
+#[derive(Default)]
+pub struct ReassignContentQueryBuilder {
+    root: String,
+    user_name: String,
+    url: String,
+    target_user_name: String,
+    token: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ReassignContentResponse {
+    pub success: bool,
+    /// Items that moved to the target user.
+    #[serde(skip)]
+    pub reassigned: i64,
+    /// Items the user still owns afterwards.
+    #[serde(skip)]
+    pub failed: i64,
+}
+
+impl ReassignContentQuery {
+    pub fn builder(
+        root: impl Into<String>,
+        user_name: impl Into<String>,
+        target_user_name: impl Into<String>,
+    ) -> ReassignContentQueryBuilder {
+        ReassignContentQueryBuilder::new(root, user_name, target_user_name)
+    }
+
+    /// Reassigns the content, counting the user's items before and after
+    /// since the portal only reports overall success.
+    pub async fn send(
+        &self,
+        client: impl Into<ArcGISClient>,
+    ) -> anyhow::Result<ReassignContentResponse> {
+        let client = client.into();
+        let owned = self.owned_items(&client).await?;
+        let response = client
+            .post(&self.url)
+            .form(&self.params)
+            .send_retrying(&client)
+            .await?;
+        let mut body = parse_response::<ReassignContentResponse>(response).await?;
+        body.failed = self.owned_items(&client).await?;
+        body.reassigned = owned - body.failed;
+        Ok(body)
+    }
+
+    async fn owned_items(&self, client: &ArcGISClient) -> anyhow::Result<i64> {
+        let mut search = SearchQuery::builder(&self.root, "")
+            .filter(SearchFilter::new().owner_is(&self.user_name))
+            .num(1);
+        if let Some(token) = &self.token {
+            search = search.token(token);
+        }
+        Ok(search.build().send(client).await?.total)
+    }
+}
+
+impl ReassignContentQueryBuilder {
+    pub fn new(
+        root: impl Into<String>,
+        user_name: impl Into<String>,
+        target_user_name: impl Into<String>,
+    ) -> Self {
+        // https://[root]/community/users/[userName]/reassign
+
+        let root = root.into();
+        let user_name = user_name.into();
+        let url = format!(
+            "{}/community/users/{}/reassign",
+            root,
+            urlencoding::encode(&user_name)
+        );
+        Self {
+            root,
+            user_name,
+            url,
+            target_user_name: target_user_name.into(),
+            ..Default::default()
+        }
+    }
+
+    pub fn token(mut self, token: impl Into<String>) -> Self {
+        self.token = Some(token.into());
+        self
+    }
+
+    pub fn build(self) -> ReassignContentQuery {
+        let mut params = HashMap::new();
+        params.insert("targetUsername".into(), self.target_user_name);
+        params.insert("f".into(), "json".into());
+
+        let url = if let Some(token) = &self.token {
+            format!("{}?token={}", self.url, token)
+        } else {
+            self.url
+        };
+
+        ReassignContentQuery {
+            root: self.root,
+            user_name: self.user_name,
+            url,
+            params,
+            token: self.token,
+        }
+    }
+}
@@ -0,0 +1,48 @@
+/// Form fields masked by default when a request body is logged.
+pub const SENSITIVE_KEYS: &[&str] = &[
+    "password",
+    "servicePassword",
+    "token",
+    "client_secret",
+    "refresh_token",
+    "code",
+];
+
+const MASK: &str = "REDACTED";
+
+/// Masks the values of `keys` (compared case-insensitively) in a
+/// form-encoded body. A body that doesn't parse as a form is masked whole,
+/// since there's no telling what it contains.
+pub fn redact_form<S: AsRef<str>>(body: &str, keys: &[S]) -> String {
+    let Ok(pairs) = serde_urlencoded::from_str::<Vec<(String, String)>>(body) else {
+        return MASK.to_string();
+    };
+    let pairs: Vec<(String, String)> = pairs
+        .into_iter()
+        .map(|(key, value)| {
+            if keys.iter().any(|k| k.as_ref().eq_ignore_ascii_case(&key)) {
+                (key, MASK.to_string())
+            } else {
+                (key, value)
+            }
+        })
+        .collect();
+    serde_urlencoded::to_string(pairs).unwrap_or_else(|_| MASK.to_string())
+}
+
+/// [`redact_form`] for a params map that hasn't been encoded yet.
+pub fn redact_params<K, V, S>(params: &std::collections::HashMap<K, V>, keys: &[S]) -> String
+where
+    K: AsRef<str>,
+    V: AsRef<str>,
+    S: AsRef<str>,
+{
+    let pairs: Vec<(&str, &str)> = params
+        .iter()
+        .map(|(k, v)| (k.as_ref(), v.as_ref()))
+        .collect();
+    match serde_urlencoded::to_string(pairs) {
+        Ok(body) => redact_form(&body, keys),
+        Err(_) => MASK.to_string(),
+    }
+}
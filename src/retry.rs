@@ -0,0 +1,439 @@
+use std::time::Duration;
+
+use reqwest::{
+    header::{HeaderName, CONTENT_TYPE},
+    Client, Method, Request, RequestBuilder, Response, StatusCode,
+};
+use tracing::Instrument;
+
+use crate::{
+    client::ArcGISClient,
+    redact::{redact_form, SENSITIVE_KEYS},
+};
+
+/// Default header for [`RetryPolicy::with_correlation_id`].
+pub const CORRELATION_ID_HEADER: HeaderName = HeaderName::from_static("x-correlation-id");
+
+/// The longest `Retry-After` waited for by default.
+pub const DEFAULT_MAX_RETRY_AFTER: Duration = Duration::from_secs(60);
+
+/// Retries transient ArcGIS failures with exponential backoff, or after the
+/// delay a `429`/`503` response asks for in `Retry-After`.
+///
+/// Retrying is method-aware: a GET can always be repeated, but a POST that
+/// hit a 5xx or a dropped connection may already have been processed (an
+/// item added, edits applied). POSTs are therefore only retried on `429`,
+/// where the server rejected the request before doing any work, on connect
+/// errors, where the request never left the client, or when the caller marks
+/// the request idempotent (e.g. an add item with `itemIdToCreate`).
+///
+/// The crate's requests use the policy of the [`ArcGISClient`] they are
+/// sent with.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    max_retries: u32,
+    backoff: Duration,
+    max_retry_after: Duration,
+    jitter: bool,
+    correlation_header: Option<HeaderName>,
+    redacted_keys: Vec<String>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::new(3, Duration::from_millis(500))
+    }
+}
+
+impl RetryPolicy {
+    pub fn new(max_retries: u32, backoff: Duration) -> Self {
+        Self {
+            max_retries,
+            backoff,
+            max_retry_after: DEFAULT_MAX_RETRY_AFTER,
+            jitter: false,
+            correlation_header: None,
+            redacted_keys: SENSITIVE_KEYS.iter().map(|k| k.to_string()).collect(),
+        }
+    }
+
+    /// The longest `Retry-After` the policy waits for; a response asking for
+    /// a longer wait is returned as is instead of being retried.
+    pub fn with_max_retry_after(mut self, max_retry_after: Duration) -> Self {
+        self.max_retry_after = max_retry_after;
+        self
+    }
+
+    /// Full jitter: waits a random duration between zero and the
+    /// exponential backoff, so a fleet of throttled workers doesn't retry
+    /// in lockstep.
+    pub fn with_jitter(mut self, jitter: bool) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Tags each request with a fresh UUID in `header` (usually
+    /// [`CORRELATION_ID_HEADER`]), kept across its retries, recorded on the
+    /// request's tracing span and included in the error when the request
+    /// fails, so one operation can be followed across services' logs. Off by default, since strict portals may reject
+    /// unknown headers.
+    pub fn with_correlation_id(mut self, header: HeaderName) -> Self {
+        self.correlation_header = Some(header);
+        self
+    }
+
+    /// Form fields masked when request bodies are traced, replacing
+    /// [`SENSITIVE_KEYS`].
+    pub fn with_redacted_keys<S: Into<String>>(
+        mut self,
+        keys: impl IntoIterator<Item = S>,
+    ) -> Self {
+        self.redacted_keys = keys.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Never retries.
+    pub fn none() -> Self {
+        Self::new(0, Duration::ZERO)
+    }
+
+    /// Whether a response with `status` should be retried.
+    pub fn should_retry_status(
+        &self,
+        method: &Method,
+        idempotent: bool,
+        status: StatusCode,
+    ) -> bool {
+        match status {
+            StatusCode::TOO_MANY_REQUESTS => true,
+            StatusCode::INTERNAL_SERVER_ERROR
+            | StatusCode::BAD_GATEWAY
+            | StatusCode::SERVICE_UNAVAILABLE
+            | StatusCode::GATEWAY_TIMEOUT => Self::is_repeatable(method, idempotent),
+            _ => false,
+        }
+    }
+
+    /// Whether a request that failed with `error` should be retried.
+    pub fn should_retry_error(
+        &self,
+        method: &Method,
+        idempotent: bool,
+        error: &reqwest::Error,
+    ) -> bool {
+        match NetworkErrorKind::of(error) {
+            // The request never left the client.
+            Some(NetworkErrorKind::Dns | NetworkErrorKind::Connect) => true,
+            // A bad certificate won't fix itself.
+            Some(NetworkErrorKind::Tls) => false,
+            Some(NetworkErrorKind::Timeout | NetworkErrorKind::Request) => {
+                Self::is_repeatable(method, idempotent)
+            }
+            None => false,
+        }
+    }
+
+    fn is_repeatable(method: &Method, idempotent: bool) -> bool {
+        idempotent || matches!(*method, Method::GET | Method::HEAD)
+    }
+
+    /// Delay before retry number `attempt` (starting at 0).
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let backoff = self.backoff.saturating_mul(2u32.saturating_pow(attempt));
+        if self.jitter {
+            backoff.mul_f64(fastrand::f64())
+        } else {
+            backoff
+        }
+    }
+
+    /// Executes `request`, retrying according to the policy.
+    pub async fn execute(&self, client: &Client, request: Request) -> reqwest::Result<Response> {
+        self.execute_with(client, request, false).await.1
+    }
+
+    /// Executes `request`, treating it as safe to repeat regardless of method.
+    pub async fn execute_idempotent(
+        &self,
+        client: &Client,
+        request: Request,
+    ) -> reqwest::Result<Response> {
+        self.execute_with(client, request, true).await.1
+    }
+
+    /// [`RetryPolicy::execute`] for the crate's own requests: network
+    /// failures come back as [`NetworkError`], and the correlation id, if
+    /// any, is attached to the error or to the response for
+    /// [`crate::parser::parse_response`].
+    pub(crate) async fn send(
+        &self,
+        client: &Client,
+        request: Request,
+        idempotent: bool,
+    ) -> anyhow::Result<Response> {
+        let (correlation_id, result) = self.execute_with(client, request, idempotent).await;
+        match result {
+            Ok(mut response) => {
+                if let Some(id) = correlation_id {
+                    response.extensions_mut().insert(CorrelationId(id));
+                }
+                Ok(response)
+            }
+            Err(e) => match NetworkError::new(e) {
+                Ok(network) => Err(NetworkError {
+                    correlation_id,
+                    ..network
+                }
+                .into()),
+                Err(e) => match correlation_id {
+                    Some(id) => Err(anyhow::Error::from(e)
+                        .context(format!("Request with correlation id {} failed", id))),
+                    None => Err(e.into()),
+                },
+            },
+        }
+    }
+
+    async fn execute_with(
+        &self,
+        client: &Client,
+        mut request: Request,
+        idempotent: bool,
+    ) -> (Option<String>, reqwest::Result<Response>) {
+        let Some(header) = &self.correlation_header else {
+            return (None, self.execute_loop(client, request, idempotent).await);
+        };
+
+        let correlation_id = uuid::Uuid::new_v4().to_string();
+        if let Ok(value) = correlation_id.parse() {
+            request.headers_mut().insert(header.clone(), value);
+        }
+        let span = tracing::info_span!("arcgis_request", correlation_id = %correlation_id);
+        let result = self
+            .execute_loop(client, request, idempotent)
+            .instrument(span)
+            .await;
+        (Some(correlation_id), result)
+    }
+
+    async fn execute_loop(
+        &self,
+        client: &Client,
+        request: Request,
+        idempotent: bool,
+    ) -> reqwest::Result<Response> {
+        let method = request.method().clone();
+        let mut attempt = 0;
+
+        if tracing::enabled!(tracing::Level::TRACE) {
+            self.trace_request(&request);
+        }
+
+        loop {
+            // Streaming bodies can't be cloned, so those get a single attempt.
+            let retry = if attempt < self.max_retries {
+                request.try_clone()
+            } else {
+                None
+            };
+            let Some(next) = retry else {
+                return client.execute(request).await;
+            };
+
+            let mut delay = self.backoff(attempt);
+            match client.execute(next).await {
+                Ok(response)
+                    if !self.should_retry_status(&method, idempotent, response.status()) =>
+                {
+                    return Ok(response);
+                }
+                Ok(response) => {
+                    match retry_after(&response) {
+                        Some(retry_after) if retry_after > self.max_retry_after => {
+                            tracing::warn!(
+                                status = %response.status(),
+                                retry_after_seconds = retry_after.as_secs(),
+                                "Not retrying ArcGIS request; Retry-After is too long"
+                            );
+                            return Ok(response);
+                        }
+                        Some(retry_after) => delay = retry_after,
+                        None => {}
+                    }
+                    tracing::warn!(status = %response.status(), attempt, "Retrying ArcGIS request");
+                }
+                Err(e) if !self.should_retry_error(&method, idempotent, &e) => return Err(e),
+                Err(e) => {
+                    tracing::warn!(error = %e, attempt, "Retrying ArcGIS request");
+                }
+            }
+
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Traces the request with its query string and form body redacted.
+    /// Other bodies (JSON, multipart) are only traced by size.
+    fn trace_request(&self, request: &Request) {
+        let mut url = request.url().clone();
+        if let Some(query) = url.query() {
+            let query = redact_form(query, &self.redacted_keys);
+            url.set_query(Some(&query));
+        }
+        let is_form = request
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|v| v.starts_with("application/x-www-form-urlencoded"));
+        let body = match request.body().and_then(|b| b.as_bytes()) {
+            Some(bytes) if is_form => {
+                redact_form(&String::from_utf8_lossy(bytes), &self.redacted_keys)
+            }
+            Some(bytes) => format!("<{} bytes>", bytes.len()),
+            None => "<stream>".to_string(),
+        };
+        tracing::trace!(method = %request.method(), %url, %body, "Sending ArcGIS request");
+    }
+}
+
+/// Sends crate requests with the policy of the client they were built from.
+pub(crate) trait SendWithRetry {
+    /// Sends a request that is only repeated when its method (or a `429`)
+    /// allows it.
+    async fn send_retrying(self, client: &ArcGISClient) -> anyhow::Result<Response>;
+}
+
+impl SendWithRetry for RequestBuilder {
+    async fn send_retrying(self, client: &ArcGISClient) -> anyhow::Result<Response> {
+        client.send(self, false).await
+    }
+}
+
+/// The correlation id a response was requested with, stored in its
+/// extensions.
+#[derive(Debug, Clone)]
+pub(crate) struct CorrelationId(pub String);
+
+/// What kind of network failure a [`reqwest::Error`] was, for deciding
+/// whether it's worth retrying.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NetworkErrorKind {
+    /// The host name didn't resolve.
+    Dns,
+    /// The connection was refused or dropped before the request was sent.
+    Connect,
+    /// The TLS handshake failed, e.g. an invalid or untrusted certificate.
+    Tls,
+    Timeout,
+    /// The request failed while it was being sent or answered.
+    Request,
+}
+
+impl NetworkErrorKind {
+    /// `None` for errors that aren't network failures, such as an error
+    /// status or a body that failed to decode.
+    pub fn of(error: &reqwest::Error) -> Option<Self> {
+        if error.is_timeout() {
+            Some(Self::Timeout)
+        } else if error.is_connect() {
+            Some(Self::of_connect(error))
+        } else if error.is_request() {
+            Some(Self::Request)
+        } else {
+            None
+        }
+    }
+
+    /// reqwest doesn't say why a connect failed, so look at the first
+    /// error in its source chain that does: a TLS error, or the I/O error
+    /// from the resolver or the socket.
+    fn of_connect(error: &reqwest::Error) -> Self {
+        let mut source = std::error::Error::source(error);
+        while let Some(e) = source {
+            if e.is::<native_tls::Error>() {
+                return Self::Tls;
+            }
+            if let Some(io) = e.downcast_ref::<std::io::Error>() {
+                return match io.kind() {
+                    // rustls reports handshake failures as invalid data.
+                    std::io::ErrorKind::InvalidData => Self::Tls,
+                    // Socket errors carry an OS error code; failed lookups
+                    // from the system resolver don't.
+                    _ if io.raw_os_error().is_some() => Self::Connect,
+                    _ => Self::Dns,
+                };
+            }
+            source = e.source();
+        }
+        Self::Connect
+    }
+}
+
+/// A network failure, keeping the underlying [`reqwest::Error`] as its
+/// source. Queries return it (inside their `anyhow::Error`) when a request
+/// fails after the retries of their client's [`RetryPolicy`]; use
+/// [`NetworkError::classify`] to tell failures apart.
+#[derive(Debug)]
+pub struct NetworkError {
+    pub kind: NetworkErrorKind,
+    /// Set when the policy sends [`RetryPolicy::with_correlation_id`].
+    pub correlation_id: Option<String>,
+    source: reqwest::Error,
+}
+
+impl NetworkError {
+    pub fn new(error: reqwest::Error) -> Result<Self, reqwest::Error> {
+        match NetworkErrorKind::of(&error) {
+            Some(kind) => Ok(Self {
+                kind,
+                correlation_id: None,
+                source: error,
+            }),
+            None => Err(error),
+        }
+    }
+
+    /// The kind of network failure behind `error`, if it was one.
+    pub fn classify(error: &anyhow::Error) -> Option<NetworkErrorKind> {
+        if let Some(network) = error.downcast_ref::<NetworkError>() {
+            return Some(network.kind);
+        }
+        error
+            .chain()
+            .find_map(|e| e.downcast_ref::<reqwest::Error>())
+            .and_then(NetworkErrorKind::of)
+    }
+
+    pub fn into_inner(self) -> reqwest::Error {
+        self.source
+    }
+}
+
+impl std::fmt::Display for NetworkError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?} error: {}", self.kind, self.source)?;
+        if let Some(id) = &self.correlation_id {
+            write!(f, " (correlation id {})", id)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for NetworkError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+/// The messages of `error`'s sources, skipping `error` itself, whose
+/// message includes the url.
+/// A `Retry-After` given in seconds, which takes precedence over the backoff
+/// up to the policy's `max_retry_after`. The HTTP-date form is ignored.
+fn retry_after(response: &Response) -> Option<Duration> {
+    let value = response.headers().get(reqwest::header::RETRY_AFTER)?;
+    let seconds = value.to_str().ok()?.trim().parse().ok()?;
+    Some(Duration::from_secs(seconds))
+}
@@ -0,0 +1,257 @@
+use std::collections::HashMap;
+
+use serde::Deserialize;
+
+use crate::{
+    client::ArcGISClient,
+    group::create::{SortField, SortOrder},
+    item::{ItemData, ItemType},
+    parser::parse_response,
+    retry::SendWithRetry,
+};
+
+/// Searches the portal's items with `/search`.
+pub struct SearchQuery {
+    url: String,
+    params: HashMap<String, String>,
+}
+
+#[derive(Default)]
+pub struct SearchQueryBuilder {
+    url: String,
+    q: String,
+    filter: Option<SearchFilter>,
+    sort: Option<SearchSort>,
+    num: Option<u32>,
+    start: Option<u32>,
+    token: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchResponse {
+    pub total: i64,
+    pub start: i64,
+    pub num: i64,
+    /// `-1` on the last page.
+    pub next_start: i64,
+    pub results: Vec<ItemData>,
+}
+
+impl SearchQuery {
+    pub fn builder(root: impl Into<String>, q: impl Into<String>) -> SearchQueryBuilder {
+        SearchQueryBuilder::new(root, q)
+    }
+
+    pub async fn send(&self, client: impl Into<ArcGISClient>) -> anyhow::Result<SearchResponse> {
+        let client = client.into();
+        let response = client
+            .get(&self.url)
+            .query(&self.params)
+            .send_retrying(&client)
+            .await?;
+        let body = parse_response::<SearchResponse>(response).await?;
+        Ok(body)
+    }
+}
+
+impl SearchQueryBuilder {
+    pub fn new(root: impl Into<String>, q: impl Into<String>) -> Self {
+        // https://[root]/search
+
+        let url = format!("{}/search", root.into());
+        Self {
+            url,
+            q: q.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Exact-match clauses applied on top of `q`.
+    pub fn filter(mut self, filter: SearchFilter) -> Self {
+        self.filter = Some(filter);
+        self
+    }
+
+    /// Orders results by one or more fields, each with its own direction.
+    pub fn sort(mut self, sort: SearchSort) -> Self {
+        self.sort = Some(sort);
+        self
+    }
+
+    /// Results per page (the portal defaults to 10, at most 100).
+    pub fn num(mut self, num: u32) -> Self {
+        self.num = Some(num);
+        self
+    }
+
+    /// 1-based index of the first result, e.g. a previous `next_start`.
+    pub fn start(mut self, start: u32) -> Self {
+        self.start = Some(start);
+        self
+    }
+
+    pub fn token(mut self, token: impl Into<String>) -> Self {
+        self.token = Some(token.into());
+        self
+    }
+
+    pub fn build(self) -> SearchQuery {
+        let mut params = HashMap::new();
+        params.insert("q".into(), self.q);
+
+        if let Some(filter) = self.filter {
+            params.insert("filter".into(), filter.build());
+        }
+        if let Some(sort) = self.sort {
+            params.extend(sort.build().into_iter().map(|(k, v)| (k.to_string(), v)));
+        }
+        if let Some(num) = self.num {
+            params.insert("num".into(), num.to_string());
+        }
+        if let Some(start) = self.start {
+            params.insert("start".into(), start.to_string());
+        }
+
+        params.insert("f".into(), "json".into());
+
+        let url = if let Some(token) = &self.token {
+            format!("{}?token={}", self.url, token)
+        } else {
+            self.url
+        };
+
+        SearchQuery { url, params }
+    }
+}
+
+/// Builds the `filter` parameter of a portal search, for
+/// [`SearchQueryBuilder::filter`].
+///
+/// Filter clauses are exact matches on a field (`type:"Web Map"`), with the
+/// value quoted and operators in capitals, since the search service treats
+/// lowercase `and`/`or` as search terms. Clauses without an explicit
+/// operator between them are joined with `AND`, so
+/// `.type_is(ItemType::WebMap).owner_is("esri")` gives
+/// `type:"Web Map" AND owner:"esri"`.
+#[derive(Debug, Clone, Default)]
+pub struct SearchFilter {
+    tokens: Vec<Token>,
+}
+
+#[derive(Debug, Clone)]
+enum Token {
+    Clause(String),
+    And,
+    Or,
+}
+
+impl SearchFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// `type:"<item_type>"`, e.g. `type:"Feature Service"`.
+    pub fn type_is(self, item_type: ItemType) -> Self {
+        self.clause("type", item_type.as_str().to_string())
+    }
+
+    pub fn owner_is(self, owner: impl Into<String>) -> Self {
+        self.clause("owner", owner.into())
+    }
+
+    pub fn tag_is(self, tag: impl Into<String>) -> Self {
+        self.clause("tags", tag.into())
+    }
+
+    pub fn title_is(self, title: impl Into<String>) -> Self {
+        self.clause("title", title.into())
+    }
+
+    pub fn and(self) -> Self {
+        self.operator(Token::And)
+    }
+
+    pub fn or(self) -> Self {
+        self.operator(Token::Or)
+    }
+
+    pub fn build(self) -> String {
+        let mut tokens = self.tokens;
+        if matches!(tokens.last(), Some(Token::And | Token::Or)) {
+            tokens.pop();
+        }
+
+        tokens
+            .into_iter()
+            .map(|token| match token {
+                Token::Clause(clause) => clause,
+                Token::And => "AND".into(),
+                Token::Or => "OR".into(),
+            })
+            .collect::<Vec<String>>()
+            .join(" ")
+    }
+
+    fn clause(mut self, field: &str, value: String) -> Self {
+        if matches!(self.tokens.last(), Some(Token::Clause(_))) {
+            self.tokens.push(Token::And);
+        }
+        let value = value.replace('\\', "\\\\").replace('"', "\\\"");
+        self.tokens
+            .push(Token::Clause(format!("{}:\"{}\"", field, value)));
+        self
+    }
+
+    fn operator(mut self, operator: Token) -> Self {
+        match self.tokens.last_mut() {
+            Some(Token::Clause(_)) => self.tokens.push(operator),
+            Some(last) => *last = operator,
+            None => {}
+        }
+        self
+    }
+}
+
+/// Builds the `sortField` and `sortOrder` parameters of a portal search, for
+/// [`SearchQueryBuilder::sort`].
+///
+/// Each field gets its own direction; both are sent as comma-separated
+/// lists in the same order, so `.by(SortField::Title, SortOrder::Asc)
+/// .by(SortField::Created, SortOrder::Desc)` gives `sortField=title,created`
+/// and `sortOrder=asc,desc`.
+#[derive(Debug, Clone, Default)]
+pub struct SearchSort {
+    fields: Vec<(SortField, SortOrder)>,
+}
+
+impl SearchSort {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sorts by `field` after the fields already added.
+    pub fn by(mut self, field: SortField, order: SortOrder) -> Self {
+        self.fields.push((field, order));
+        self
+    }
+
+    pub fn sort_by_many(mut self, fields: Vec<(SortField, SortOrder)>) -> Self {
+        self.fields.extend(fields);
+        self
+    }
+
+    /// The query parameters, or none when no field was added.
+    pub fn build(self) -> Vec<(&'static str, String)> {
+        if self.fields.is_empty() {
+            return vec![];
+        }
+        let join = |f: fn(&(SortField, SortOrder)) -> &'static str| {
+            self.fields.iter().map(f).collect::<Vec<_>>().join(",")
+        };
+        vec![
+            ("sortField", join(|(field, _)| field.as_str())),
+            ("sortOrder", join(|(_, order)| order.as_str())),
+        ]
+    }
+}
@@ -0,0 +1,147 @@
+use std::collections::HashMap;
+
+use serde::Deserialize;
+
+use crate::{client::ArcGISClient, parser::parse_response, retry::SendWithRetry};
+
+/// Shares several items with groups, the organization, or everyone in a
+/// single `/content/users/[userName]/shareItems` request.
+pub struct ShareItemsQuery {
+    url: String,
+    groups: Vec<String>,
+    params: HashMap<String, String>,
+}
+
+#[derive(Default)]
+pub struct ShareItemsQueryBuilder {
+    url: String,
+    item_ids: Vec<String>,
+    groups: Vec<String>,
+    everyone: bool,
+    org: bool,
+    confirm_item_control: bool,
+    token: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ShareItemsResponse {
+    pub results: Vec<ShareItemResult>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ShareItemResult {
+    pub item_id: String,
+    pub success: bool,
+    /// Requested group ids the item was shared with.
+    #[serde(skip)]
+    pub shared_with: Vec<String>,
+    /// Group ids the item could not be shared with.
+    #[serde(default)]
+    pub not_shared_with: Vec<String>,
+}
+
+impl ShareItemsQuery {
+    pub fn builder(
+        root: impl Into<String>,
+        user_name: impl Into<String>,
+        item_ids: Vec<String>,
+    ) -> ShareItemsQueryBuilder {
+        ShareItemsQueryBuilder::new(root, user_name, item_ids)
+    }
+
+    pub async fn send(
+        &self,
+        client: impl Into<ArcGISClient>,
+    ) -> anyhow::Result<ShareItemsResponse> {
+        let client = client.into();
+        let response = client
+            .post(&self.url)
+            .form(&self.params)
+            .send_retrying(&client)
+            .await?;
+        let mut body = parse_response::<ShareItemsResponse>(response).await?;
+        for result in body.results.iter_mut().filter(|r| r.success) {
+            result.shared_with = self
+                .groups
+                .iter()
+                .filter(|g| !result.not_shared_with.contains(g))
+                .cloned()
+                .collect();
+        }
+        Ok(body)
+    }
+}
+
+impl ShareItemsQueryBuilder {
+    pub fn new(
+        root: impl Into<String>,
+        user_name: impl Into<String>,
+        item_ids: Vec<String>,
+    ) -> Self {
+        // https://[root]/content/users/[userName]/shareItems
+
+        let url = format!(
+            "{}/content/users/{}/shareItems",
+            root.into(),
+            urlencoding::encode(&user_name.into())
+        );
+        Self {
+            url,
+            item_ids,
+            ..Default::default()
+        }
+    }
+
+    pub fn groups(mut self, groups: Vec<String>) -> Self {
+        self.groups = groups;
+        self
+    }
+
+    pub fn everyone(mut self, everyone: bool) -> Self {
+        self.everyone = everyone;
+        self
+    }
+
+    pub fn org(mut self, org: bool) -> Self {
+        self.org = org;
+        self
+    }
+
+    /// Required when sharing to groups with update capabilities.
+    pub fn confirm_item_control(mut self, confirm_item_control: bool) -> Self {
+        self.confirm_item_control = confirm_item_control;
+        self
+    }
+
+    pub fn token(mut self, token: impl Into<String>) -> Self {
+        self.token = Some(token.into());
+        self
+    }
+
+    pub fn build(self) -> ShareItemsQuery {
+        let mut params = HashMap::new();
+        params.insert("items".into(), self.item_ids.join(","));
+        params.insert("groups".into(), self.groups.join(","));
+        params.insert("everyone".into(), self.everyone.to_string());
+        params.insert("org".into(), self.org.to_string());
+
+        if self.confirm_item_control {
+            params.insert("confirmItemControl".into(), "true".into());
+        }
+
+        params.insert("f".into(), "json".into());
+
+        let url = if let Some(token) = &self.token {
+            format!("{}?token={}", self.url, token)
+        } else {
+            self.url
+        };
+
+        ShareItemsQuery {
+            url,
+            groups: self.groups,
+            params,
+        }
+    }
+}
@@ -1,22 +1,32 @@
-use reqwest::Client;
+use reqwest::multipart::{Form, Part};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::path::PathBuf;
 
 use crate::parser::parse_response;
+use crate::{client::ArcGISClient, item::Item, retry::SendWithRetry};
 
 pub struct UpdateItemQuery {
     url: String,
+    root: String,
+    id: String,
     params: HashMap<String, String>,
+    metadata_file: Option<PathBuf>,
+    thumbnail_file: Option<(Vec<u8>, String)>,
 }
 
 #[derive(Default)]
 pub struct UpdateItemQueryBuilder {
     url: String,
+    root: String,
+    id: String,
 
     title: Option<String>,
     thumbnail: Option<String>,
     thumbnail_url: Option<String>,
+    thumbnail_file: Option<(Vec<u8>, String)>,
     metadata: Option<String>,
+    metadata_file: Option<PathBuf>,
     type_keywords: Option<String>,
     description: Option<String>,
     tags: Option<Vec<String>>,
@@ -61,11 +71,86 @@ impl UpdateItemQuery {
         UpdateItemQueryBuilder::new(root, user_name, id)
     }
 
-    pub async fn send(&self, client: &Client) -> anyhow::Result<UpdateItemResponse> {
-        let response = client.post(&self.url).form(&self.params).send().await?;
+    pub async fn send(
+        &self,
+        client: impl Into<ArcGISClient>,
+    ) -> anyhow::Result<UpdateItemResponse> {
+        let client = client.into();
+        #[cfg(feature = "xml-validation")]
+        self.validate_metadata()?;
+
+        let response = if self.metadata_file.is_none() && self.thumbnail_file.is_none() {
+            client
+                .post(&self.url)
+                .form(&self.params)
+                .send_retrying(&client)
+                .await?
+        } else {
+            let mut form = Form::new();
+            for (key, value) in &self.params {
+                form = form.text(key.clone(), value.clone());
+            }
+            if let Some(path) = &self.metadata_file {
+                // Streamed from disk, keeping the file's own name.
+                let part = Part::file(path).await?.mime_str("application/xml")?;
+                form = form.part("metadata", part);
+            }
+            if let Some((bytes, file_name)) = &self.thumbnail_file {
+                form = form.part(
+                    "thumbnail",
+                    Part::bytes(bytes.clone()).file_name(file_name.clone()),
+                );
+            }
+            client
+                .post(&self.url)
+                .multipart(form)
+                .send_retrying(&client)
+                .await?
+        };
         let body = parse_response::<UpdateItemResponse>(response).await?;
         Ok(body)
     }
+
+    /// Sends the update, then fetches the item so the caller sees its new
+    /// state.
+    pub async fn send_and_fetch(&self, client: impl Into<ArcGISClient>) -> anyhow::Result<Item> {
+        let client = client.into();
+        let response = self.send(&client).await?;
+        if !response.success {
+            anyhow::bail!("Failed to update item {}", self.id);
+        }
+        Item::new(&self.root, client, &self.id).await
+    }
+
+    #[cfg(feature = "xml-validation")]
+    fn validate_metadata(&self) -> anyhow::Result<()> {
+        use quick_xml::{events::Event, Reader};
+
+        fn check<R: std::io::BufRead>(mut reader: Reader<R>) -> anyhow::Result<()> {
+            let mut buf = Vec::new();
+            loop {
+                match reader.read_event_into(&mut buf) {
+                    Ok(Event::Eof) => return Ok(()),
+                    Ok(_) => buf.clear(),
+                    Err(e) => {
+                        return Err(anyhow::anyhow!(
+                            "Invalid metadata XML at byte {}: {}",
+                            reader.error_position(),
+                            e
+                        ))
+                    }
+                }
+            }
+        }
+
+        if let Some(xml) = self.params.get("metadata") {
+            check(Reader::from_str(xml))?;
+        }
+        if let Some(path) = &self.metadata_file {
+            check(Reader::from_file(path)?)?;
+        }
+        Ok(())
+    }
 }
 
 impl UpdateItemQueryBuilder {
@@ -76,15 +161,19 @@ impl UpdateItemQueryBuilder {
     ) -> Self {
         // https://[root]/content/users/[userName]/items/[itemID]/update
 
+        let root = root.into();
+        let id = id.into();
         let url = format!(
             "{}/content/users/{}/items/{}/update",
-            root.into(),
-            user_name.into(),
-            id.into()
+            root,
+            urlencoding::encode(&user_name.into()),
+            id
         );
         // TODO: validtate url
         Self {
             url,
+            root,
+            id,
             ..Default::default()
         }
     }
@@ -104,8 +193,26 @@ impl UpdateItemQueryBuilder {
         self
     }
 
+    /// Uploads `bytes` as the thumbnail; `file_name` (e.g. `preview.png`)
+    /// tells the portal the image type.
+    pub fn thumbnail_file(mut self, bytes: Vec<u8>, file_name: impl Into<String>) -> Self {
+        self.thumbnail_file = Some((bytes, file_name.into()));
+        self
+    }
+
+    /// Inline metadata XML, sent as the `metadata` form field.
     pub fn metadata(mut self, metadata: impl Into<String>) -> Self {
         self.metadata = Some(metadata.into());
+        self.metadata_file = None;
+        self
+    }
+
+    /// Metadata XML read from `path` when the query is sent and uploaded as
+    /// a file part, for documents too large to keep in memory (e.g. ISO
+    /// 19139).
+    pub fn metadata_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.metadata_file = Some(path.into());
+        self.metadata = None;
         self
     }
 
@@ -247,6 +354,14 @@ impl UpdateItemQueryBuilder {
     pub fn build(self) -> UpdateItemQuery {
         let mut params = HashMap::new();
 
+        if let Some(metadata) = self.metadata {
+            params.insert("metadata".into(), metadata);
+        }
+
+        if let Some(thumbnail_url) = self.thumbnail_url {
+            params.insert("thumbnailUrl".into(), thumbnail_url);
+        }
+
         if let Some(desc) = self.description {
             params.insert("description".into(), desc);
         }
@@ -354,7 +469,11 @@ impl UpdateItemQueryBuilder {
         params.insert("f".into(), "json".into());
         UpdateItemQuery {
             url: self.url,
+            root: self.root,
+            id: self.id,
             params,
+            metadata_file: self.metadata_file,
+            thumbnail_file: self.thumbnail_file,
         }
     }
 }
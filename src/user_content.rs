@@ -0,0 +1,205 @@
+use serde::Deserialize;
+
+use crate::{
+    client::ArcGISClient,
+    delete_items::{DeleteItemResult, DeleteItemsQuery},
+    parser::parse_response,
+    retry::SendWithRetry,
+};
+
+/// `deleteItems` batch size when emptying a folder.
+const DELETE_BATCH_SIZE: usize = 100;
+
+/// A user's content and folders under `/content/users/[userName]`.
+pub struct UserContent {
+    root: String,
+    user_name: String,
+    url: String,
+    client: ArcGISClient,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct FolderInfo {
+    pub id: String,
+    pub title: String,
+    pub username: Option<String>,
+    /// Milliseconds since the epoch.
+    pub created: Option<i64>,
+}
+
+#[derive(Deserialize)]
+struct CreateFolderResponse {
+    success: bool,
+    folder: Option<FolderInfo>,
+}
+
+#[derive(Deserialize)]
+struct UserContentResponse {
+    #[serde(default)]
+    folders: Vec<FolderInfo>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct FolderContentResponse {
+    #[serde(default)]
+    items: Vec<FolderItem>,
+    next_start: i64,
+}
+
+#[derive(Deserialize)]
+struct FolderItem {
+    id: String,
+}
+
+#[derive(Deserialize)]
+struct DeleteFolderResponse {
+    success: bool,
+}
+
+/// What [`UserContent::delete_folder_recursive`] removed.
+#[derive(Debug, Default)]
+pub struct FolderCleanup {
+    pub deleted_items: Vec<String>,
+    /// Items that could not be deleted, e.g. delete-protected ones.
+    pub failed_items: Vec<DeleteItemResult>,
+    /// False when items were left behind, since ArcGIS refuses to delete a
+    /// folder that isn't empty.
+    pub folder_deleted: bool,
+}
+
+impl UserContent {
+    pub fn new(
+        root: impl Into<String>,
+        client: impl Into<ArcGISClient>,
+        user_name: impl Into<String>,
+    ) -> Self {
+        let client = client.into();
+        // https://[root]/content/users/[userName]
+        let root = root.into();
+        let user_name = user_name.into();
+        let url = format!("{}/content/users/{}", root, urlencoding::encode(&user_name));
+        Self {
+            root,
+            user_name,
+            url,
+            client: client.clone(),
+        }
+    }
+
+    pub fn root(&self) -> &str {
+        &self.root
+    }
+
+    /// The owner, for the `user_name` of add and publish queries.
+    pub fn user_name(&self) -> &str {
+        &self.user_name
+    }
+
+    /// Creates a folder; titles must be unique within the user's content.
+    pub async fn create_folder(&self, title: impl Into<String>) -> anyhow::Result<FolderInfo> {
+        let title = title.into();
+        let response = self
+            .client
+            .post(format!("{}/createFolder", self.url))
+            .form(&[("title", title.as_str()), ("f", "json")])
+            .send_retrying(&self.client)
+            .await?;
+        let response = parse_response::<CreateFolderResponse>(response).await?;
+
+        match response.folder {
+            Some(folder) if response.success => Ok(folder),
+            _ => Err(anyhow::anyhow!("Failed to create folder {}", title)),
+        }
+    }
+
+    /// The user's folders, not including the root folder.
+    pub async fn list_folders(&self) -> anyhow::Result<Vec<FolderInfo>> {
+        // Only the folders are wanted, so skip the root items.
+        let response = self
+            .client
+            .get(&self.url)
+            .query(&[("num", "0"), ("f", "json")])
+            .send_retrying(&self.client)
+            .await?;
+        Ok(parse_response::<UserContentResponse>(response)
+            .await?
+            .folders)
+    }
+
+    /// Deletes the empty folder `folder_id`.
+    pub async fn delete_folder(&self, folder_id: &str) -> anyhow::Result<()> {
+        // https://[root]/content/users/[userName]/[folderId]/delete
+        let response = self
+            .client
+            .post(format!(
+                "{}/{}/delete",
+                self.url,
+                urlencoding::encode(folder_id)
+            ))
+            .form(&[("f", "json")])
+            .send_retrying(&self.client)
+            .await?;
+        if !parse_response::<DeleteFolderResponse>(response)
+            .await?
+            .success
+        {
+            anyhow::bail!("Failed to delete folder {}", folder_id);
+        }
+        Ok(())
+    }
+
+    /// Deletes every item in `folder_id`, then the folder itself, e.g. to
+    /// tear down a temporary workspace. If any item survives, the folder
+    /// is kept and the report lists the failures.
+    pub async fn delete_folder_recursive(&self, folder_id: &str) -> anyhow::Result<FolderCleanup> {
+        let item_ids = self.folder_item_ids(folder_id).await?;
+
+        let mut cleanup = FolderCleanup::default();
+        for batch in item_ids.chunks(DELETE_BATCH_SIZE) {
+            let response = DeleteItemsQuery::builder(&self.root, &self.user_name, batch.to_vec())
+                .build()
+                .send(&self.client)
+                .await?;
+            for result in response.results {
+                if result.success {
+                    cleanup.deleted_items.push(result.item_id);
+                } else {
+                    cleanup.failed_items.push(result);
+                }
+            }
+        }
+
+        if cleanup.failed_items.is_empty() {
+            self.delete_folder(folder_id).await?;
+            cleanup.folder_deleted = true;
+        }
+        Ok(cleanup)
+    }
+
+    async fn folder_item_ids(&self, folder_id: &str) -> anyhow::Result<Vec<String>> {
+        // https://[root]/content/users/[userName]/[folderId]
+        let url = format!("{}/{}", self.url, urlencoding::encode(folder_id));
+        let mut ids = vec![];
+        let mut start = 1;
+        loop {
+            let response = self
+                .client
+                .get(&url)
+                .query(&[
+                    ("start", start.to_string()),
+                    ("num", "100".to_string()),
+                    ("f", "json".to_string()),
+                ])
+                .send_retrying(&self.client)
+                .await?;
+            let page = parse_response::<FolderContentResponse>(response).await?;
+            ids.extend(page.items.into_iter().map(|item| item.id));
+            if page.next_start <= 0 {
+                return Ok(ids);
+            }
+            start = page.next_start;
+        }
+    }
+}
@@ -0,0 +1,773 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::{
+    client::ArcGISClient,
+    feature_layer::FeatureLayer,
+    feature_layer_query::{EsriQueryResponse, Extent},
+    publish_item::SpatialReference,
+};
+
+/// `layerType` of an operational or basemap layer
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum LayerType {
+    #[serde(rename = "ArcGISFeatureLayer")]
+    ArcGISFeatureLayer,
+    #[serde(rename = "ArcGISMapServiceLayer")]
+    ArcGISMapServiceLayer,
+    #[serde(rename = "ArcGISTiledMapServiceLayer")]
+    ArcGISTiledMapServiceLayer,
+    #[serde(rename = "ArcGISImageServiceLayer")]
+    ArcGISImageServiceLayer,
+    #[serde(rename = "VectorTileLayer")]
+    VectorTileLayer,
+    #[serde(rename = "GroupLayer")]
+    GroupLayer,
+}
+
+/// Web map item data, serialized into the `text` of a `Web Map` item.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WebMapJson {
+    pub operational_layers: Vec<OperationalLayer>,
+    /// Standalone (non-spatial) tables, e.g. related records shown in popups.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tables: Vec<OperationalLayer>,
+    pub base_map: BaseMap,
+    pub authoring_app: String,
+    pub authoring_app_version: String,
+    pub initial_state: InitialState,
+    pub spatial_reference: SpatialReference,
+    pub time_zone: String,
+    pub version: String,
+}
+
+/// A layer drawn on top of the basemap.
+///
+/// Group layers carry their children in `layers`; every other layer type
+/// leaves it empty.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OperationalLayer {
+    pub id: String,
+    pub title: String,
+    pub layer_type: LayerType,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub item_id: Option<String>,
+    /// Style of a `VectorTileLayer`, which has no `url`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub style_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub opacity: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub visibility: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub popup_info: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub layer_definition: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub feature_collection: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub blend_mode: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub effect: Option<Vec<Effect>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub layers: Vec<OperationalLayer>,
+}
+
+impl OperationalLayer {
+    fn new(layer_type: LayerType, title: impl Into<String>) -> Self {
+        Self {
+            id: layer_id(),
+            title: title.into(),
+            layer_type,
+            url: None,
+            item_id: None,
+            style_url: None,
+            opacity: None,
+            visibility: None,
+            popup_info: None,
+            layer_definition: None,
+            feature_collection: None,
+            blend_mode: None,
+            effect: None,
+            layers: vec![],
+        }
+    }
+
+    /// An `ArcGISFeatureLayer` pointing at a feature layer url (`.../FeatureServer/0`).
+    pub fn feature_layer(url: impl Into<String>, title: impl Into<String>) -> Self {
+        Self {
+            url: Some(url.into()),
+            ..Self::new(LayerType::ArcGISFeatureLayer, title)
+        }
+    }
+
+    /// An `ArcGISMapServiceLayer`, a dynamic map service drawn by the server
+    /// (`.../MapServer`).
+    pub fn map_service(url: impl Into<String>, title: impl Into<String>) -> Self {
+        Self {
+            url: Some(url.into()),
+            visibility: Some(true),
+            ..Self::new(LayerType::ArcGISMapServiceLayer, title)
+        }
+    }
+
+    /// A `VectorTileLayer` drawn with the style at `style_url`
+    /// (`.../VectorTileServer/resources/styles/root.json`).
+    pub fn vector_tile(style_url: impl Into<String>, title: impl Into<String>) -> Self {
+        Self {
+            style_url: Some(style_url.into()),
+            visibility: Some(true),
+            ..Self::new(LayerType::VectorTileLayer, title)
+        }
+    }
+
+    /// An `ArcGISImageServiceLayer` pointing at an image service url
+    /// (`.../ImageServer`).
+    pub fn image_service(url: impl Into<String>, title: impl Into<String>) -> Self {
+        Self {
+            url: Some(url.into()),
+            visibility: Some(true),
+            ..Self::new(LayerType::ArcGISImageServiceLayer, title)
+        }
+    }
+
+    /// A `GroupLayer` nesting `layers` under a single entry in the layer list.
+    pub fn group(title: impl Into<String>, layers: Vec<OperationalLayer>) -> Self {
+        Self {
+            visibility: Some(true),
+            layers,
+            ..Self::new(LayerType::GroupLayer, title)
+        }
+    }
+
+    /// A layer whose features are stored in the map itself
+    /// (`featureCollection`) instead of a feature service, drawn with a
+    /// simple renderer.
+    ///
+    /// Fields are taken from the first feature's attributes, and an
+    /// `OBJECTID` is added when the features don't have one. Geometry is
+    /// assumed to be WGS84 unless `features` carries a spatial reference.
+    pub fn feature_collection(title: impl Into<String>, features: EsriQueryResponse) -> Self {
+        let title = title.into();
+        Self {
+            feature_collection: Some(feature_collection(&title, features)),
+            visibility: Some(true),
+            ..Self::new(LayerType::ArcGISFeatureLayer, title)
+        }
+    }
+
+    pub fn with_item_id(mut self, item_id: impl Into<String>) -> Self {
+        self.item_id = Some(item_id.into());
+        self
+    }
+
+    /// Draws the layer with `renderer` (e.g. a `uniqueValue` or
+    /// `classBreaks` renderer) instead of the service's or the default
+    /// simple renderer. For feature collections the renderer is set on the
+    /// embedded layer definitions.
+    pub fn with_renderer(mut self, renderer: Value) -> Self {
+        self.set_renderer(renderer);
+        self
+    }
+
+    fn set_renderer(&mut self, renderer: Value) {
+        let definitions: Vec<&mut Value> = match &mut self.feature_collection {
+            Some(collection) => collection["layers"]
+                .as_array_mut()
+                .into_iter()
+                .flatten()
+                .map(|layer| &mut layer["layerDefinition"])
+                .collect(),
+            None => vec![self.layer_definition.get_or_insert_with(|| json!({}))],
+        };
+        for definition in definitions {
+            definition["drawingInfo"]["renderer"] = renderer.clone();
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BaseMap {
+    pub base_map_layers: Vec<BaseMapLayer>,
+    pub title: String,
+}
+
+impl BaseMap {
+    /// The Esri "Topographic" basemap (hillshade + vector tile topo).
+    pub fn topographic() -> Self {
+        Self {
+            base_map_layers: vec![
+                BaseMapLayer {
+                    id: "World_Hillshade_3689".into(),
+                    title: "World Hillshade".into(),
+                    layer_type: LayerType::ArcGISTiledMapServiceLayer,
+                    url: Some("https://services.arcgisonline.com/arcgis/rest/services/Elevation/World_Hillshade/MapServer".into()),
+                    item_id: None,
+                    style_url: None,
+                    opacity: 1.0,
+                    visibility: true,
+                    blend_mode: None,
+                    effect: None,
+                },
+                BaseMapLayer {
+                    id: "VectorTile_6451".into(),
+                    title: "World Topographic Map".into(),
+                    layer_type: LayerType::VectorTileLayer,
+                    url: None,
+                    item_id: Some("7dc6cea0b1764a1f9af2e679f642f0f5".into()),
+                    style_url: Some("https://cdn.arcgis.com/sharing/rest/content/items/7dc6cea0b1764a1f9af2e679f642f0f5/resources/styles/root.json".into()),
+                    opacity: 1.0,
+                    visibility: true,
+                    blend_mode: None,
+                    effect: None,
+                },
+            ],
+            title: "Topographic".into(),
+        }
+    }
+
+    /// A basemap from one of the organization's own items, e.g. a branded
+    /// vector tile style (`VectorTileLayer`) made with the Vector Tile Style
+    /// Editor or a tile layer item (`ArcGISTiledMapServiceLayer`).
+    pub fn from_item(
+        item_id: impl Into<String>,
+        layer_type: LayerType,
+        title: impl Into<String>,
+    ) -> Self {
+        let title = title.into();
+        Self {
+            base_map_layers: vec![BaseMapLayer {
+                item_id: Some(item_id.into()),
+                ..BaseMapLayer::new(layer_type, title.clone())
+            }],
+            title,
+        }
+    }
+
+    /// A basemap from a single tiled layer: a map service url for
+    /// `ArcGISTiledMapServiceLayer`, or a style url
+    /// (`.../resources/styles/root.json`) for `VectorTileLayer`.
+    pub fn from_url(
+        url: impl Into<String>,
+        layer_type: LayerType,
+        title: impl Into<String>,
+    ) -> Self {
+        let title = title.into();
+        let mut layer = BaseMapLayer::new(layer_type, title.clone());
+        match layer_type {
+            LayerType::VectorTileLayer => layer.style_url = Some(url.into()),
+            _ => layer.url = Some(url.into()),
+        }
+        Self {
+            base_map_layers: vec![layer],
+            title,
+        }
+    }
+}
+
+impl Default for BaseMap {
+    fn default() -> Self {
+        Self::topographic()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BaseMapLayer {
+    pub id: String,
+    pub title: String,
+    pub layer_type: LayerType,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub item_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub style_url: Option<String>,
+    pub opacity: f64,
+    pub visibility: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub blend_mode: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub effect: Option<Vec<Effect>>,
+}
+
+impl BaseMapLayer {
+    fn new(layer_type: LayerType, title: String) -> Self {
+        Self {
+            id: layer_id(),
+            title,
+            layer_type,
+            url: None,
+            item_id: None,
+            style_url: None,
+            opacity: 1.0,
+            visibility: true,
+            blend_mode: None,
+            effect: None,
+        }
+    }
+}
+
+/// One entry of a layer `effect`, optionally limited to a map scale.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Effect {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scale: Option<f64>,
+    pub value: Vec<EffectValue>,
+}
+
+impl Effect {
+    pub fn new(value: Vec<EffectValue>) -> Self {
+        Self { scale: None, value }
+    }
+
+    pub fn at_scale(mut self, scale: f64) -> Self {
+        self.scale = Some(scale);
+        self
+    }
+}
+
+/// A single effect function such as `{"type": "bloom", "strength": 1.5}`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct EffectValue {
+    #[serde(rename = "type")]
+    pub r#type: String,
+    #[serde(flatten)]
+    pub properties: serde_json::Map<String, Value>,
+}
+
+impl EffectValue {
+    pub fn new(r#type: impl Into<String>) -> Self {
+        Self {
+            r#type: r#type.into(),
+            properties: serde_json::Map::new(),
+        }
+    }
+
+    pub fn property(mut self, key: impl Into<String>, value: impl Into<Value>) -> Self {
+        self.properties.insert(key.into(), value.into());
+        self
+    }
+}
+
+/// `initialState`; an empty object lets the viewer frame the layers itself.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InitialState {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub viewpoint: Option<Value>,
+}
+
+fn feature_collection(title: &str, features: EsriQueryResponse) -> Value {
+    let geometry_type = features.geometry_type.clone().unwrap_or_else(|| {
+        let geometry = features.features.iter().find_map(|f| f.geometry.as_ref());
+        match geometry {
+            Some(g) if g.get("paths").is_some() => "esriGeometryPolyline",
+            Some(g) if g.get("rings").is_some() => "esriGeometryPolygon",
+            Some(g) if g.get("points").is_some() => "esriGeometryMultipoint",
+            _ => "esriGeometryPoint",
+        }
+        .to_string()
+    });
+    let spatial_reference = features
+        .spatial_reference
+        .clone()
+        .unwrap_or(SpatialReference {
+            wkid: 4326,
+            latest_wkid: Some(4326),
+        });
+
+    let mut fields = vec![json!({
+        "name": "OBJECTID",
+        "alias": "OBJECTID",
+        "type": "esriFieldTypeOID",
+    })];
+    // Fields can be missing or null on some features, and a double column
+    // can hold whole numbers, so infer each type from every feature.
+    let mut field_types: Vec<(&String, Option<&str>)> = Vec::new();
+    for feature in &features.features {
+        let Value::Object(attributes) = &feature.attributes else {
+            continue;
+        };
+        for (name, value) in attributes.iter().filter(|(name, _)| *name != "OBJECTID") {
+            let field_type = match value {
+                Value::Null => None,
+                Value::Number(n) if n.is_i64() || n.is_u64() => Some("esriFieldTypeInteger"),
+                Value::Number(_) => Some("esriFieldTypeDouble"),
+                _ => Some("esriFieldTypeString"),
+            };
+            match field_types.iter_mut().find(|(field, _)| *field == name) {
+                Some((_, current)) => *current = widen_field_type(*current, field_type),
+                None => field_types.push((name, field_type)),
+            }
+        }
+    }
+    for (name, field_type) in field_types {
+        let field_type = field_type.unwrap_or("esriFieldTypeString");
+        fields.push(json!({ "name": name, "alias": name, "type": field_type }));
+    }
+
+    // New OBJECTIDs go after the largest one already present, so they never
+    // collide with a feature that had one.
+    let mut next_object_id = features
+        .features
+        .iter()
+        .filter_map(|f| f.attributes.get("OBJECTID").and_then(Value::as_i64))
+        .max()
+        .unwrap_or(0);
+    let features: Vec<Value> = features
+        .features
+        .into_iter()
+        .map(|feature| {
+            let mut attributes = feature.attributes;
+            if let Value::Object(map) = &mut attributes {
+                map.entry("OBJECTID").or_insert_with(|| {
+                    next_object_id += 1;
+                    json!(next_object_id)
+                });
+            }
+            json!({ "attributes": attributes, "geometry": feature.geometry })
+        })
+        .collect();
+
+    let symbol = match geometry_type.as_str() {
+        "esriGeometryPolyline" => json!({
+            "type": "esriSLS",
+            "style": "esriSLSSolid",
+            "color": [0, 122, 194, 255],
+            "width": 2,
+        }),
+        "esriGeometryPolygon" => json!({
+            "type": "esriSFS",
+            "style": "esriSFSSolid",
+            "color": [0, 122, 194, 64],
+            "outline": {
+                "type": "esriSLS",
+                "style": "esriSLSSolid",
+                "color": [0, 122, 194, 255],
+                "width": 1,
+            },
+        }),
+        _ => json!({
+            "type": "esriSMS",
+            "style": "esriSMSCircle",
+            "color": [0, 122, 194, 255],
+            "size": 8,
+            "outline": { "color": [255, 255, 255, 255], "width": 1 },
+        }),
+    };
+
+    json!({
+        "layers": [{
+            "layerDefinition": {
+                "name": title,
+                "type": "Feature Layer",
+                "geometryType": geometry_type,
+                "objectIdField": "OBJECTID",
+                "fields": fields,
+                "drawingInfo": {
+                    "renderer": { "type": "simple", "symbol": symbol },
+                },
+            },
+            "featureSet": {
+                "geometryType": geometry_type,
+                "spatialReference": spatial_reference,
+                "features": features,
+            },
+        }],
+    })
+}
+
+/// The narrowest field type that holds values of both `current` and `next`;
+/// `None` is a field only seen null so far.
+fn widen_field_type<'a>(current: Option<&'a str>, next: Option<&'a str>) -> Option<&'a str> {
+    match (current, next) {
+        (current, None) => current,
+        (None, next) => next,
+        (Some(current), Some(next)) if current == next => Some(current),
+        (Some("esriFieldTypeInteger"), Some("esriFieldTypeDouble"))
+        | (Some("esriFieldTypeDouble"), Some("esriFieldTypeInteger")) => {
+            Some("esriFieldTypeDouble")
+        }
+        _ => Some("esriFieldTypeString"),
+    }
+}
+
+fn layer_id() -> String {
+    format!("{}-layer", uuid::Uuid::new_v4().simple())
+}
+
+/// Settings scoped to one layer, from [`WebMapBuilder::layer`].
+pub struct LayerConfigurator {
+    builder: WebMapBuilder,
+    layer: OperationalLayer,
+}
+
+impl LayerConfigurator {
+    pub fn opacity(mut self, opacity: f64) -> LayerConfigurator {
+        self.layer.opacity = Some(opacity);
+        self
+    }
+
+    pub fn visibility(mut self, visible: bool) -> LayerConfigurator {
+        self.layer.visibility = Some(visible);
+        self
+    }
+
+    /// `popupInfo`, e.g. `{"title": "{NAME}", "fieldInfos": [...]}`.
+    pub fn popup(mut self, popup_info: Value) -> LayerConfigurator {
+        self.layer.popup_info = Some(popup_info);
+        self
+    }
+
+    pub fn renderer(mut self, renderer: Value) -> LayerConfigurator {
+        self.layer.set_renderer(renderer);
+        self
+    }
+
+    /// Only draws features matching the SQL `expression`.
+    pub fn definition_expression(mut self, expression: impl Into<String>) -> LayerConfigurator {
+        let definition = self.layer.layer_definition.get_or_insert_with(|| json!({}));
+        definition["definitionExpression"] = Value::String(expression.into());
+        self
+    }
+
+    /// Adds the layer and returns to the builder.
+    pub fn done(self) -> WebMapBuilder {
+        self.builder.add_layer(self.layer)
+    }
+}
+
+pub struct WebMapBuilder {
+    operational_layers: Vec<OperationalLayer>,
+    tables: Vec<OperationalLayer>,
+    base_map: BaseMap,
+    initial_state: InitialState,
+}
+
+impl Default for WebMapBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl WebMapBuilder {
+    pub fn new() -> WebMapBuilder {
+        WebMapBuilder {
+            operational_layers: vec![],
+            tables: vec![],
+            base_map: BaseMap::default(),
+            initial_state: InitialState::default(),
+        }
+    }
+
+    /// A map with the single feature layer at `url`, framed on the layer's
+    /// extent over the topographic basemap.
+    ///
+    /// Fails if the layer metadata can't be fetched, so a dead url is caught
+    /// before the map is saved.
+    pub async fn from_feature_service(
+        client: impl Into<ArcGISClient>,
+        url: &str,
+        title: impl Into<String>,
+    ) -> anyhow::Result<WebMapBuilder> {
+        let client = client.into();
+        let layer = FeatureLayer::new(client, url).await?;
+
+        let mut builder = WebMapBuilder::new()
+            .set_basemap(BaseMap::topographic())
+            .add_feature_layer(url, title);
+        if let Some(extent) = layer.metadata.extent {
+            builder = builder.set_initial_extent(extent);
+        }
+
+        Ok(builder)
+    }
+
+    pub fn add_layer(mut self, layer: OperationalLayer) -> WebMapBuilder {
+        self.operational_layers.push(layer);
+        self
+    }
+
+    /// Starts configuring `layer`; it is added when
+    /// [`LayerConfigurator::done`] returns to the builder.
+    pub fn layer(self, layer: OperationalLayer) -> LayerConfigurator {
+        LayerConfigurator {
+            builder: self,
+            layer,
+        }
+    }
+
+    pub fn add_feature_layer(
+        self,
+        url: impl Into<String>,
+        title: impl Into<String>,
+    ) -> WebMapBuilder {
+        self.add_layer(OperationalLayer::feature_layer(url, title))
+    }
+
+    pub fn add_map_service_layer(
+        self,
+        url: impl Into<String>,
+        title: impl Into<String>,
+    ) -> WebMapBuilder {
+        self.add_layer(OperationalLayer::map_service(url, title))
+    }
+
+    pub fn add_vector_tile_layer(
+        self,
+        style_url: impl Into<String>,
+        title: impl Into<String>,
+    ) -> WebMapBuilder {
+        self.add_layer(OperationalLayer::vector_tile(style_url, title))
+    }
+
+    pub fn add_image_service_layer(
+        self,
+        url: impl Into<String>,
+        title: impl Into<String>,
+    ) -> WebMapBuilder {
+        self.add_layer(OperationalLayer::image_service(url, title))
+    }
+
+    /// Adds the table at `url` (`.../FeatureServer/1`) to the map's
+    /// standalone tables. Layer settings such as `set_layer_opacity` don't
+    /// apply to tables.
+    pub fn add_table(mut self, url: impl Into<String>, title: impl Into<String>) -> WebMapBuilder {
+        self.tables
+            .push(OperationalLayer::feature_layer(url, title));
+        self
+    }
+
+    /// Embeds `features` in the map as a feature collection layer, so small
+    /// datasets don't need a published feature service.
+    pub fn add_feature_collection(
+        self,
+        title: impl Into<String>,
+        features: EsriQueryResponse,
+    ) -> WebMapBuilder {
+        self.add_layer(OperationalLayer::feature_collection(title, features))
+    }
+
+    /// Adds a `GroupLayer` containing `layers`, in the order given.
+    pub fn add_group_layer(
+        self,
+        title: impl Into<String>,
+        layers: Vec<OperationalLayer>,
+    ) -> WebMapBuilder {
+        self.add_layer(OperationalLayer::group(title, layers))
+    }
+
+    /// Sets `blendMode` (e.g. `multiply`, `screen`) on the last added layer.
+    pub fn set_layer_blend_mode(mut self, blend_mode: impl Into<String>) -> WebMapBuilder {
+        if let Some(layer) = self.operational_layers.last_mut() {
+            layer.blend_mode = Some(blend_mode.into());
+        }
+        self
+    }
+
+    /// Sets the renderer of the last added layer, see
+    /// [`OperationalLayer::with_renderer`].
+    pub fn set_layer_renderer(mut self, renderer: Value) -> WebMapBuilder {
+        if let Some(layer) = self.operational_layers.last_mut() {
+            layer.set_renderer(renderer);
+        }
+        self
+    }
+
+    /// Sets `effect` on the last added layer.
+    pub fn set_layer_effect(mut self, effect: Vec<Effect>) -> WebMapBuilder {
+        if let Some(layer) = self.operational_layers.last_mut() {
+            layer.effect = Some(effect);
+        }
+        self
+    }
+
+    /// Sets `opacity` (0.0 to 1.0) on the last added layer.
+    pub fn set_layer_opacity(mut self, opacity: f64) -> WebMapBuilder {
+        if let Some(layer) = self.operational_layers.last_mut() {
+            layer.opacity = Some(opacity);
+        }
+        self
+    }
+
+    /// Sets `visibility` on the last added layer.
+    pub fn set_layer_visibility(mut self, visible: bool) -> WebMapBuilder {
+        if let Some(layer) = self.operational_layers.last_mut() {
+            layer.visibility = Some(visible);
+        }
+        self
+    }
+
+    /// Sets `opacity` on the layer at `index`, in the order layers were
+    /// added. Out of range indexes are ignored.
+    pub fn set_layer_opacity_at(mut self, index: usize, opacity: f64) -> WebMapBuilder {
+        if let Some(layer) = self.operational_layers.get_mut(index) {
+            layer.opacity = Some(opacity);
+        }
+        self
+    }
+
+    /// Sets `visibility` on the layer at `index`, in the order layers were
+    /// added. Out of range indexes are ignored.
+    pub fn set_layer_visibility_at(mut self, index: usize, visible: bool) -> WebMapBuilder {
+        if let Some(layer) = self.operational_layers.get_mut(index) {
+            layer.visibility = Some(visible);
+        }
+        self
+    }
+
+    /// Opens the map framed on `extent`.
+    pub fn set_initial_extent(mut self, extent: Extent) -> WebMapBuilder {
+        self.initial_state.viewpoint = Some(serde_json::json!({ "targetGeometry": extent }));
+        self
+    }
+
+    pub fn set_basemap(mut self, base_map: BaseMap) -> WebMapBuilder {
+        self.base_map = base_map;
+        self
+    }
+
+    /// Uses the item `item_id` as the basemap, see [`BaseMap::from_item`].
+    pub fn set_basemap_from_item(
+        self,
+        item_id: impl Into<String>,
+        layer_type: LayerType,
+        title: impl Into<String>,
+    ) -> WebMapBuilder {
+        self.set_basemap(BaseMap::from_item(item_id, layer_type, title))
+    }
+
+    /// Uses the tiled layer at `url` as the basemap, see
+    /// [`BaseMap::from_url`].
+    pub fn set_basemap_from_url(
+        self,
+        url: impl Into<String>,
+        layer_type: LayerType,
+        title: impl Into<String>,
+    ) -> WebMapBuilder {
+        self.set_basemap(BaseMap::from_url(url, layer_type, title))
+    }
+
+    pub fn build(self) -> WebMapJson {
+        WebMapJson {
+            operational_layers: self.operational_layers,
+            tables: self.tables,
+            base_map: self.base_map,
+            authoring_app: "ArcGISMapViewer".into(),
+            authoring_app_version: "2025.3".into(),
+            initial_state: self.initial_state,
+            spatial_reference: SpatialReference {
+                wkid: 102100,
+                latest_wkid: Some(3857),
+            },
+            time_zone: "system".into(),
+            version: "2.35".into(),
+        }
+    }
+}
@@ -0,0 +1,85 @@
+mod common;
+
+use arcgis_api_rs::{
+    analyze::AnalyzeQuery,
+    publish_item::{FileType, PublishItemQuery},
+};
+use common::MockServer;
+use reqwest::Client;
+
+#[tokio::test]
+async fn analyze_uploads_local_file() {
+    let server = MockServer::json(
+        r#"{"publishParameters": {"type": "csv", "layerInfo": {"geometryType": "esriGeometryPoint"}},
+            "records": [{"attributes": {"Name": "Well 1"}}]}"#,
+    );
+
+    let result = AnalyzeQuery::from_file(
+        &server.url,
+        "Name,Latitude,Longitude\nWell 1,35.0,-120.5",
+        FileType::Csv,
+    )
+    .analyze_parameters(serde_json::json!({"locationType": "coordinates"}))
+    .token("tok")
+    .build()
+    .send(&Client::new())
+    .await
+    .expect("Failed to analyze file");
+
+    let request = &server.requests()[0];
+    assert!(request.path() == "/content/features/analyze");
+    assert!(request.url.ends_with("?token=tok"));
+    assert!(request.body.contains("filename=\"upload.csv\""));
+    assert!(request.body.contains("Well 1,35.0,-120.5"));
+    assert!(request.body.contains("name=\"filetype\"\r\n\r\ncsv"));
+    assert!(result.publish_parameters["layerInfo"]["geometryType"] == "esriGeometryPoint");
+    assert!(result.records.len() == 1);
+}
+
+#[tokio::test]
+async fn analyzed_parameters_feed_publish() {
+    let server = MockServer::start(|request| {
+        let body = if request.path().ends_with("/analyze") {
+            r#"{"publishParameters": {"type": "csv", "name": "upload", "locationType": "coordinates",
+                "layerInfo": {"geometryType": "esriGeometryPoint", "fields": [
+                    {"name": "Lat", "type": "esriFieldTypeDouble", "locationType": "latitude"},
+                    {"name": "Lon", "type": "esriFieldTypeDouble", "locationType": "longitude"},
+                    {"name": "Depth", "type": "esriFieldTypeInteger", "sqlType": "sqlTypeInteger"}]}}}"#
+        } else {
+            r#"{"services": []}"#
+        };
+        (200, body.to_string())
+    });
+    let client = Client::new();
+    let analysis = AnalyzeQuery::from_file(&server.url, "Lat,Lon,Depth\n35,-120,12", FileType::Csv)
+        .build()
+        .send(&client)
+        .await
+        .expect("Failed to analyze file");
+
+    let layer_info = analysis.layer_info().expect("Missing layerInfo");
+    assert!(layer_info.geometry_type.as_deref() == Some("esriGeometryPoint"));
+    assert!(layer_info.fields[2].r#type == "esriFieldTypeInteger");
+    assert!(analysis.coordinate_field("latitude").as_deref() == Some("Lat"));
+
+    PublishItemQuery::builder(&server.url, "jdoe", "abc")
+        .name("Wells")
+        .source_item_type("CSV")
+        .analyzed(&analysis)
+        .token("tok")
+        .build()
+        .send(&client)
+        .await
+        .expect("Failed to publish");
+
+    let body: Vec<(String, String)> =
+        serde_urlencoded::from_str(&server.requests()[1].body).unwrap();
+    let parameters = &body
+        .iter()
+        .find(|(k, _)| k == "publishParameters")
+        .unwrap()
+        .1;
+    let parameters: serde_json::Value = serde_json::from_str(parameters).unwrap();
+    assert!(parameters["name"] == "Wells");
+    assert!(parameters["layerInfo"]["fields"][2]["name"] == "Depth");
+}
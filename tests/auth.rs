@@ -0,0 +1,444 @@
+mod common;
+
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use arcgis_api_rs::{
+    auth::{
+        authorize_url, exchange_code, refresh_access_token, ArcGISProvider, ArcGISTokenManager,
+        AuthType, OAuthRefreshProvider,
+    },
+    client::ArcGISClient,
+    config::Settings,
+    oauth::TokenStore,
+    parser::parse_response,
+};
+use common::MockServer;
+use reqwest::Client;
+use secrecy::{ExposeSecret, SecretString};
+
+#[tokio::test]
+async fn refresh_token_grant() {
+    let server = MockServer::json(r#"{"access_token": "new-token", "expires_in": 1800}"#);
+
+    let token = refresh_access_token(
+        &Client::new(),
+        &server.url,
+        "app-id",
+        &SecretString::new("refresh-me".into()),
+    )
+    .await
+    .expect("Failed to refresh token");
+
+    let request = &server.requests()[0];
+    assert!(request.path() == "/oauth2/token");
+    assert!(request.body.contains("grant_type=refresh_token"));
+    assert!(request.body.contains("refresh_token=refresh-me"));
+    assert!(token.access_token.expose_secret() == "new-token");
+}
+
+#[tokio::test]
+async fn authorization_code_exchange() {
+    let server = MockServer::json(
+        r#"{"access_token": "access", "expires_in": 1800, "refresh_token": "refresh",
+            "username": "jdoe"}"#,
+    );
+    let redirect_uri = "http://localhost:8000/callback";
+
+    let url = authorize_url(&server.url, "app-id", redirect_uri, &["portal", "premium"]);
+    let token = exchange_code(
+        &Client::new(),
+        &server.url,
+        "app-id",
+        redirect_uri,
+        "the-code",
+    )
+    .await
+    .expect("Failed to exchange code");
+
+    assert!(url.ends_with(
+        "/oauth2/authorize?client_id=app-id&response_type=code\
+         &redirect_uri=http%3A%2F%2Flocalhost%3A8000%2Fcallback&scope=portal%20premium"
+    ));
+    let request = &server.requests()[0];
+    assert!(request.path() == "/oauth2/token");
+    assert!(request.body.contains("grant_type=authorization_code"));
+    assert!(request.body.contains("code=the-code"));
+    assert!(token.refresh_token.expose_secret() == "refresh");
+    assert!(token.username.as_deref() == Some("jdoe"));
+}
+
+#[tokio::test]
+async fn token_store_refresh() {
+    let server = MockServer::json(r#"{"access_token": "new-token", "expires_in": 1800}"#);
+    let mut store = TokenStore {
+        access_token: "old-token".into(),
+        refresh_token: "refresh-me".into(),
+        expires_in: 0,
+    };
+
+    store
+        .refresh(&Client::new(), &server.url, "app-id")
+        .await
+        .expect("Failed to refresh token store");
+
+    assert!(store.access_token == "new-token");
+    assert!(store.expires_in == 1800);
+    assert!(store.refresh_token == "refresh-me");
+}
+
+#[tokio::test]
+async fn oauth_manager_refreshes_expired_token() {
+    let tokens = Arc::new(AtomicUsize::new(0));
+    let issued = tokens.clone();
+    let server = MockServer::start(move |_| {
+        let n = issued.fetch_add(1, Ordering::SeqCst);
+        // The first token expires inside the manager's refresh skew.
+        let expires_in = if n == 0 { 5 } else { 1800 };
+        let body = format!(
+            r#"{{"access_token": "token-{}", "expires_in": {}}}"#,
+            n, expires_in
+        );
+        (200, body)
+    });
+    let manager = ArcGISTokenManager::oauth_refresh(OAuthRefreshProvider {
+        client: Client::new(),
+        portal: server.url.clone(),
+        client_id: "app-id".to_string(),
+        refresh_token: SecretString::new("refresh-me".into()),
+    })
+    .with_skew(Duration::from_secs(10));
+
+    assert!(manager.get().await.unwrap() == "token-0");
+    assert!(manager.get().await.unwrap() == "token-1");
+    assert!(manager.get().await.unwrap() == "token-1");
+
+    let requests = server.requests();
+    assert!(requests.len() == 2);
+    assert!(requests[1].path() == "/oauth2/token");
+    assert!(requests[1].body.contains("grant_type=refresh_token"));
+    assert!(requests[1].body.contains("refresh_token=refresh-me"));
+}
+
+#[tokio::test]
+async fn code_exchange_seeds_token_manager() {
+    let server = MockServer::start(|request| {
+        if request.body.contains("grant_type=authorization_code") {
+            let body = r#"{"access_token": "access", "expires_in": 8,
+                "refresh_token": "refresh", "username": "jdoe"}"#;
+            (200, body.to_string())
+        } else {
+            (
+                200,
+                r#"{"access_token": "refreshed", "expires_in": 1800}"#.to_string(),
+            )
+        }
+    });
+    let client = Client::new();
+    let token = exchange_code(
+        &client,
+        &server.url,
+        "app-id",
+        "http://localhost:8000/callback",
+        "the-code",
+    )
+    .await
+    .expect("Failed to exchange code");
+
+    let manager = token.into_token_manager(&client, &server.url, "app-id");
+    assert!(manager.get().await.unwrap() == "access");
+    assert!(server.requests().len() == 1);
+
+    // Once the exchanged token is inside the refresh skew, the refresh
+    // token is used to get a new one.
+    let manager = manager.with_skew(Duration::from_secs(10));
+    assert!(manager.get().await.unwrap() == "refreshed");
+    let requests = server.requests();
+    assert!(requests.len() == 2);
+    assert!(requests[1].body.contains("refresh_token=refresh"));
+}
+
+fn provider(portal: &str) -> ArcGISProvider {
+    ArcGISProvider {
+        client: Client::new(),
+        portal: portal.to_string(),
+        username: SecretString::new("user".into()),
+        password: SecretString::new("pass".into()),
+        referer: "127.0.0.1".to_string(),
+        expiration: "60".to_string(),
+    }
+}
+
+fn token_body(token: &str) -> String {
+    let expires = SystemTime::now() + Duration::from_secs(3600);
+    let expires = expires.duration_since(UNIX_EPOCH).unwrap().as_millis();
+    format!(
+        r#"{{"token": "{}", "expires": {}, "ssl": true}}"#,
+        token, expires
+    )
+}
+
+#[tokio::test]
+async fn authenticated_manager_prefetches_token() {
+    let server = MockServer::json(token_body("first"));
+
+    let manager = ArcGISTokenManager::authenticated(provider(&server.url))
+        .await
+        .expect("Failed to authenticate");
+    assert!(server.requests().len() == 1);
+
+    assert!(manager.get().await.unwrap() == "first");
+    assert!(server.requests().len() == 1);
+}
+
+#[tokio::test]
+async fn manager_refresh_replaces_cached_token() {
+    let tokens = Arc::new(AtomicUsize::new(0));
+    let issued = tokens.clone();
+    let server = MockServer::start(move |_| {
+        let n = issued.fetch_add(1, Ordering::SeqCst);
+        (200, token_body(&format!("token-{}", n)))
+    });
+    let manager = ArcGISTokenManager::new(provider(&server.url));
+    assert!(manager.token_expiration().await.is_none());
+
+    assert!(manager.get().await.unwrap() == "token-0");
+    manager.refresh().await.expect("Failed to refresh");
+
+    assert!(manager.get().await.unwrap() == "token-1");
+    let expires = manager.token_expiration().await.unwrap();
+    assert!(expires > SystemTime::now() + Duration::from_secs(3000));
+}
+
+#[tokio::test]
+async fn authenticated_manager_surfaces_bad_credentials() {
+    let server = MockServer::json(r#"{"error": {"code": 400, "message": "Invalid credentials"}}"#);
+
+    let manager = ArcGISTokenManager::authenticated(provider(&server.url)).await;
+
+    assert!(manager.is_err());
+}
+
+fn settings(portal: &str) -> Settings {
+    Settings {
+        portal_root: portal.to_string(),
+        portal_apps_root: portal.to_string(),
+        services_root: portal.to_string(),
+        client_id: "app-id".to_string(),
+        client_secret: SecretString::new("app-secret".into()),
+        token_expiration: "60".to_string(),
+        test_token: None,
+        test_user_name: None,
+        accept_language: None,
+        #[cfg(feature = "dangerous-config")]
+        danger_accept_invalid_certs: false,
+        arcgis_portal: portal.to_string(),
+        arcgis_api_root: portal.to_string(),
+        arcgis_portal_apps: portal.to_string(),
+        arcgis_username: SecretString::new("user".into()),
+        arcgis_password: SecretString::new("pass".into()),
+    }
+}
+
+#[tokio::test]
+async fn client_sends_accept_language() {
+    let server = MockServer::json("{}");
+    let settings = Settings {
+        accept_language: Some("es".into()),
+        ..settings(&server.url)
+    };
+
+    let client = settings
+        .build_authorized_request_client(AuthType::None)
+        .await
+        .expect("Failed to build client");
+    client.execute(client.get(&server.url)).await.unwrap();
+
+    assert!(server.requests()[0].header("Accept-Language") == Some("es"));
+}
+
+#[cfg(feature = "dangerous-config")]
+#[tokio::test]
+async fn client_accepting_invalid_certs_still_connects() {
+    let server = MockServer::json("{}");
+    let settings = Settings {
+        danger_accept_invalid_certs: true,
+        ..settings(&server.url)
+    };
+
+    let client = settings
+        .build_authorized_request_client(AuthType::None)
+        .await
+        .expect("Failed to build client");
+
+    assert!(
+        client
+            .execute(client.get(&server.url))
+            .await
+            .unwrap()
+            .status()
+            == 200
+    );
+}
+
+#[tokio::test]
+async fn client_sends_api_key() {
+    let server = MockServer::json("{}");
+
+    let client = settings(&server.url)
+        .build_authorized_request_client(AuthType::ApiKey(SecretString::new("my-key".into())))
+        .await
+        .expect("Failed to build client");
+    client.execute(client.get(&server.url)).await.unwrap();
+
+    let requests = server.requests();
+    assert!(requests.len() == 1);
+    assert!(requests[0].header("X-Esri-Authorization") == Some("Bearer my-key"));
+}
+
+#[tokio::test]
+async fn api_key_is_only_sent_to_the_portal() {
+    let portal = MockServer::json("{}");
+    let basemap = MockServer::json("{}");
+
+    let client = ArcGISClient::builder()
+        .portal_root(format!("{}/sharing/rest", portal.url))
+        .api_key("my-key")
+        .build();
+    client.execute(client.get(&portal.url)).await.unwrap();
+    client.execute(client.get(&basemap.url)).await.unwrap();
+
+    assert!(portal.requests()[0].header("X-Esri-Authorization") == Some("Bearer my-key"));
+    assert!(basemap.requests()[0]
+        .header("X-Esri-Authorization")
+        .is_none());
+}
+
+#[tokio::test]
+async fn token_is_only_sent_to_the_portal() {
+    let portal = MockServer::json(r#"{"access_token": "app-token", "expires_in": 7200}"#);
+    let basemap = MockServer::json("{}");
+
+    let client = settings(&portal.url)
+        .build_authorized_request_client(AuthType::AppAuth)
+        .await
+        .expect("Failed to build client");
+    client.execute(client.get(&portal.url)).await.unwrap();
+    client.execute(client.get(&basemap.url)).await.unwrap();
+
+    let requests = portal.requests();
+    assert!(requests[0].path() == "/oauth2/token");
+    assert!(requests[0].header("X-Esri-Authorization").is_none());
+    assert!(requests[1].header("X-Esri-Authorization") == Some("Bearer app-token"));
+    assert!(basemap.requests()[0]
+        .header("X-Esri-Authorization")
+        .is_none());
+}
+
+#[tokio::test]
+async fn oauth_refresh_client_caches_the_access_token() {
+    let portal = MockServer::json(r#"{"access_token": "user-token", "expires_in": 1800}"#);
+
+    let client = ArcGISClient::builder()
+        .portal_root(&portal.url)
+        .oauth_refresh_auth("app-id", "refresh-me")
+        .build();
+    client.execute(client.get(&portal.url)).await.unwrap();
+    client.execute(client.get(&portal.url)).await.unwrap();
+
+    let requests = portal.requests();
+    assert!(requests.len() == 3);
+    assert!(requests[0].path() == "/oauth2/token");
+    assert!(requests[0].body.contains("grant_type=refresh_token"));
+    assert!(requests[0].body.contains("refresh_token=refresh-me"));
+    assert!(requests[1..]
+        .iter()
+        .all(|r| r.header("X-Esri-Authorization") == Some("Bearer user-token")));
+}
+
+#[tokio::test]
+async fn oauth_client_signs_requests_after_code_exchange() {
+    let portal = MockServer::json(
+        r#"{"access_token": "user-token", "expires_in": 1800, "refresh_token": "refresh"}"#,
+    );
+
+    let client = ArcGISClient::builder()
+        .portal_root(&portal.url)
+        .oauth_auth("app-id", "http://localhost:8000/callback", ["portal"])
+        .build();
+    let url = client.authorize_url().unwrap();
+    let before = client.execute(client.get(&portal.url)).await;
+    client.exchange_code("the-code").await.unwrap();
+    client.execute(client.get(&portal.url)).await.unwrap();
+
+    let requests = portal.requests();
+    assert!(url.starts_with(&format!("{}/oauth2/authorize?client_id=app-id", portal.url)));
+    assert!(url.ends_with("&scope=portal"));
+    assert!(before.is_err());
+    assert!(requests.len() == 2);
+    assert!(requests[0].body.contains("grant_type=authorization_code"));
+    assert!(requests[0].header("X-Esri-Authorization").is_none());
+    assert!(requests[1].header("X-Esri-Authorization") == Some("Bearer user-token"));
+}
+
+#[derive(serde::Deserialize)]
+struct Success {
+    success: bool,
+}
+
+#[tokio::test]
+async fn rejected_token_is_refreshed_and_retried() {
+    let tokens = Arc::new(AtomicUsize::new(0));
+    let issued = tokens.clone();
+    let server = MockServer::start(move |request| {
+        if request.path().ends_with("/generateToken") {
+            let n = issued.fetch_add(1, Ordering::SeqCst);
+            (200, token_body(&format!("token-{}", n)))
+        } else if request.url.contains("token=token-0") {
+            let body = r#"{"error": {"code": 498, "message": "Invalid token."}}"#;
+            (200, body.to_string())
+        } else {
+            (200, r#"{"success": true}"#.to_string())
+        }
+    });
+    let manager = ArcGISTokenManager::new(provider(&server.url));
+    let client = Client::new();
+
+    let result: Success = manager
+        .with_token(|token| {
+            let request = client.get(format!("{}/content?token={}", server.url, token));
+            async move { parse_response(request.send().await?).await }
+        })
+        .await
+        .expect("Request failed after token refresh");
+
+    assert!(result.success);
+    assert!(tokens.load(Ordering::SeqCst) == 2);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn concurrent_callers_share_one_token_request() {
+    let server = MockServer::start(|_| {
+        // Hold the refresh open so every task arrives while it is in flight.
+        std::thread::sleep(Duration::from_millis(100));
+        (200, token_body("shared"))
+    });
+    let manager = Arc::new(ArcGISTokenManager::new(provider(&server.url)));
+
+    let tasks: Vec<_> = (0..50)
+        .map(|_| {
+            let manager = manager.clone();
+            tokio::spawn(async move { manager.get().await })
+        })
+        .collect();
+    for task in tasks {
+        assert!(task.await.unwrap().unwrap() == "shared");
+    }
+
+    assert!(server.requests().len() == 1);
+}
@@ -0,0 +1,115 @@
+// Each test binary uses a different subset of these helpers.
+#![allow(dead_code)]
+
+use std::sync::{Arc, Mutex};
+
+/// A request as seen by [`MockServer`].
+#[derive(Debug, Clone)]
+pub struct RecordedRequest {
+    pub method: String,
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl RecordedRequest {
+    pub fn path(&self) -> &str {
+        self.url.split('?').next().unwrap_or_default()
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// A local HTTP server standing in for a portal or service, so requests can
+/// be checked without ArcGIS credentials.
+pub struct MockServer {
+    pub url: String,
+    requests: Arc<Mutex<Vec<RecordedRequest>>>,
+}
+
+impl MockServer {
+    /// Answers each request with the `(status, body)` returned by `handler`.
+    pub fn start<F, B>(handler: F) -> Self
+    where
+        F: Fn(&RecordedRequest) -> (u16, B) + Send + 'static,
+        B: Into<Vec<u8>>,
+    {
+        Self::start_with_headers(move |request| {
+            let (status, body) = handler(request);
+            (status, vec![], body)
+        })
+    }
+
+    /// Like [`MockServer::start`], with response headers such as
+    /// `Retry-After`.
+    pub fn start_with_headers<F, B>(handler: F) -> Self
+    where
+        F: Fn(&RecordedRequest) -> (u16, Vec<(&'static str, String)>, B) + Send + 'static,
+        B: Into<Vec<u8>>,
+    {
+        let server = tiny_http::Server::http("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", server.server_addr().to_ip().unwrap());
+        let requests = Arc::new(Mutex::new(vec![]));
+
+        let seen = requests.clone();
+        std::thread::spawn(move || {
+            for mut request in server.incoming_requests() {
+                let mut body = String::new();
+                let _ = request.as_reader().read_to_string(&mut body);
+                let recorded = RecordedRequest {
+                    method: request.method().to_string(),
+                    url: request.url().to_string(),
+                    headers: request
+                        .headers()
+                        .iter()
+                        .map(|h| (h.field.to_string(), h.value.to_string()))
+                        .collect(),
+                    body,
+                };
+
+                let (status, headers, body) = handler(&recorded);
+                seen.lock().unwrap().push(recorded);
+                let mut response = tiny_http::Response::from_data(body).with_status_code(status);
+                for (name, value) in headers {
+                    let header = tiny_http::Header::from_bytes(name, value).unwrap();
+                    response.add_header(header);
+                }
+                let _ = request.respond(response);
+            }
+        });
+
+        Self { url, requests }
+    }
+
+    /// Answers every request with `body`.
+    pub fn json(body: impl Into<String>) -> Self {
+        let body = body.into();
+        Self::start(move |_| (200, body.clone()))
+    }
+
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+/// An item's `ItemData` JSON with only the required fields, owned by `jdoe`.
+pub fn item_json(id: &str, item_type: &str) -> String {
+    item_json_with(id, item_type, serde_json::json!({}))
+}
+
+/// Like [`item_json`], with the fields of `extra` added or replaced.
+pub fn item_json_with(id: &str, item_type: &str, extra: serde_json::Value) -> String {
+    let mut item = serde_json::json!({
+        "id": id, "owner": "jdoe", "title": "Wells", "type": item_type, "created": 0,
+        "modified": 0, "typeKeywords": [], "tags": [], "isOrgItem": true, "categories": []
+    });
+    if let serde_json::Value::Object(extra) = extra {
+        item.as_object_mut().unwrap().extend(extra);
+    }
+    item.to_string()
+}
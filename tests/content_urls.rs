@@ -0,0 +1,219 @@
+mod common;
+
+use arcgis_api_rs::{
+    add_item::AddItemQuery,
+    publish_item::{FileType, PublishItemQuery, PublishTypeMismatch},
+    reassign_content::ReassignContentQuery,
+    share_items::ShareItemsQuery,
+};
+use common::MockServer;
+use reqwest::Client;
+
+#[tokio::test]
+async fn add_item_encodes_user_name() {
+    let server = MockServer::json(r#"{"success": true, "id": "abc", "folder": ""}"#);
+
+    AddItemQuery::builder(&server.url, "José Doe")
+        .build()
+        .send(&Client::new())
+        .await
+        .unwrap();
+
+    assert!(server.requests()[0].path() == "/content/users/Jos%C3%A9%20Doe/addItem");
+}
+
+#[tokio::test]
+async fn add_item_targets_folder() {
+    let server = MockServer::json(r#"{"success": true, "id": "abc", "folder": "f1"}"#);
+
+    AddItemQuery::builder(&server.url, "jdoe")
+        .folder("f1")
+        .token("tok")
+        .build()
+        .send(&Client::new())
+        .await
+        .unwrap();
+
+    let request = &server.requests()[0];
+    assert!(request.path() == "/content/users/jdoe/f1/addItem");
+    assert!(request.url.ends_with("?token=tok"));
+}
+
+#[tokio::test]
+async fn publish_targets_folder() {
+    let server = MockServer::json(r#"{"services": []}"#);
+
+    PublishItemQuery::builder(&server.url, "jdoe", "abc")
+        .name("Wells")
+        .source_item_type("CSV")
+        .folder("f1")
+        .token("tok")
+        .build()
+        .send(&Client::new())
+        .await
+        .unwrap();
+
+    assert!(server.requests()[0].path() == "/content/users/jdoe/f1/publish");
+}
+
+#[tokio::test]
+async fn share_items_encodes_user_name() {
+    let server = MockServer::json(r#"{"results": []}"#);
+
+    ShareItemsQuery::builder(&server.url, "José Doe", vec!["abc".into()])
+        .build()
+        .send(&Client::new())
+        .await
+        .unwrap();
+
+    assert!(server.requests()[0].path() == "/content/users/Jos%C3%A9%20Doe/shareItems");
+}
+
+#[tokio::test]
+async fn publish_rejects_mismatched_file_type() {
+    let server = MockServer::json(r#"{"services": []}"#);
+
+    let error = PublishItemQuery::builder(&server.url, "jdoe", "abc")
+        .file_type(FileType::Csv)
+        .source_item_type("Shapefile")
+        .token("tok")
+        .build()
+        .send(&Client::new())
+        .await
+        .unwrap_err();
+
+    let mismatch = error.downcast_ref::<PublishTypeMismatch>().unwrap();
+    assert!(mismatch.file_type == FileType::Csv && mismatch.item_type == "Shapefile");
+    assert!(server.requests().is_empty());
+}
+
+#[tokio::test]
+async fn async_add_item_polls_status() {
+    let polls = std::sync::atomic::AtomicUsize::new(0);
+    let server = MockServer::start(move |request| {
+        let body = if request.path().ends_with("/addItem") {
+            r#"{"success": true, "id": "abc", "folder": null}"#
+        } else if polls.fetch_add(1, std::sync::atomic::Ordering::SeqCst) == 0 {
+            r#"{"status": "processing", "itemId": "abc"}"#
+        } else {
+            r#"{"status": "completed", "itemId": "abc"}"#
+        };
+        (200, body.to_string())
+    });
+    let client = Client::new();
+
+    let response = AddItemQuery::builder(&server.url, "jdoe")
+        .data_url("https://example.com/wells.csv")
+        .async_upload(true)
+        .token("tok")
+        .build()
+        .send(&client)
+        .await
+        .expect("Failed to add item");
+    let id = response
+        .job
+        .expect("Async upload should return a job")
+        .wait(
+            &client,
+            std::time::Duration::from_millis(1),
+            std::time::Duration::from_secs(5),
+        )
+        .await
+        .expect("Upload did not complete");
+
+    let requests = server.requests();
+    assert!(id == "abc");
+    assert!(requests.len() == 3);
+    assert!(requests[2].path() == "/content/users/jdoe/items/abc/status");
+    assert!(requests[2].url.contains("token=tok"));
+}
+
+#[tokio::test]
+async fn async_add_item_wait_times_out() {
+    let server = MockServer::start(|request| {
+        let body = if request.path().ends_with("/addItem") {
+            r#"{"success": true, "id": "abc", "folder": null}"#
+        } else {
+            r#"{"status": "processing", "itemId": "abc"}"#
+        };
+        (200, body.to_string())
+    });
+    let client = Client::new();
+
+    let response = AddItemQuery::builder(&server.url, "jdoe")
+        .data_url("https://example.com/wells.csv")
+        .async_upload(true)
+        .build()
+        .send(&client)
+        .await
+        .expect("Failed to add item");
+    let error = response
+        .job
+        .unwrap()
+        .wait(
+            &client,
+            std::time::Duration::from_millis(1),
+            std::time::Duration::from_millis(50),
+        )
+        .await
+        .unwrap_err();
+
+    assert!(error.to_string().contains("did not finish"));
+}
+
+#[tokio::test]
+async fn add_item_multipart_boundary_is_gateway_safe() {
+    let server = MockServer::json(r#"{"success": true, "id": "abc", "folder": ""}"#);
+
+    AddItemQuery::builder(&server.url, "jdoe")
+        .file("Longitude,Latitude\n-120.5,35.0")
+        .build()
+        .send(&Client::new())
+        .await
+        .unwrap();
+
+    let content_type = server.requests()[0]
+        .header("Content-Type")
+        .unwrap()
+        .to_string();
+    let boundary = content_type
+        .strip_prefix("multipart/form-data; boundary=")
+        .expect("Not a multipart body");
+    assert!(!boundary.is_empty() && boundary.len() <= 70);
+    assert!(boundary.chars().all(|c| c.is_ascii_hexdigit() || c == '-'));
+}
+
+#[tokio::test]
+async fn reassign_all_content() {
+    let searches = std::sync::atomic::AtomicUsize::new(0);
+    let server = MockServer::start(move |request| {
+        if request.path() != "/search" {
+            return (200, r#"{"success": true}"#.to_string());
+        }
+        // 5 items before, one left behind after.
+        let total = match searches.fetch_add(1, std::sync::atomic::Ordering::SeqCst) {
+            0 => 5,
+            _ => 1,
+        };
+        let body = format!(
+            r#"{{"total": {}, "start": 1, "num": 1, "nextStart": -1, "results": []}}"#,
+            total
+        );
+        (200, body)
+    });
+
+    let response = ReassignContentQuery::builder(&server.url, "José Doe", "jdoe_admin")
+        .token("tok")
+        .build()
+        .send(&Client::new())
+        .await
+        .expect("Failed to reassign content");
+
+    let requests = server.requests();
+    let request = &requests[1];
+    assert!(response.success);
+    assert!(response.reassigned == 4 && response.failed == 1);
+    assert!(requests[0].url.contains("owner%3A%22Jos%C3%A9+Doe%22"));
+    assert!(request.path() == "/community/users/Jos%C3%A9%20Doe/reassign");
+    assert!(request.body.contains("targetUsername=jdoe_admin"));
+}
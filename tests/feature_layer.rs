@@ -1,14 +1,19 @@
+mod common;
+
 use arcgis_api_rs::{
     auth::AuthType,
     config::get_config,
-    feature_layer::FeatureLayer,
+    feature_layer::{EsriType, FeatureLayer, FeatureServiceInfo, SqlType, SyncModel},
     feature_layer_query::{
-        EsriCountResponse, EsriQueryResponse, FeatureLayerQueryBuilder, GeometryType,
-        QueryGeometry, SpatialRelationship,
+        EsriCountResponse, EsriFeature, EsriQueryResponse, Extent, FeatureLayerQueryBuilder,
+        GeometryType, QuantizationMode, QueryGeometry, ResponseFormat, SpatialRelationship,
+        StatisticType,
     },
-    feature_layer_update::{ApplyEditsQuery, ApplyEditsResponse},
-    parser::parse_response,
+    feature_layer_update::{ApplyEditsQuery, ApplyEditsResponse, ApplyEditsRolledBack, BodyFormat},
+    parser::{parse_response, EsriErrorResponse},
 };
+use common::MockServer;
+use futures_util::TryStreamExt;
 use reqwest::Client;
 
 // USA_States_Generalized_Boundaries
@@ -80,6 +85,200 @@ async fn query_feature_layer_metadata() {
 //     assert!(json.features.len() == 51);
 // }
 
+#[tokio::test]
+async fn feature_layer_service_info() {
+    let server = MockServer::start(|request| {
+        let body = if request.path().ends_with("/FeatureServer/0") {
+            r#"{"type": "Feature Layer", "name": "Wells", "fields": []}"#
+        } else {
+            r#"{"currentVersion": 11.1, "owningSystemUrl": "https://gis.example.com/portal",
+                "layers": [{"id": 0, "name": "Wells"}]}"#
+        };
+        (200, body.to_string())
+    });
+    let url = format!("{}/rest/services/Wells/FeatureServer/0", server.url);
+    let layer = FeatureLayer::new(&Client::new(), &url).await.unwrap();
+
+    let info = layer
+        .service_info()
+        .await
+        .expect("Failed to fetch service info");
+
+    assert!(server.requests()[1].path() == "/rest/services/Wells/FeatureServer");
+    assert!(info.owning_system_url() == Some("https://gis.example.com/portal"));
+    assert!(info.is_owned_by("https://GIS.example.com/portal/sharing/rest/"));
+    assert!(!info.is_owned_by("https://www.arcgis.com/sharing/rest"));
+    assert!(info.layers[0].name == "Wells");
+}
+
+#[tokio::test]
+async fn arcgis_online_services_accept_organization_tokens() {
+    let server = MockServer::json(
+        r#"{"currentVersion": 11.3, "owningSystemUrl": "http://www.arcgis.com", "layers": []}"#,
+    );
+
+    let info = FeatureServiceInfo::fetch(&Client::new(), &server.url)
+        .await
+        .expect("Failed to fetch service info");
+
+    assert!(info.is_owned_by("https://acme.maps.arcgis.com/sharing/rest"));
+    assert!(info.is_owned_by("https://www.arcgis.com/sharing/rest/"));
+    assert!(!info.is_owned_by("https://gis.example.com/portal/sharing/rest"));
+}
+
+#[tokio::test]
+async fn feature_attachments() {
+    let server = MockServer::start(|request| {
+        let body = if request.path().ends_with("/addAttachment") {
+            r#"{"addAttachmentResult": {"objectId": 12, "globalId": "{A1}", "success": true}}"#
+        } else if request.path().ends_with("/deleteAttachments") {
+            r#"{"deleteAttachmentResults": [{"objectId": 12, "success": true}]}"#
+        } else if request.path().ends_with("/attachments") {
+            r#"{"attachmentInfos": [{"id": 12, "name": "well.jpg", "contentType": "image/jpeg", "size": 3}]}"#
+        } else {
+            r#"{"type": "Feature Layer", "name": "Wells", "fields": []}"#
+        };
+        (200, body.to_string())
+    });
+    let url = format!("{}/FeatureServer/0", server.url);
+    let layer = FeatureLayer::new(&Client::new(), &url).await.unwrap();
+    let attachments = layer.attachments(5);
+
+    let added = attachments
+        .add(vec![1, 2, 3], "well.jpg", "image/jpeg")
+        .await
+        .expect("Failed to add attachment");
+    let listed = attachments.list().await.expect("Failed to list");
+    let deleted = attachments.delete(&[12]).await.expect("Failed to delete");
+
+    let requests = server.requests();
+    assert!(requests[1].path() == "/FeatureServer/0/5/addAttachment");
+    assert!(requests[1]
+        .header("Content-Type")
+        .unwrap()
+        .starts_with("multipart/form-data"));
+    assert!(requests[1].body.contains(r#"filename="well.jpg""#));
+    assert!(requests[3].body == "attachmentIds=12&f=json");
+    assert!(added.id == 12 && added.size == 3);
+    assert!(listed[0].content_type == "image/jpeg");
+    assert!(deleted[0].success);
+}
+
+#[tokio::test]
+async fn create_replica_requires_sync() {
+    let server = MockServer::start(|request| {
+        let body = if request.path().ends_with("/createReplica") {
+            r#"{"transportType": "esriTransportTypeUrl", "responseUrl": "https://example.com/wells.geodatabase"}"#
+        } else if request.path().ends_with("/FeatureServer") {
+            r#"{"capabilities": "Query,Sync", "layers": []}"#
+        } else {
+            r#"{"type": "Feature Layer", "name": "Wells", "fields": []}"#
+        };
+        (200, body.to_string())
+    });
+    let url = format!("{}/FeatureServer/0", server.url);
+    let layer = FeatureLayer::new(&Client::new(), &url).await.unwrap();
+
+    let replica = layer
+        .create_replica(&[0, 1], None, SyncModel::PerLayer)
+        .await
+        .expect("Failed to create replica");
+
+    let request = &server.requests()[2];
+    assert!(request.path() == "/FeatureServer/createReplica");
+    assert!(request.body.contains("layers=0%2C1"));
+    assert!(request.body.contains("syncModel=perLayer"));
+    assert!(replica.response_url.as_deref() == Some("https://example.com/wells.geodatabase"));
+}
+
+#[tokio::test]
+async fn validate_where_clause() {
+    let server = MockServer::start(|request| {
+        let body = if request.path().ends_with("/validateSQL") {
+            r#"{"isValidSQL": false, "validationErrors": [{"errorCode": -2147220985, "description": "An invalid SQL statement was used."}]}"#
+        } else {
+            r#"{"type": "Feature Layer", "name": "Wells", "fields": [], "supportsValidateSQL": true}"#
+        };
+        (200, body.to_string())
+    });
+    let url = format!("{}/FeatureServer/0", server.url);
+    let layer = FeatureLayer::new(&Client::new(), &url).await.unwrap();
+
+    let validation = layer
+        .validate_sql("DEPTH >", SqlType::Where)
+        .await
+        .expect("Failed to validate SQL");
+
+    let request = &server.requests()[1];
+    assert!(layer.metadata.supports_validate_sql);
+    assert!(request.path() == "/FeatureServer/0/validateSQL");
+    assert!(request.body.contains("sql=DEPTH+%3E&sqlType=where"));
+    assert!(!validation.is_valid_sql);
+    assert!(validation.validation_errors[0].description == "An invalid SQL statement was used.");
+}
+
+#[tokio::test]
+async fn ping_feature_layer() {
+    // configure
+    dotenv::dotenv().ok();
+    let fs_url = std::env::var("TEST_FEATURE_SERVICE")
+        .expect("Failed to find env variable 'TEST_FEATURE_SERVICE'");
+    let client = Client::new();
+    let layer = FeatureLayer::new(&client, &fs_url)
+        .await
+        .expect("Failed to fetch feature layer");
+
+    // test
+    assert!(layer.ping().await.expect("Ping request failed"));
+}
+
+#[tokio::test]
+async fn ping_reachable_and_unreachable_layers() {
+    // configure
+    let server = MockServer::start(|request| match request.path() {
+        "/Wells/FeatureServer/0/query" => (200, r#"{"count": 12}"#),
+        "/Stopped/FeatureServer/0/query" => (
+            200,
+            r#"{"error": {"code": 500, "message": "Service not started"}}"#,
+        ),
+        "/Missing/FeatureServer/0/query" => (404, ""),
+        _ => (
+            200,
+            r#"{"type": "Feature Layer", "name": "Layer", "fields": []}"#,
+        ),
+    });
+    let client = Client::new();
+    let layer = |name: &str| {
+        let url = format!("{}/{}/FeatureServer/0", server.url, name);
+        let client = client.clone();
+        async move { FeatureLayer::new(&client, &url).await.unwrap() }
+    };
+
+    // test
+    assert!(layer("Wells").await.ping().await.unwrap());
+    assert!(!layer("Stopped").await.ping().await.unwrap());
+    assert!(!layer("Missing").await.ping().await.unwrap());
+    let query = &server.requests()[1];
+    assert!(query.url.contains("returnCountOnly=true"));
+}
+
+#[tokio::test]
+async fn metadata_errors_keep_the_service_error() {
+    // configure
+    let server = MockServer::json(r#"{"error": {"code": 498, "message": "Invalid token."}}"#);
+
+    // test
+    let error = FeatureLayer::new(&Client::new(), &server.url)
+        .await
+        .unwrap_err();
+    assert!(error
+        .to_string()
+        .starts_with("Failed to fetch feature service metadata: "));
+    assert!(error.to_string().contains("Invalid token."));
+    let service_error = error.downcast_ref::<EsriErrorResponse>().unwrap();
+    assert!(service_error.error.is_token_error());
+}
+
 #[tokio::test]
 async fn query_with_point_geometry() {
     // configure
@@ -181,3 +380,447 @@ async fn update_feature() {
         .iter()
         .for_each(|edit| assert!(edit.success))
 }
+
+#[tokio::test]
+async fn export_feature_layer_ndjson() {
+    // configure
+    let server = MockServer::start(|request| {
+        let body = if request.url.contains("resultOffset=0") {
+            r#"{"features": [{"attributes": {"id": 1}}, {"attributes": {"id": 2}}],
+                "exceededTransferLimit": true}"#
+        } else {
+            r#"{"features": [{"attributes": {"id": 3}}]}"#
+        };
+        (200, body.to_string())
+    });
+    let mut out: Vec<u8> = vec![];
+
+    // test
+    let written = FeatureLayerQueryBuilder::new()
+        .export_ndjson(&Client::new(), &server.url, &mut out)
+        .await
+        .expect("Failed to export features");
+
+    let lines: Vec<&str> = std::str::from_utf8(&out).unwrap().lines().collect();
+    assert!(written == 3);
+    assert!(lines[2] == r#"{"attributes":{"id":3},"geometry":null}"#);
+    assert!(server.requests()[1].url.contains("resultOffset=2"));
+}
+
+#[tokio::test]
+async fn query_with_order_and_geometry() {
+    let server = MockServer::json(
+        r#"{"geometryType": "esriGeometryPoint",
+            "fields": [{"name": "NAME", "alias": "Name", "type": "esriFieldTypeString"},
+                       {"name": "DAY", "alias": "Day", "type": "esriFieldTypeDateOnly"}],
+            "features": [{"attributes": {"NAME": "Well 1"}, "geometry": {"x": -120.5, "y": 35.0}}]}"#,
+    );
+
+    let response = FeatureLayerQueryBuilder::new()
+        .set_where("DEPTH > 100")
+        .set_out_fields_vec(vec!["NAME".into()])
+        .set_return_geometry(true)
+        .set_query_geometry(
+            QueryGeometry::envelope(-121.0, 34.0, -119.0, 36.0, SpatialRelationship::Contains)
+                .with_spatial_reference(4326),
+        )
+        .set_record_count(100)
+        .set_order_by_fields("NAME ASC")
+        .build()
+        .send_features(&Client::new(), &server.url)
+        .await
+        .expect("Failed to query features");
+
+    let url = urlencoding::decode(&server.requests()[0].url)
+        .unwrap()
+        .replace('+', " ");
+    assert!(url.contains("resultRecordCount=100"));
+    assert!(url.contains("orderByFields=NAME ASC"));
+    assert!(url.contains("geometryType=esriGeometryEnvelope"));
+    assert!(url.contains("spatialRel=esriSpatialRelContains"));
+    assert!(url.contains("inSR=4326"));
+    assert!(url.contains(r#""xmin":-121.0"#));
+    assert!(response.fields.len() == 2 && response.fields[0].alias == "Name");
+    assert!(
+        matches!(&response.fields[1].r#type, EsriType::Other(t) if t == "esriFieldTypeDateOnly")
+    );
+    assert!(response.features.len() == 1);
+}
+
+#[tokio::test]
+async fn query_rejects_malformed_fields() {
+    let server = MockServer::json(
+        r#"{"fields": [{"alias": "Name", "type": "esriFieldTypeString"}], "features": []}"#,
+    );
+
+    let result = FeatureLayerQueryBuilder::new()
+        .build()
+        .send_features(&Client::new(), &server.url)
+        .await;
+
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+async fn stream_feature_layer_pages() {
+    let server = MockServer::start(|request| {
+        let body = if request.url.contains("resultOffset=0") {
+            r#"{"features": [{"attributes": {"id": 1}}, {"attributes": {"id": 2}}],
+                "exceededTransferLimit": true}"#
+        } else {
+            r#"{"features": [{"attributes": {"id": 3}}]}"#
+        };
+        (200, body.to_string())
+    });
+
+    let features: Vec<_> = FeatureLayerQueryBuilder::new()
+        .set_record_count(2)
+        .set_page_delay(std::time::Duration::from_millis(1))
+        .stream(&Client::new(), &server.url)
+        .try_collect()
+        .await
+        .expect("Failed to stream features");
+
+    let requests = server.requests();
+    assert!(features.len() == 3);
+    assert!(features[2].attributes["id"] == 3);
+    assert!(requests.len() == 2);
+    assert!(requests[1].url.contains("resultOffset=2"));
+    assert!(requests[1].url.contains("resultRecordCount=2"));
+}
+
+#[tokio::test]
+async fn query_count_and_extent_only() {
+    let server = MockServer::start(|request| {
+        let body = if request.url.contains("returnCountOnly=true") {
+            r#"{"count": 42}"#
+        } else {
+            r#"{"extent": {"xmin": -121.0, "ymin": 34.0, "xmax": -119.0, "ymax": 36.0,
+                "spatialReference": {"wkid": 4326}}}"#
+        };
+        (200, body.to_string())
+    });
+    let client = Client::new();
+
+    let count = FeatureLayerQueryBuilder::new()
+        .set_where("DEPTH > 100")
+        .set_format(ResponseFormat::GeoJson)
+        .count(&client, &server.url)
+        .await
+        .expect("Failed to count features");
+    let extent = FeatureLayerQueryBuilder::new()
+        .set_where("DEPTH > 100")
+        .extent(&client, &server.url)
+        .await
+        .expect("Failed to fetch extent")
+        .expect("Missing extent");
+
+    assert!(count == 42);
+    assert!(server.requests()[0].url.contains("f=json"));
+    assert!(extent.xmin == -121.0 && extent.ymax == 36.0);
+    assert!(server.requests()[1].url.contains("returnExtentOnly=true"));
+}
+
+#[tokio::test]
+async fn query_grouped_statistics() {
+    let server = MockServer::json(
+        r#"{"features": [
+            {"attributes": {"STATE": "CA", "total_pop": 39000000, "avg_pop": 670000.5}},
+            {"attributes": {"STATE": "NV", "total_pop": 3100000, "avg_pop": 180000.0}}
+        ]}"#,
+    );
+
+    let rows = FeatureLayerQueryBuilder::new()
+        .add_statistic(StatisticType::Sum, "POP", "total_pop")
+        .add_statistic(StatisticType::Avg, "POP", "avg_pop")
+        .group_by(vec!["STATE".into()])
+        .statistics(&Client::new(), &server.url)
+        .await
+        .expect("Failed to query statistics");
+
+    let url = urlencoding::decode(&server.requests()[0].url)
+        .unwrap()
+        .into_owned();
+    assert!(url.contains(
+        r#"outStatistics=[{"statisticType":"sum","onStatisticField":"POP","outStatisticFieldName":"total_pop"},"#
+    ));
+    assert!(url.contains("groupByFieldsForStatistics=STATE"));
+    assert!(rows.len() == 2);
+    assert!(rows[0]["STATE"] == "CA" && rows[0]["total_pop"] == 39000000);
+}
+
+#[tokio::test]
+async fn query_with_quantization() {
+    // configure
+    let server = MockServer::json(
+        r#"{"features": [{"attributes": {"id": 1}, "geometry": {"x": 10, "y": 20}}],
+            "transform": {"originPosition": "upperLeft", "scale": [2.0, 2.0],
+                          "translate": [-100.0, 50.0]}}"#,
+    );
+    let extent = Extent {
+        xmin: -100.0,
+        ymin: 0.0,
+        xmax: 0.0,
+        ymax: 50.0,
+        spatial_reference: None,
+    };
+
+    // test
+    let response = FeatureLayerQueryBuilder::new()
+        .set_return_geometry(true)
+        .quantization(extent, 2.0, QuantizationMode::View)
+        .build()
+        .send(&Client::new(), &server.url)
+        .await
+        .expect("Feature service query failed");
+    let json = parse_response::<EsriQueryResponse>(response)
+        .await
+        .expect("Failed to parse response");
+
+    let url = urlencoding::decode(&server.requests()[0].url)
+        .unwrap()
+        .replace('+', " ");
+    assert!(url.contains(r#""mode":"view","originPosition":"upperLeft","tolerance":2.0"#));
+    assert!(json.transform.unwrap().translate == vec![-100.0, 50.0]);
+}
+
+#[tokio::test]
+async fn apply_edits_as_json_body() {
+    // configure
+    let server =
+        MockServer::json(r#"{"addResults": [], "updateResults": [], "deleteResults": []}"#);
+    let adds = vec![serde_json::json!({"attributes": {"name": "Well 1"}})];
+
+    // test
+    ApplyEditsQuery::builder()
+        .set_adds(adds)
+        .set_body_format(BodyFormat::Json)
+        .build()
+        .send(&Client::new(), &server.url)
+        .await
+        .expect("Apply edits failed");
+
+    let request = &server.requests()[0];
+    let body: serde_json::Value = serde_json::from_str(&request.body).unwrap();
+    assert!(request.header("Content-Type") == Some("application/json"));
+    assert!(request.url == "/applyEdits?f=json");
+    assert!(body["adds"][0]["attributes"]["name"] == "Well 1");
+    assert!(body["rollbackOnFailure"] == true);
+}
+
+#[tokio::test]
+async fn apply_edits_reports_failed_features() {
+    // configure
+    let server = MockServer::json(
+        r#"{"addResults": [{"objectId": 7, "success": true}, {"success": false, "error": {"code": 1000, "description": "Invalid geometry"}}],
+            "updateResults": [], "deleteResults": [{"objectId": 3, "success": true}]}"#,
+    );
+    let feature: EsriFeature = serde_json::from_str(
+        r#"{"attributes": {"name": "Well 1"}, "geometry": {"x": 1.0, "y": 2.0}}"#,
+    )
+    .unwrap();
+
+    // test
+    let response = ApplyEditsQuery::builder()
+        .set_add_features(vec![feature.clone(), feature])
+        .set_delete_ids(vec![3])
+        .set_rollback_on_failure(false)
+        .build()
+        .send_edits(&Client::new(), &server.url)
+        .await
+        .expect("Apply edits failed");
+
+    let body = &server.requests()[0].body;
+    assert!(body.contains("rollbackOnFailure=false"));
+    assert!(body.contains("deletes=%5B3%5D"));
+    assert!(response.add_results[0].object_id == Some(7));
+    let failed: Vec<_> = response.failed().collect();
+    assert!(failed.len() == 1);
+    assert!(failed[0].error.as_ref().unwrap().description == "Invalid geometry");
+}
+
+#[cfg(feature = "pbf")]
+#[tokio::test]
+async fn query_as_pbf() {
+    use arcgis_api_rs::pbf::*;
+    use prost::Message;
+
+    // configure
+    let result = FeatureResult {
+        object_id_field_name: "OBJECTID".into(),
+        geometry_type: 2, // polyline
+        transform: Some(Transform {
+            quantize_origin_postion: 0,
+            scale: Some(Scale {
+                x_scale: 0.5,
+                y_scale: 0.5,
+                ..Default::default()
+            }),
+            translate: Some(Translate {
+                x_translate: -100.0,
+                y_translate: 50.0,
+                ..Default::default()
+            }),
+        }),
+        fields: vec![
+            Field {
+                name: "OBJECTID".into(),
+            },
+            Field {
+                name: "NAME".into(),
+            },
+        ],
+        features: vec![Feature {
+            attributes: vec![
+                AttributeValue {
+                    value_type: Some(ValueType::Sint64Value(7)),
+                },
+                AttributeValue {
+                    value_type: Some(ValueType::StringValue("Main St".into())),
+                },
+            ],
+            geometry: Some(Geometry {
+                lengths: vec![3],
+                coords: vec![10, 20, 2, 2, 2, -4],
+            }),
+        }],
+        ..Default::default()
+    };
+    let body = FeatureCollectionPBuffer {
+        version: "1".into(),
+        query_result: Some(QueryResult {
+            results: Some(Results::FeatureResult(result)),
+        }),
+    }
+    .encode_to_vec();
+    let server = MockServer::start(move |_| (200, body.clone()));
+
+    // test
+    let response = FeatureLayerQueryBuilder::new()
+        .as_pbf()
+        .build()
+        .send_features(&Client::new(), &server.url)
+        .await
+        .expect("Failed to decode pbf");
+
+    let feature = &response.features[0];
+    let path = &feature.geometry.as_ref().unwrap()["paths"][0];
+    assert!(server.requests()[0].url.contains("f=pbf"));
+    assert!(feature.attributes["NAME"] == "Main St");
+    assert!(feature.attributes["OBJECTID"] == 7);
+    assert!(path[0] == serde_json::json!([-95.0, 40.0]));
+    assert!(path[1] == serde_json::json!([-94.0, 39.0]));
+    assert!(path[2] == serde_json::json!([-93.0, 41.0]));
+}
+
+#[tokio::test]
+async fn apply_edits_splits_large_edit_sets() {
+    // configure
+    let server = MockServer::start(|request| {
+        let form: std::collections::HashMap<String, String> =
+            serde_urlencoded::from_str(&request.body).unwrap();
+        let results = |key: &str| {
+            let edits: Vec<serde_json::Value> = serde_json::from_str(&form[key]).unwrap();
+            edits
+                .iter()
+                .map(|_| serde_json::json!({"objectId": 1, "success": true}))
+                .collect::<Vec<_>>()
+        };
+        let body = serde_json::json!({
+            "addResults": results("adds"),
+            "updateResults": results("updates"),
+            "deleteResults": results("deletes"),
+        });
+        (200, body.to_string())
+    });
+    let adds = (0..5)
+        .map(|i| serde_json::json!({"attributes": {"id": i}}))
+        .collect();
+
+    // test
+    let response = ApplyEditsQuery::builder()
+        .set_adds(adds)
+        .set_delete_ids(vec![8, 9])
+        .set_max_edits_per_request(3)
+        .build()
+        .send_edits(&Client::new(), &server.url)
+        .await
+        .expect("Apply edits failed");
+
+    let requests = server.requests();
+    assert!(requests.len() == 3);
+    assert!(requests[1].body.contains("deletes=%5B8%5D"));
+    assert!(requests[2].body.contains("adds=%5B%5D"));
+    assert!(response.add_results.len() == 5);
+    assert!(response.delete_results.len() == 2);
+}
+
+#[tokio::test]
+async fn apply_edits_stops_after_failed_request() {
+    // configure
+    let server = MockServer::start(|request| {
+        if request.body.contains("%22id%22%3A0") {
+            (
+                200,
+                r#"{"addResults": [{"objectId": 1, "success": true}], "updateResults": [], "deleteResults": []}"#,
+            )
+        } else {
+            (
+                200,
+                r#"{"error": {"code": 400, "message": "Unable to complete operation.", "details": []}}"#,
+            )
+        }
+    });
+    let adds = (0..3)
+        .map(|i| serde_json::json!({"attributes": {"id": i}}))
+        .collect();
+
+    // test
+    let error = ApplyEditsQuery::builder()
+        .set_adds(adds)
+        .set_max_edits_per_request(1)
+        .build()
+        .send_edits(&Client::new(), &server.url)
+        .await
+        .expect_err("Second request should fail");
+
+    assert!(server.requests().len() == 2);
+    assert!(error.to_string().contains("request 2 of 3"));
+}
+
+#[tokio::test]
+async fn apply_edits_reports_rolled_back_request() {
+    // configure
+    let server = MockServer::start(|request| {
+        if request.body.contains("%22id%22%3A1") {
+            (
+                200,
+                r#"{"addResults": [{"success": false, "error": {"code": 1000, "description": "Invalid value"}}], "updateResults": [], "deleteResults": []}"#,
+            )
+        } else {
+            (
+                200,
+                r#"{"addResults": [{"objectId": 1, "success": true}], "updateResults": [], "deleteResults": []}"#,
+            )
+        }
+    });
+    let adds = (0..3)
+        .map(|i| serde_json::json!({"attributes": {"id": i}}))
+        .collect();
+
+    // test
+    let error = ApplyEditsQuery::builder()
+        .set_adds(adds)
+        .set_max_edits_per_request(1)
+        .build()
+        .send_edits(&Client::new(), &server.url)
+        .await
+        .expect_err("Rolled back request should fail");
+
+    let rolled_back = error.downcast_ref::<ApplyEditsRolledBack>().unwrap();
+    assert!(server.requests().len() == 2);
+    assert!(rolled_back.request == 2 && rolled_back.requests == 3);
+    assert!(rolled_back.unsent == 1);
+    assert!(rolled_back.results.add_results.len() == 2);
+    assert!(error.to_string().contains("edits not sent: 1"));
+}
@@ -0,0 +1,58 @@
+mod common;
+
+use arcgis_api_rs::{
+    feature_layer_query::GeometryType,
+    geometry_service::{GeometryService, LinearUnit},
+};
+use common::MockServer;
+use reqwest::Client;
+use serde_json::json;
+
+#[tokio::test]
+async fn project_points() {
+    let server = MockServer::json(r#"{"geometries": [{"x": 111319.49, "y": 111325.14}]}"#);
+    let service = GeometryService::new(Client::new(), &server.url);
+
+    let projected = service
+        .project(
+            GeometryType::Point,
+            &[json!({"x": 1.0, "y": 1.0})],
+            4326,
+            3857,
+        )
+        .await
+        .expect("Failed to project");
+
+    let request = &server.requests()[0];
+    let params: Vec<(String, String)> = serde_urlencoded::from_str(&request.body).unwrap();
+    assert!(request.path() == "/project");
+    assert!(
+        params[0].1 == r#"{"geometries":[{"x":1.0,"y":1.0}],"geometryType":"esriGeometryPoint"}"#
+    );
+    assert!(params[2] == ("outSR".to_string(), "3857".to_string()));
+    assert!(projected[0]["x"] == 111319.49);
+}
+
+#[tokio::test]
+async fn buffer_points() {
+    let server = MockServer::json(r#"{"geometries": [{"rings": [[[0, 0], [1, 0], [0, 0]]]}]}"#);
+    let service = GeometryService::new(Client::new(), &server.url);
+
+    let buffers = service
+        .buffer(
+            GeometryType::Point,
+            &[json!({"x": 1.0, "y": 1.0})],
+            4326,
+            &[100.0, 250.5],
+            LinearUnit::Meter,
+            4326,
+        )
+        .await
+        .expect("Failed to buffer");
+
+    let request = &server.requests()[0];
+    assert!(request.path() == "/buffer");
+    assert!(request.body.contains("distances=100%2C250.5"));
+    assert!(request.body.contains("unit=9001"));
+    assert!(buffers[0].get("rings").is_some());
+}
@@ -0,0 +1,32 @@
+mod common;
+
+use arcgis_api_rs::group::create::Group;
+use common::MockServer;
+use reqwest::Client;
+
+fn group() -> Group {
+    serde_json::from_str(
+        r#"{"id": "g1", "title": "Field Crew", "isInvitationOnly": false, "isViewOnly": false,
+            "isReadOnly": false, "isFav": false, "autoJoin": false, "leavingDisallowed": false,
+            "hiddenMembers": false, "membershipAccess": "org", "access": "private",
+            "owner": "jdoe", "tags": [], "typeKeywords": [], "capabilities": [], "created": 0,
+            "modified": 0, "notificationsEnabled": false, "protected": false}"#,
+    )
+    .unwrap()
+}
+
+#[tokio::test]
+async fn list_group_users() {
+    let server =
+        MockServer::json(r#"{"owner": "jdoe", "admins": ["jdoe", "asmith"], "users": ["bjones"]}"#);
+
+    let users = group()
+        .users(&server.url, &Client::new())
+        .await
+        .expect("Failed to list group users");
+
+    assert!(server.requests()[0].path() == "/community/groups/g1/users");
+    assert!(users.owner == "jdoe");
+    assert!(users.admins == ["jdoe", "asmith"]);
+    assert!(users.users == ["bjones"]);
+}
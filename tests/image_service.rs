@@ -0,0 +1,68 @@
+mod common;
+
+use arcgis_api_rs::{
+    feature_layer_query::Extent, image_service::ImageService, publish_item::SpatialReference,
+};
+use common::MockServer;
+use reqwest::Client;
+
+fn image_server() -> MockServer {
+    MockServer::start(|request| {
+        let body: Vec<u8> = if request.path().ends_with("/exportImage") {
+            vec![0x89, b'P', b'N', b'G']
+        } else if request.path().ends_with("/identify") {
+            br#"{"objectId": 0, "name": "Pixel", "value": "42", "location": {"x": 1, "y": 2}}"#
+                .to_vec()
+        } else {
+            br#"{"name": "Elevation", "currentVersion": 11.1, "bandCount": 1, "pixelType": "F32",
+                "pixelSizeX": 10, "pixelSizeY": 10}"#
+                .to_vec()
+        };
+        (200, body)
+    })
+}
+
+#[tokio::test]
+async fn export_image_returns_bytes() {
+    let server = image_server();
+    let url = format!("{}/ImageServer", server.url);
+    let service = ImageService::new(&Client::new(), &url).await.unwrap();
+    let bbox = Extent {
+        xmin: 0.0,
+        ymin: 0.0,
+        xmax: 10.0,
+        ymax: 5.0,
+        spatial_reference: Some(SpatialReference {
+            wkid: 3857,
+            latest_wkid: None,
+        }),
+    };
+
+    let image = service
+        .export_image(&bbox, (400, 200), "png")
+        .await
+        .expect("Failed to export image");
+
+    let request = &server.requests()[1];
+    assert!(service.info().band_count == Some(1));
+    assert!(request.path() == "/ImageServer/exportImage");
+    assert!(request.url.contains("bbox=0%2C0%2C10%2C5"));
+    assert!(request.url.contains("size=400%2C200"));
+    assert!(request.url.contains("bboxSR=3857"));
+    assert!(image == vec![0x89, b'P', b'N', b'G']);
+}
+
+#[tokio::test]
+async fn identify_samples_pixel() {
+    let server = image_server();
+    let url = format!("{}/ImageServer", server.url);
+    let service = ImageService::new(&Client::new(), &url).await.unwrap();
+
+    let pixel = service
+        .identify(1.0, 2.0, 4326)
+        .await
+        .expect("Failed to identify");
+
+    assert!(server.requests()[1].path() == "/ImageServer/identify");
+    assert!(pixel.value == "42");
+}
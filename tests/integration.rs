@@ -3,11 +3,8 @@ use std::fs;
 use arcgis_api_rs::{
     auth::AuthType,
     config::get_config,
-    feature_layer::FeatureLayer,
-    feature_layer_query::{EsriCountResponse, EsriQueryResponse, FeatureLayerQueryBuilder},
     group::create::CreateGroupQuery,
     oauth::{get_token, TokenStore},
-    parser::parse_response,
 };
 use reqwest::Client;
 use secrecy::ExposeSecret;
@@ -62,7 +59,7 @@ async fn create_group() {
 
     let group = create_result.group;
 
-    assert!(&group.title == &title);
+    assert!(group.title == title);
 
     let delete_result = group
         .delete(&config.portal_root, &client, &group.id)
@@ -1,20 +1,28 @@
+mod common;
+
 use std::sync::Arc;
 
 use arcgis_api_rs::{
-    add_item::{points_json_to_csv, AddItemQuery},
+    add_item::{points_json_to_csv, AddItemQuery, JobStatus},
     auth::{ArcGISProvider, ArcGISTokenManager, AuthType},
     config::{get_config, Settings},
-    item::{create_web_map, Item, PointWithData},
+    delete_items::DeleteItemsQuery,
+    group::create::AccessLevel,
+    item::{create_web_map, Item, ItemData, PointWithData},
+    parser::EsriErrorResponse,
     publish_item::PublishItemQuery,
+    update_item::UpdateItemQuery,
+    user_content::UserContent,
 };
 use std::collections::HashMap;
 
+use common::{item_json, item_json_with, MockServer};
 use once_cell::sync::Lazy;
 use secrecy::ExposeSecret;
 
 static TEST_CONFIG: Lazy<Settings> = Lazy::new(|| get_config().expect("Failed to create config"));
 
-static HTTP_CLIENT: Lazy<reqwest::Client> = Lazy::new(|| reqwest::Client::new());
+static HTTP_CLIENT: Lazy<reqwest::Client> = Lazy::new(reqwest::Client::new);
 
 static ARCGIS_TOKEN_MANAGER: Lazy<Arc<ArcGISTokenManager>> = Lazy::new(|| {
     let provider = ArcGISProvider {
@@ -77,7 +85,7 @@ async fn test_update_item() {
     assert!(item.data.tags == test_tags);
 
     let test_tags2: Vec<String> =
-        vec![vec!["dev2".into(), "test2".into()], item.data.tags.clone()].concat();
+        [vec!["dev2".into(), "test2".into()], item.data.tags.clone()].concat();
     item.update(item.update_builder().tags(test_tags2.clone()))
         .await
         .expect("Failed to update item");
@@ -95,7 +103,7 @@ async fn test_add_item() {
     let test_user_name = config.arcgis_username.expose_secret().to_string();
 
     // test points in lat/long
-    let test_json = serde_json::json!({"points": [[-109.39187790158928,41.419509792907284],[-101.55640533404183,41.339988469773225],[-101.78703063454039,31.004095664783694],[-109.35624516142607,31.036737940262469]]});
+    let test_json = serde_json::json!({"points": [[-109.39187790158928,41.419509792907284],[-101.55640533404183,41.339988469773225],[-101.78703063454039,31.004095664783694],[-109.35624516142607,31.03673794026247]]});
 
     let test_csv = points_json_to_csv(&test_json.to_string()).unwrap();
 
@@ -139,7 +147,7 @@ async fn test_publish_item() {
 
     println!("{:?}", response);
 
-    assert!(response.services.len() > 0);
+    assert!(!response.services.is_empty());
 }
 
 #[tokio::test]
@@ -245,11 +253,11 @@ async fn test_create_web_map_from_input() {
     let test_user_name = config.arcgis_username.expose_secret().to_string();
     let uuid = uuid::Uuid::new_v4().to_string().replace("-", "");
     let title = format!("Test_Map_{}", uuid);
-    
+
     // Create points with associated data
     let input_points = vec![
         PointWithData {
-            coordinates: [-109.39187790158928, 41.419509792907284],
+            coordinates: vec![-109.39187790158928, 41.419509792907284],
             data: HashMap::from([
                 ("Name".to_string(), "Point 1".to_string()),
                 ("Description".to_string(), "Northwest corner".to_string()),
@@ -257,7 +265,7 @@ async fn test_create_web_map_from_input() {
             ]),
         },
         PointWithData {
-            coordinates: [-101.55640533404183, 41.339988469773225],
+            coordinates: vec![-101.55640533404183, 41.339988469773225],
             data: HashMap::from([
                 ("Name".to_string(), "Point 2".to_string()),
                 ("Description".to_string(), "Northeast corner".to_string()),
@@ -265,7 +273,7 @@ async fn test_create_web_map_from_input() {
             ]),
         },
         PointWithData {
-            coordinates: [-101.78703063454039, 31.004095664783694],
+            coordinates: vec![-101.78703063454039, 31.004095664783694],
             data: HashMap::from([
                 ("Name".to_string(), "Point 3".to_string()),
                 ("Description".to_string(), "Southeast corner".to_string()),
@@ -273,7 +281,7 @@ async fn test_create_web_map_from_input() {
             ]),
         },
         PointWithData {
-            coordinates: [-109.35624516142607, 31.036737940262469],
+            coordinates: vec![-109.35624516142607, 31.03673794026247],
             data: HashMap::from([
                 ("Name".to_string(), "Point 4".to_string()),
                 ("Description".to_string(), "Southwest corner".to_string()),
@@ -281,7 +289,7 @@ async fn test_create_web_map_from_input() {
             ]),
         },
     ];
-    
+
     let map_url = create_web_map(
         &config.arcgis_api_root,
         &config.portal_apps_root,
@@ -296,3 +304,670 @@ async fn test_create_web_map_from_input() {
     println!("{}", map_url);
     assert!(map_url.contains("webmap="));
 }
+
+fn category_server() -> MockServer {
+    let item = item_json_with(
+        "abc",
+        "CSV",
+        serde_json::json!({"categories": ["/Categories/Water"]}),
+    );
+    MockServer::start(move |request| {
+        let body = if request.method == "GET" {
+            item.as_str()
+        } else {
+            r#"{"results": [{"itemId": "abc", "success": true}]}"#
+        };
+        (200, body.to_string())
+    })
+}
+
+#[tokio::test]
+async fn test_add_categories() {
+    let server = category_server();
+    let mut item = Item::new(&server.url, &reqwest::Client::new(), "abc")
+        .await
+        .expect("Failed to fetch item");
+
+    item.add_categories(vec!["/Categories/Water/Wells".into()])
+        .await
+        .expect("Failed to add categories");
+
+    let update = &server.requests()[1];
+    let items: Vec<(String, String)> = serde_urlencoded::from_str(&update.body).unwrap();
+    assert!(update.path() == "/content/updateItems");
+    assert!(
+        items[0].1 == r#"[{"abc":{"categories":["/Categories/Water","/Categories/Water/Wells"]}}]"#
+    );
+}
+
+#[tokio::test]
+async fn test_categories_require_full_path() {
+    let server = category_server();
+    let mut item = Item::new(&server.url, &reqwest::Client::new(), "abc")
+        .await
+        .expect("Failed to fetch item");
+
+    assert!(item.set_categories(vec!["Water".into()]).await.is_err());
+    assert!(server.requests().len() == 1);
+}
+
+#[tokio::test]
+async fn test_update_item_and_fetch() {
+    let item = item_json_with(
+        "abc",
+        "CSV",
+        serde_json::json!({"title": "Renamed", "modified": 1}),
+    );
+    let server = MockServer::start(move |request| {
+        let body = if request.path().ends_with("/update") {
+            r#"{"success": true, "id": "abc"}"#
+        } else {
+            item.as_str()
+        };
+        (200, body.to_string())
+    });
+
+    let item = UpdateItemQuery::builder(&server.url, "jdoe", "abc")
+        .title("Renamed")
+        .build()
+        .send_and_fetch(&reqwest::Client::new())
+        .await
+        .expect("Failed to update item");
+
+    assert!(item.data.title == "Renamed");
+    assert!(server.requests()[1].path() == "/content/items/abc");
+}
+
+#[tokio::test]
+async fn test_delete_item() {
+    let item = item_json("abc", "CSV");
+    let server = MockServer::start(move |request| {
+        let body = if request.path().ends_with("/delete") {
+            r#"{"success": true, "itemId": "abc", "folder": null}"#
+        } else {
+            item.as_str()
+        };
+        (200, body.to_string())
+    });
+    let item = Item::new(&server.url, &reqwest::Client::new(), "abc")
+        .await
+        .expect("Failed to fetch item");
+
+    let response = item.delete().await.expect("Failed to delete item");
+
+    assert!(server.requests()[1].path() == "/content/users/jdoe/items/abc/delete");
+    assert!(response.success && response.item_id == "abc");
+    assert!(response.folder.is_none());
+}
+
+#[tokio::test]
+async fn test_share_item() {
+    let item = item_json("abc", "Web Map");
+    let server = MockServer::start(move |request| {
+        let body = if request.path().ends_with("/shareItems") {
+            r#"{"results": [{"itemId": "abc", "success": true, "notSharedWith": ["g2"]}]}"#
+        } else {
+            item.as_str()
+        };
+        (200, body.to_string())
+    });
+    let item = Item::new(&server.url, &reqwest::Client::new(), "abc")
+        .await
+        .expect("Failed to fetch item");
+
+    let response = item
+        .share(false, true, vec!["g1".into(), "g2".into()])
+        .await
+        .expect("Failed to share item");
+
+    let request = &server.requests()[1];
+    assert!(request.path() == "/content/users/jdoe/shareItems");
+    assert!(request.body.contains("items=abc"));
+    assert!(request.body.contains("org=true"));
+    assert!(response.shared_with == vec!["g1"]);
+    assert!(response.not_shared_with == vec!["g2"]);
+}
+
+#[tokio::test]
+async fn test_folders_and_move_item() {
+    let item = item_json("abc", "CSV");
+    let server = MockServer::start(move |request| {
+        let path = request.path();
+        let body = if path.ends_with("/createFolder") {
+            r#"{"success": true, "folder": {"username": "jdoe", "id": "f1", "title": "Wells"}}"#
+        } else if path.ends_with("/moveItems") {
+            r#"{"results": [{"itemId": "abc", "success": true}]}"#
+        } else if path == "/content/users/jdoe" {
+            r#"{"username": "jdoe", "items": [], "folders": [{"id": "f1", "title": "Wells", "created": 0}]}"#
+        } else {
+            item.as_str()
+        };
+        (200, body.to_string())
+    });
+    let client = reqwest::Client::new();
+    let content = UserContent::new(&server.url, &client, "jdoe");
+
+    let folder = content
+        .create_folder("Wells")
+        .await
+        .expect("Failed to create folder");
+    let folders = content
+        .list_folders()
+        .await
+        .expect("Failed to list folders");
+    let item = Item::new(&server.url, &client, "abc")
+        .await
+        .expect("Failed to fetch item");
+    item.move_to_folder(&folder.id)
+        .await
+        .expect("Failed to move item");
+
+    let requests = server.requests();
+    assert!(requests[0].body == "title=Wells&f=json");
+    assert!(folders[0].id == "f1");
+    assert!(requests[3].path() == "/content/users/jdoe/moveItems");
+    assert!(requests[3].body == "items=abc&folder=f1&f=json");
+}
+
+#[tokio::test]
+async fn test_protect_item() {
+    let item = item_json_with("abc", "CSV", serde_json::json!({"protected": false}));
+    let server = MockServer::start(move |request| {
+        let body = if request.method == "POST" {
+            r#"{"success": true}"#
+        } else {
+            item.as_str()
+        };
+        (200, body.to_string())
+    });
+    let item = Item::new(&server.url, &reqwest::Client::new(), "abc")
+        .await
+        .expect("Failed to fetch item");
+
+    assert!(item.protect().await.expect("Failed to protect").success);
+    assert!(item.unprotect().await.expect("Failed to unprotect").success);
+
+    let requests = server.requests();
+    assert!(!item.data.protected);
+    assert!(requests[1].path() == "/content/users/jdoe/items/abc/protect");
+    assert!(requests[2].path() == "/content/users/jdoe/items/abc/unprotect");
+}
+
+#[tokio::test]
+async fn test_reassign_item_requires_admin() {
+    let item = item_json("abc", "CSV");
+    let server = MockServer::start(move |request| {
+        let body = if request.method == "POST" {
+            r#"{"error": {"code": 403, "message": "You do not have permissions to access this resource or perform this operation.", "details": []}}"#
+        } else {
+            item.as_str()
+        };
+        (200, body.to_string())
+    });
+    let item = Item::new(&server.url, &reqwest::Client::new(), "abc")
+        .await
+        .expect("Failed to fetch item");
+
+    let error = item
+        .reassign("asmith", Some("Archive"))
+        .await
+        .expect_err("Reassign should fail without admin");
+
+    let request = &server.requests()[1];
+    assert!(request.path() == "/content/users/jdoe/items/abc/reassign");
+    assert!(request.body == "targetUsername=asmith&f=json&targetFoldername=Archive");
+    assert!(
+        error
+            .downcast_ref::<EsriErrorResponse>()
+            .unwrap()
+            .error
+            .code
+            == 403
+    );
+}
+
+#[tokio::test]
+async fn test_set_thumbnail() {
+    let item = item_json_with(
+        "abc",
+        "CSV",
+        serde_json::json!({"thumbnail": "thumbnail/preview.png"}),
+    );
+    let server = MockServer::start(move |request| {
+        let body = if request.path().ends_with("/update") {
+            r#"{"success": true, "id": "abc"}"#
+        } else {
+            item.as_str()
+        };
+        (200, body.to_string())
+    });
+    let mut item = Item::new(&server.url, &reqwest::Client::new(), "abc")
+        .await
+        .expect("Failed to fetch item");
+
+    item.set_thumbnail(b"png".to_vec(), "preview.png")
+        .await
+        .expect("Failed to set thumbnail");
+    item.set_thumbnail_from_url("https://example.com/preview.png")
+        .await
+        .expect("Failed to set thumbnail url");
+
+    let requests = server.requests();
+    assert!(requests[1].path() == "/content/users/jdoe/items/abc/update");
+    assert!(requests[1]
+        .body
+        .contains(r#"name="thumbnail"; filename="preview.png""#));
+    assert!(requests[3]
+        .body
+        .contains("thumbnailUrl=https%3A%2F%2Fexample.com%2Fpreview.png"));
+    assert!(item.data.thumbnail.as_deref() == Some("thumbnail/preview.png"));
+}
+
+#[test]
+fn test_job_status_spellings() {
+    let statuses: Vec<JobStatus> = serde_json::from_str(
+        r#"["processing", "completed", "esriJobSucceeded", "esriJobTimedOut", "failed", "queued"]"#,
+    )
+    .unwrap();
+
+    assert!(statuses[0] == JobStatus::Executing && !statuses[0].is_finished());
+    assert!(statuses[1] == JobStatus::Completed && statuses[2] == JobStatus::Completed);
+    assert!(statuses[3] == JobStatus::TimedOut && statuses[3].is_finished());
+    assert!(statuses[4] == JobStatus::Failed);
+    assert!(statuses[5] == JobStatus::Other("queued".into()));
+    assert!("succeeded".parse::<JobStatus>().unwrap() == JobStatus::Other("succeeded".into()));
+
+    let cancelling = "esriJobCancelling".parse::<JobStatus>().unwrap();
+    assert!(cancelling == JobStatus::Cancelling && !cancelling.is_finished());
+    assert!("esriJobCancelled"
+        .parse::<JobStatus>()
+        .unwrap()
+        .is_finished());
+}
+
+#[tokio::test]
+async fn test_wait_for_publish() {
+    let polls = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let counter = polls.clone();
+    let item = item_json("svc", "Feature Service");
+    let server = MockServer::start(move |request| {
+        let body = if request.path().ends_with("/status") {
+            match counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst) {
+                0 => r#"{"status": "processing", "itemId": "svc"}"#,
+                _ => r#"{"status": "completed", "itemId": "svc"}"#,
+            }
+        } else {
+            item.as_str()
+        };
+        (200, body.to_string())
+    });
+    let item = Item::new(&server.url, &reqwest::Client::new(), "svc")
+        .await
+        .expect("Failed to fetch item");
+
+    item.wait_for_publish(
+        "job1",
+        std::time::Duration::from_millis(10),
+        std::time::Duration::from_secs(5),
+    )
+    .await
+    .expect("Publish did not complete");
+
+    let request = &server.requests()[1];
+    assert!(request.path() == "/content/users/jdoe/items/svc/status");
+    assert!(request.url.contains("jobId=job1&jobType=publish"));
+    assert!(polls.load(std::sync::atomic::Ordering::SeqCst) == 2);
+}
+
+/// An item whose publish job status requests are answered with `statuses`
+/// in order, repeating the last one.
+async fn publishing_item(statuses: Vec<&'static str>) -> (MockServer, Item) {
+    let polls = std::sync::atomic::AtomicUsize::new(0);
+    let item = item_json("svc", "Feature Service");
+    let server = MockServer::start(move |request| {
+        let body = if request.path().ends_with("/status") {
+            let n = polls.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            statuses[n.min(statuses.len() - 1)]
+        } else {
+            item.as_str()
+        };
+        (200, body.to_string())
+    });
+    let item = Item::new(&server.url, &reqwest::Client::new(), "svc")
+        .await
+        .expect("Failed to fetch item");
+    (server, item)
+}
+
+#[tokio::test]
+async fn test_wait_for_publish_outcomes() {
+    let poll = std::time::Duration::from_millis(10);
+    let timeout = std::time::Duration::from_millis(200);
+    let (_server, transient) = publishing_item(vec![
+        r#"{"error": {"code": 502, "message": "Bad Gateway"}}"#,
+        r#"{"status": "completed", "itemId": "svc"}"#,
+    ])
+    .await;
+    let (_server, failed) = publishing_item(vec![
+        r#"{"status": "processing", "itemId": "svc"}"#,
+        r#"{"status": "failed", "itemId": "svc", "statusMessage": "Invalid CSV"}"#,
+    ])
+    .await;
+    let (_server, stuck) = publishing_item(vec![
+        r#"{"status": "processing", "itemId": "svc"}"#,
+        r#"{"error": {"code": 502, "message": "Bad Gateway"}}"#,
+    ])
+    .await;
+
+    transient
+        .wait_for_publish("job1", poll, timeout)
+        .await
+        .expect("A transient error ended the wait");
+    let failed = failed
+        .wait_for_publish("job1", poll, timeout)
+        .await
+        .unwrap_err();
+    let stuck = stuck
+        .wait_for_publish("job1", poll, timeout)
+        .await
+        .unwrap_err();
+
+    assert!(failed.to_string() == "Publishing svc failed: Invalid CSV");
+    assert!(stuck.to_string().contains("did not finish"));
+    assert!(format!("{:#}", stuck).contains("Bad Gateway"));
+}
+
+#[tokio::test]
+async fn test_wait_for_publish_stops_on_permanent_errors() {
+    let poll = std::time::Duration::from_millis(10);
+    let timeout = std::time::Duration::from_secs(5);
+
+    for error in [
+        r#"{"error": {"code": 498, "message": "Invalid token."}}"#,
+        r#"{"error": {"code": 403, "message": "You do not have permissions to access this resource or perform this operation."}}"#,
+        r#"{"error": {"code": 400, "message": "Job not found."}}"#,
+    ] {
+        let (server, item) = publishing_item(vec![error]).await;
+
+        let result = item.wait_for_publish("job1", poll, timeout).await;
+
+        let polls = server
+            .requests()
+            .iter()
+            .filter(|r| r.path().ends_with("/status"))
+            .count();
+        assert!(result.is_err());
+        assert!(polls == 1);
+    }
+}
+
+#[tokio::test]
+async fn test_delete_folder_recursive() {
+    let server = MockServer::start(|request| {
+        let body = if request.path().ends_with("/deleteItems") {
+            r#"{"results": [{"itemId": "a", "success": true}, {"itemId": "b", "success": true}]}"#
+        } else if request.path().ends_with("/delete") {
+            r#"{"success": true, "folder": {"id": "f1", "title": "Tmp"}}"#
+        } else if request.url.contains("start=1&") {
+            r#"{"items": [{"id": "a"}], "nextStart": 2}"#
+        } else {
+            r#"{"items": [{"id": "b"}], "nextStart": -1}"#
+        };
+        (200, body.to_string())
+    });
+    let content = UserContent::new(&server.url, reqwest::Client::new(), "jdoe");
+
+    let cleanup = content
+        .delete_folder_recursive("f1")
+        .await
+        .expect("Failed to delete folder");
+
+    let requests = server.requests();
+    assert!(requests[2].path() == "/content/users/jdoe/deleteItems");
+    assert!(requests[2].body.contains("items=a%2Cb"));
+    assert!(requests[3].path() == "/content/users/jdoe/f1/delete");
+    assert!(cleanup.deleted_items == vec!["a", "b"] && cleanup.folder_deleted);
+}
+
+#[tokio::test]
+async fn test_delete_folder_recursive_keeps_folder_with_protected_items() {
+    let server = MockServer::start(|request| {
+        let body = if request.path().ends_with("/deleteItems") {
+            r#"{"results": [{"itemId": "a", "success": false,
+                "error": {"code": 400, "message": "Unable to delete item. Delete protection is turned on."}}]}"#
+        } else {
+            r#"{"items": [{"id": "a"}], "nextStart": -1}"#
+        };
+        (200, body.to_string())
+    });
+    let content = UserContent::new(&server.url, reqwest::Client::new(), "jdoe");
+
+    let cleanup = content
+        .delete_folder_recursive("f1")
+        .await
+        .expect("Failed to clean up folder");
+
+    assert!(!cleanup.folder_deleted);
+    assert!(cleanup.failed_items[0].item_id == "a");
+    assert!(server.requests().len() == 2);
+}
+
+#[tokio::test]
+async fn test_update_item_metadata_file() {
+    let server = MockServer::json(r#"{"success": true, "id": "abc"}"#);
+    let path = std::env::temp_dir().join("iso19139-test.xml");
+    std::fs::write(&path, "<metadata><title>Wells</title></metadata>").unwrap();
+
+    UpdateItemQuery::builder(&server.url, "jdoe", "abc")
+        .description("Wells")
+        .metadata_file(&path)
+        .build()
+        .send(&reqwest::Client::new())
+        .await
+        .expect("Failed to update item");
+
+    let request = &server.requests()[0];
+    assert!(request
+        .header("Content-Type")
+        .unwrap()
+        .starts_with("multipart/form-data"));
+    assert!(request
+        .body
+        .contains(r#"name="metadata"; filename="iso19139-test.xml""#));
+    assert!(request
+        .body
+        .contains("<metadata><title>Wells</title></metadata>"));
+    assert!(request.body.contains(r#"name="description""#));
+}
+
+#[tokio::test]
+async fn test_update_item_inline_metadata() {
+    let server = MockServer::json(r#"{"success": true, "id": "abc"}"#);
+
+    UpdateItemQuery::builder(&server.url, "jdoe", "abc")
+        .metadata("<metadata><title>Wells</title></metadata>")
+        .build()
+        .send(&reqwest::Client::new())
+        .await
+        .expect("Failed to update item");
+
+    let request = &server.requests()[0];
+    assert!(request.header("Content-Type") == Some("application/x-www-form-urlencoded"));
+    assert!(request
+        .body
+        .contains("metadata=%3Cmetadata%3E%3Ctitle%3EWells%3C%2Ftitle%3E%3C%2Fmetadata%3E"));
+}
+
+#[cfg(feature = "xml-validation")]
+#[tokio::test]
+async fn test_update_item_rejects_malformed_metadata() {
+    let server = MockServer::json(r#"{"success": true, "id": "abc"}"#);
+
+    let result = UpdateItemQuery::builder(&server.url, "jdoe", "abc")
+        .metadata("<metadata><title>Wells</metadata>")
+        .build()
+        .send(&reqwest::Client::new())
+        .await;
+
+    assert!(result.is_err());
+    assert!(server.requests().is_empty());
+}
+
+#[tokio::test]
+async fn test_item_extent_bbox() {
+    let server = MockServer::json(item_json_with(
+        "abc",
+        "CSV",
+        serde_json::json!({
+            "extent": [[-120.5, 35.0], [-119.5, 36.0]],
+            "spatialReference": "WGS_1984_Web_Mercator_Auxiliary_Sphere"
+        }),
+    ));
+
+    let item = Item::new(&server.url, &reqwest::Client::new(), "abc")
+        .await
+        .expect("Failed to fetch item");
+
+    let extent = item.extent_bbox().expect("Missing extent");
+    assert!(extent.xmin == -120.5 && extent.ymax == 36.0);
+    assert!(extent.spatial_reference.unwrap().wkid == 4326);
+    assert!(
+        item.data.spatial_reference.as_deref() == Some("WGS_1984_Web_Mercator_Auxiliary_Sphere")
+    );
+}
+
+#[test]
+fn test_item_full_metadata() {
+    let item: ItemData = serde_json::from_str(&item_json_with(
+        "abc",
+        "CSV",
+        serde_json::json!({
+            "access": "org", "size": 20480, "ownerFolder": "f1", "licenseInfo": "CC-BY",
+            "culture": "en-us", "properties": {"source": "survey"}, "protected": true,
+            "screenshots": [], "listed": false, "contentStatus": "org_authoritative",
+            "lastViewed": 1700000000000u64
+        }),
+    ))
+    .unwrap();
+
+    assert!(item.access == Some(AccessLevel::Org));
+    assert!(item.size == Some(20480) && item.protected);
+    assert!(item.owner_folder.as_deref() == Some("f1"));
+    assert!(item.properties.unwrap()["source"] == "survey");
+    assert!(item.content_status.as_deref() == Some("org_authoritative"));
+    assert!(item.culture.as_deref() == Some("en-us"));
+}
+
+#[test]
+fn test_item_access_level() {
+    let access: Vec<AccessLevel> =
+        serde_json::from_str(r#"["public", "shared", "private", "partner"]"#).unwrap();
+
+    assert!(access[0] == AccessLevel::Public);
+    assert!(access[1] == AccessLevel::Shared);
+    assert!(access[2] == AccessLevel::Private);
+    assert!(access[3] == AccessLevel::Other("partner".into()));
+    assert!(access[3].as_str() == "partner");
+}
+
+#[test]
+fn test_item_popularity_fields() {
+    let item: ItemData = serde_json::from_str(&item_json_with(
+        "abc",
+        "CSV",
+        serde_json::json!({"numViews": 1520, "numRatings": 4, "avgRating": 3.75, "numComments": 2}),
+    ))
+    .unwrap();
+    let unrated: ItemData = serde_json::from_str(&item_json("def", "CSV")).unwrap();
+
+    assert!(item.num_views == 1520 && item.num_ratings == 4 && item.num_comments == 2);
+    assert!(item.avg_rating == 3.75);
+    assert!(unrated.num_views == 0 && unrated.avg_rating == 0.0);
+}
+
+#[test]
+fn test_item_kind_helpers() {
+    let hosted = ItemData {
+        r#type: "Feature Service".into(),
+        type_keywords: vec!["Data".into(), "Service".into(), "Hosted Service".into()],
+        ..Default::default()
+    };
+    assert!(hosted.is_feature_service() && hosted.is_hosted() && !hosted.is_web_map());
+
+    let registered = ItemData {
+        r#type: "Feature Service".into(),
+        type_keywords: vec!["Data".into(), "Service".into()],
+        ..Default::default()
+    };
+    assert!(registered.is_feature_service() && !registered.is_hosted());
+
+    let web_map = ItemData {
+        r#type: "Web Map".into(),
+        ..Default::default()
+    };
+    assert!(web_map.is_web_map() && !web_map.is_feature_service());
+}
+
+#[tokio::test]
+async fn test_clone_item_to_another_portal() {
+    let item = item_json_with(
+        "abc",
+        "CSV",
+        serde_json::json!({
+            "typeKeywords": ["CSV"], "tags": ["water", "wells"], "snippet": "Well locations"
+        }),
+    );
+    let source = MockServer::start(move |request| {
+        let body = if request.path().ends_with("/data") {
+            "Longitude,Latitude\n-120.5,35.0\n"
+        } else {
+            item.as_str()
+        };
+        (200, body.to_string())
+    });
+    let target = MockServer::json(r#"{"success": true, "id": "xyz", "folder": ""}"#);
+    let client = reqwest::Client::new();
+
+    let item = Item::new(&source.url, &client, "abc")
+        .await
+        .expect("Failed to fetch item");
+    let id = item
+        .clone_to(&target.url, &client, "jsmith")
+        .await
+        .expect("Failed to clone item");
+
+    let add = &target.requests()[0];
+    assert!(id == "xyz");
+    assert!(add.path() == "/content/users/jsmith/addItem");
+    assert!(add.body.contains("Longitude,Latitude\n-120.5,35.0"));
+    assert!(add.body.contains("water,wells"));
+    assert!(add.body.contains("Well locations"));
+}
+
+#[tokio::test]
+async fn test_delete_items_reports_each_item() {
+    let server = MockServer::json(
+        r#"{"results": [
+            {"itemId": "abc", "success": true},
+            {"itemId": "def", "success": false,
+             "error": {"code": 500, "message": "Unable to delete item. Delete protection is turned on."}}
+        ]}"#,
+    );
+
+    let response = DeleteItemsQuery::builder(&server.url, "jdoe", vec!["abc".into(), "def".into()])
+        .build()
+        .send(&reqwest::Client::new())
+        .await
+        .expect("Failed to delete items");
+
+    let failed: Vec<_> = response.failed().collect();
+    assert!(server.requests()[0].body.contains("items=abc%2Cdef"));
+    assert!(response.results.len() == 2);
+    assert!(failed.len() == 1 && failed[0].item_id == "def");
+    assert!(failed[0]
+        .error
+        .as_ref()
+        .unwrap()
+        .message
+        .contains("Delete protection"));
+}
@@ -0,0 +1,40 @@
+mod common;
+
+use arcgis_api_rs::parser::{parse_response, EsriErrorResponse};
+use common::MockServer;
+use reqwest::Client;
+
+#[derive(serde::Deserialize, Debug)]
+struct Success {
+    #[allow(dead_code)]
+    success: bool,
+}
+
+#[tokio::test]
+async fn error_details_are_surfaced() {
+    let server = MockServer::json(
+        r#"{"error": {"code": 400, "message": "Unable to publish item.",
+            "details": ["Row 12: invalid latitude"]}}"#,
+    );
+
+    let response = Client::new().get(&server.url).send().await.unwrap();
+    let error = parse_response::<Success>(response).await.unwrap_err();
+
+    let esri = error.downcast_ref::<EsriErrorResponse>().unwrap();
+    assert!(esri.error.details() == ["Row 12: invalid latitude"]);
+    assert!(
+        error.to_string() == "ArcGIS error 400: Unable to publish item. (Row 12: invalid latitude)"
+    );
+}
+
+#[tokio::test]
+async fn error_without_details() {
+    let server = MockServer::json(
+        r#"{"error": {"code": 498, "message": "Invalid token.", "details": null}}"#,
+    );
+
+    let response = Client::new().get(&server.url).send().await.unwrap();
+    let error = parse_response::<Success>(response).await.unwrap_err();
+
+    assert!(error.to_string() == "ArcGIS error 498: Invalid token.");
+}
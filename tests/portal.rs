@@ -0,0 +1,102 @@
+mod common;
+
+use arcgis_api_rs::{
+    feature_layer::FeatureLayer,
+    portal::{InsufficientCredits, Portal},
+};
+use common::MockServer;
+use reqwest::Client;
+
+#[tokio::test]
+async fn portal_version_from_self() {
+    let server = MockServer::json(r#"{"id": "0123", "name": "Org", "currentVersion": "11.1.0"}"#);
+
+    let portal = Portal::new(&server.url, &Client::new())
+        .await
+        .expect("Failed to fetch portal");
+
+    assert!(server.requests()[0].path() == "/portals/self");
+    assert!(portal.portal_version() == Some((11, 1)));
+    assert!(portal.require_version((11, 1), "enriched search").is_ok());
+    assert!(portal.require_version((11, 3), "enriched search").is_err());
+}
+
+#[tokio::test]
+async fn portal_version_compares_minor_as_integer() {
+    let server = MockServer::json(r#"{"id": "0123", "name": "Org", "currentVersion": "11.10"}"#);
+
+    let portal = Portal::new(&server.url, &Client::new())
+        .await
+        .expect("Failed to fetch portal");
+
+    assert!(portal.portal_version() == Some((11, 10)));
+    assert!(portal.require_version((11, 9), "enriched search").is_ok());
+}
+
+#[tokio::test]
+async fn anonymous_portal_reads_public_layer() {
+    let server = MockServer::start(|request| {
+        let body = if request.path() == "/portals/self" {
+            r#"{"id": "0123", "name": "Org", "currentVersion": "2025.1"}"#
+        } else {
+            r#"{"type": "Feature Layer", "name": "Parks", "fields": []}"#
+        };
+        (200, body.to_string())
+    });
+
+    let portal = Portal::anonymous(&server.url)
+        .await
+        .expect("Failed to connect anonymously");
+    let url = format!("{}/rest/services/Parks/FeatureServer/0", portal.root());
+    let layer = FeatureLayer::new(portal.client(), &url)
+        .await
+        .expect("Failed to read public layer");
+
+    assert!(layer.metadata.name == "Parks");
+    let requests = server.requests();
+    assert!(requests.len() == 2);
+    assert!(requests
+        .iter()
+        .all(|r| r.header("X-Esri-Authorization").is_none() && !r.url.contains("token=")));
+}
+
+#[tokio::test]
+async fn require_credits_checks_remaining_credits() {
+    let server = MockServer::json(
+        r#"{"id": "0123", "name": "Org", "subscriptionInfo": {"availableCredits": 42.5}}"#,
+    );
+    let portal = Portal::new(&server.url, &Client::new()).await.unwrap();
+
+    assert!(portal.credits_remaining().await.unwrap() == 42.5);
+    assert!(portal.require_credits(10.0).await.is_ok());
+    let error = portal.require_credits(100.0).await.unwrap_err();
+    let insufficient = error.downcast_ref::<InsufficientCredits>().unwrap();
+    assert!(insufficient.available == 42.5 && insufficient.required == 100.0);
+}
+
+#[tokio::test]
+async fn community_self_is_cached_until_refresh() {
+    let server = MockServer::start(|request| {
+        let body = if request.path() == "/community/self" {
+            r#"{"username": "jdoe", "role": "org_publisher", "privileges": ["portal:user:createItem"]}"#
+        } else {
+            r#"{"id": "0123", "name": "Org", "currentVersion": "2025.1"}"#
+        };
+        (200, body.to_string())
+    });
+    let mut portal = Portal::new(&server.url, &Client::new()).await.unwrap();
+
+    let user = portal.community_self().await.expect("Failed to fetch user");
+    assert!(user.username == "jdoe" && user.role.as_deref() == Some("org_publisher"));
+    portal.community_self().await.unwrap();
+    assert!(server.requests().len() == 2);
+
+    portal.refresh_self().await.expect("Failed to refresh");
+    portal.community_self().await.unwrap();
+    assert!(server.requests().len() == 4);
+
+    let content = portal.content().await.expect("Failed to get content");
+    assert!(content.user_name() == "jdoe");
+    assert!(portal.content_for("asmith").user_name() == "asmith");
+    assert!(server.requests().len() == 4);
+}
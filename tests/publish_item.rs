@@ -0,0 +1,239 @@
+mod common;
+
+use std::collections::HashMap;
+
+use arcgis_api_rs::publish_item::{
+    CoordinateFieldType, CsvPublishParameters, FilePublishParameters, FileType, LocationType,
+    PublishItemQuery, PublishItemQueryBuilder, PublishItemQueryParams, SpatialReference,
+};
+use common::MockServer;
+use reqwest::Client;
+
+#[test]
+fn publish_parameters_to_json() {
+    let parameters = CsvPublishParameters {
+        name: "Wells".into(),
+        location_type: LocationType::Coordinates,
+        latitude_field_name: Some("Latitude".into()),
+        longitude_field_name: Some("Longitude".into()),
+        ..Default::default()
+    };
+
+    let json = parameters.to_json_value();
+
+    assert!(parameters.validate().is_ok());
+    assert!(json["type"] == "csv");
+    assert!(json["locationType"] == "coordinates");
+    assert!(json["latitudeFieldName"] == "Latitude");
+    assert!(
+        serde_json::from_str::<serde_json::Value>(&parameters.to_json_string()).unwrap() == json
+    );
+}
+
+#[test]
+fn publish_parameters_require_location_fields() {
+    let coordinates = CsvPublishParameters {
+        name: "Wells".into(),
+        location_type: LocationType::Coordinates,
+        latitude_field_name: Some("Latitude".into()),
+        ..Default::default()
+    };
+    let unnamed = CsvPublishParameters {
+        location_type: LocationType::None,
+        ..Default::default()
+    };
+
+    assert!(coordinates.validate().is_err());
+    assert!(unnamed.validate().is_err());
+}
+
+#[test]
+fn geojson_publish_parameters() {
+    let params = PublishItemQueryParams {
+        item_id: "abc".into(),
+        file_type: FileType::GeoJson,
+        f: "json".into(),
+        token: Some("tok".into()),
+        file_publish_parameters: Some(FilePublishParameters {
+            name: "Parcels".into(),
+            max_record_count: Some(5000.0),
+            target_sr: Some(SpatialReference {
+                wkid: 102100,
+                latest_wkid: Some(3857),
+            }),
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+
+    let body: Vec<(String, String)> =
+        serde_urlencoded::from_str(&params.to_urlencoded().unwrap()).unwrap();
+    let value = |key: &str| body.iter().find(|(k, _)| k == key).unwrap().1.clone();
+    let publish_parameters: serde_json::Value =
+        serde_json::from_str(&value("publishParameters")).unwrap();
+
+    assert!(value("fileType") == "geojson");
+    assert!(publish_parameters["name"] == "Parcels");
+    assert!(publish_parameters["maxRecordCount"] == 5000.0);
+    assert!(publish_parameters["targetSR"]["wkid"] == 102100);
+    assert!(FileType::from_item_type("Service Definition") == Some(FileType::ServiceDefinition));
+}
+
+#[tokio::test]
+async fn shapefile_publish_parameters_drop_csv_options() {
+    let server = MockServer::json(r#"{"services": []}"#);
+
+    PublishItemQuery::builder(&server.url, "jdoe", "abc")
+        .name("Parcels")
+        .description("County parcels")
+        .file_type(FileType::Shapefile)
+        .source_item_type("Shapefile")
+        .token("tok")
+        .build()
+        .send(&Client::new())
+        .await
+        .expect("Failed to publish shapefile");
+
+    let body = &server.requests()[0].body;
+    let parameters = publish_parameters(body);
+    assert!(body.contains("fileType=shapefile"));
+    assert!(parameters["name"] == "Parcels");
+    assert!(parameters["description"] == "County parcels");
+    assert!(parameters.get("locationType").is_none());
+    assert!(parameters.get("layerInfo").is_none());
+    assert!(parameters.get("type").is_none());
+}
+
+/// The `publishParameters` posted in `body`.
+fn publish_parameters(body: &str) -> serde_json::Value {
+    let body: Vec<(String, String)> = serde_urlencoded::from_str(body).unwrap();
+    let parameters = &body
+        .iter()
+        .find(|(k, _)| k == "publishParameters")
+        .unwrap()
+        .1;
+    serde_json::from_str(parameters).unwrap()
+}
+
+#[tokio::test]
+async fn publish_fetches_the_source_item_type() {
+    let server = MockServer::start(|request| {
+        let body = if request.path() == "/content/items/abc" {
+            r#"{"id": "abc", "type": "Shapefile"}"#
+        } else {
+            r#"{"services": []}"#
+        };
+        (200, body.to_string())
+    });
+    let client = Client::new();
+
+    let mismatch = PublishItemQuery::builder(&server.url, "jdoe", "abc")
+        .name("Parcels")
+        .token("tok")
+        .build()
+        .send(&client)
+        .await
+        .expect_err("Published a shapefile as CSV");
+    PublishItemQuery::builder(&server.url, "jdoe", "abc")
+        .name("Parcels")
+        .file_type(FileType::Shapefile)
+        .token("tok")
+        .build()
+        .send(&client)
+        .await
+        .expect("Failed to publish shapefile");
+
+    let requests = server.requests();
+    assert!(mismatch.to_string() == r#"Cannot publish a "Shapefile" item with fileType "csv""#);
+    assert!(requests.len() == 3);
+    assert!(requests[0].url.contains("token=tok"));
+    assert!(requests[2].path() == "/content/users/jdoe/publish");
+}
+
+#[tokio::test]
+async fn publish_csv_by_address_or_mgrs() {
+    let server = MockServer::json(r#"{"services": []}"#);
+    let client = Client::new();
+
+    PublishItemQuery::builder(&server.url, "jdoe", "abc")
+        .name("Customers")
+        .source_item_type("CSV")
+        .address_template("{Street}, {City}, {State} {Zip}")
+        .additional_fields(vec!["Street".into(), "City".into()])
+        .token("tok")
+        .build()
+        .send(&client)
+        .await
+        .expect("Failed to publish by address");
+    PublishItemQuery::builder(&server.url, "jdoe", "abc")
+        .name("Sites")
+        .source_item_type("CSV")
+        .coordinate_field("Grid", CoordinateFieldType::Mgrs)
+        .token("tok")
+        .build()
+        .send(&client)
+        .await
+        .expect("Failed to publish by MGRS");
+
+    let requests = server.requests();
+    let address = publish_parameters(&requests[0].body);
+    let mgrs = publish_parameters(&requests[1].body);
+    assert!(address["locationType"] == "address");
+    assert!(address["addressTemplate"] == "{Street}, {City}, {State} {Zip}");
+    assert!(address.get("coordinateFieldType").is_none());
+    assert!(address["layerInfo"]["fields"][1]["name"] == "City");
+    assert!(mgrs["locationType"] == "coordinates");
+    assert!(mgrs["coordinateFieldName"] == "Grid");
+    assert!(mgrs["coordinateFieldType"] == "MGRS");
+    assert!(mgrs["layerInfo"]["fields"][0]["name"] == "Grid");
+}
+
+#[test]
+fn publish_builder_keeps_stored_parameters() {
+    let stored = CsvPublishParameters {
+        name: "Customers".into(),
+        location_type: LocationType::Address,
+        address_template: Some("{Address}, {City}".into()),
+        source_country: Some("us".into()),
+        ..Default::default()
+    };
+
+    let query = PublishItemQueryBuilder::from(stored)
+        .item("https://example.com", "jdoe", "abc")
+        .additional_fields(vec!["Address".into(), "City".into()])
+        .build();
+    let parameters = CsvPublishParameters::from(&query);
+
+    assert!(matches!(parameters.location_type, LocationType::Address));
+    assert!(parameters.address_template.as_deref() == Some("{Address}, {City}"));
+    assert!(parameters.source_country.as_deref() == Some("us"));
+    assert!(parameters.layer_info["fields"][0]["name"] == "Address");
+}
+
+#[tokio::test]
+async fn publish_validates_location_fields_before_sending() {
+    let server = MockServer::json(r#"{"services": []}"#);
+    let client = Client::new();
+    let builder = || PublishItemQuery::builder(&server.url, "jdoe", "abc").source_item_type("CSV");
+    let country = HashMap::from([("Country".to_string(), "Nation".to_string())]);
+
+    let lookup = builder()
+        .name("Sales")
+        .lookup("Country", country)
+        .additional_fields(vec!["Nation".into()])
+        .build();
+    let unnamed = builder().build().send(&client).await;
+    let undescribed = builder()
+        .name("Customers")
+        .address_template("{Address}")
+        .build()
+        .send(&client)
+        .await;
+
+    let parameters = CsvPublishParameters::from(&lookup);
+    assert!(matches!(parameters.location_type, LocationType::Lookup));
+    assert!(parameters.lookup_type.as_deref() == Some("Country"));
+    assert!(unnamed.is_err());
+    assert!(undescribed.is_err());
+    assert!(server.requests().is_empty());
+}
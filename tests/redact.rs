@@ -0,0 +1,73 @@
+mod common;
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use arcgis_api_rs::{
+    auth::ArcGISProvider,
+    redact::{redact_form, redact_params, SENSITIVE_KEYS},
+};
+use common::MockServer;
+
+/// Collects formatted trace output.
+#[derive(Clone, Default)]
+struct Captured(Arc<Mutex<Vec<u8>>>);
+
+impl std::io::Write for Captured {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn redacts_sensitive_form_fields() {
+    let body = "username=ann&password=hunter2&servicePassword=p%40ss&f=json";
+
+    let redacted = redact_form(body, SENSITIVE_KEYS);
+
+    assert!(redacted == "username=ann&password=REDACTED&servicePassword=REDACTED&f=json");
+}
+
+#[test]
+fn redacts_custom_keys_case_insensitively() {
+    let mut params = HashMap::new();
+    params.insert("apiSecret", "s3cret");
+
+    let redacted = redact_params(&params, &["APISECRET"]);
+
+    assert!(redacted == "apiSecret=REDACTED");
+}
+
+#[tokio::test]
+async fn traced_token_requests_are_redacted() {
+    let server = MockServer::json(r#"{"token": "tok", "expires": 0, "ssl": true}"#);
+    let captured = Captured::default();
+    let writer = captured.clone();
+    let subscriber = tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::TRACE)
+        .with_writer(move || writer.clone())
+        .finish();
+    let _guard = tracing::subscriber::set_default(subscriber);
+    let provider = ArcGISProvider {
+        client: reqwest::Client::new(),
+        portal: server.url.clone(),
+        username: "ann".into(),
+        password: "hunter2".into(),
+        referer: "https://example.com".into(),
+        expiration: "60".into(),
+    };
+
+    provider.fetch_token().await.expect("Token fetch failed");
+
+    let logs = String::from_utf8(captured.0.lock().unwrap().clone()).unwrap();
+    assert!(server.requests()[0].body.contains("password=hunter2"));
+    assert!(logs.contains("password=REDACTED"));
+    assert!(!logs.contains("hunter2"));
+}
@@ -0,0 +1,335 @@
+mod common;
+
+use std::{
+    io::Write,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
+};
+
+use arcgis_api_rs::{
+    auth::ArcGISProvider,
+    client::ArcGISClient,
+    feature_layer::FeatureServiceInfo,
+    feature_layer_query::GeometryType,
+    geometry_service::GeometryService,
+    parser::EsriErrorResponse,
+    retry::{NetworkError, NetworkErrorKind, RetryPolicy, CORRELATION_ID_HEADER},
+};
+use common::MockServer;
+use reqwest::{Client, Method};
+
+/// Answers with `statuses` in order, repeating the last one.
+fn mock_server(statuses: Vec<u16>) -> MockServer {
+    let hits = AtomicUsize::new(0);
+    MockServer::start(move |_| {
+        let n = hits.fetch_add(1, Ordering::SeqCst);
+        (statuses[n.min(statuses.len() - 1)], "{}")
+    })
+}
+
+fn policy() -> RetryPolicy {
+    RetryPolicy::new(3, Duration::from_millis(1))
+}
+
+/// A client whose requests are retried with [`policy`] and carry a
+/// correlation id.
+fn arcgis_client() -> ArcGISClient {
+    ArcGISClient::builder()
+        .retry_policy(policy().with_correlation_id(CORRELATION_ID_HEADER))
+        .build()
+}
+
+#[tokio::test]
+async fn retries_get_on_server_error() {
+    let server = mock_server(vec![503, 502, 200]);
+    let client = Client::new();
+
+    let request = client.get(&server.url).build().unwrap();
+    let response = policy().execute(&client, request).await.unwrap();
+
+    assert!(response.status() == 200);
+    assert!(server.requests().len() == 3);
+}
+
+#[tokio::test]
+async fn does_not_retry_post_on_server_error() {
+    let server = mock_server(vec![503, 200]);
+    let client = Client::new();
+
+    let request = client.post(&server.url).body("f=json").build().unwrap();
+    let response = policy().execute(&client, request).await.unwrap();
+
+    assert!(response.status() == 503);
+    assert!(server.requests().len() == 1);
+}
+
+#[tokio::test]
+async fn retries_post_on_too_many_requests() {
+    let server = mock_server(vec![429, 200]);
+    let client = Client::new();
+
+    let request = client.post(&server.url).body("f=json").build().unwrap();
+    let response = policy().execute(&client, request).await.unwrap();
+
+    assert!(response.status() == 200);
+    assert!(server.requests().len() == 2);
+}
+
+#[tokio::test]
+async fn retries_idempotent_post_on_server_error() {
+    let server = mock_server(vec![500, 200]);
+    let client = Client::new();
+
+    let request = client
+        .post(&server.url)
+        .body("itemIdToCreate=abc")
+        .build()
+        .unwrap();
+    let response = policy().execute_idempotent(&client, request).await.unwrap();
+
+    assert!(response.status() == 200);
+    assert!(server.requests().len() == 2);
+}
+
+#[tokio::test]
+async fn gives_up_after_max_retries() {
+    let server = mock_server(vec![503]);
+    let client = Client::new();
+
+    let request = client.get(&server.url).build().unwrap();
+    let response = policy().execute(&client, request).await.unwrap();
+
+    assert!(response.status() == 503);
+    assert!(server.requests().len() == 4);
+}
+
+#[test]
+fn only_get_is_repeatable_by_default() {
+    let policy = policy();
+    let status = reqwest::StatusCode::BAD_GATEWAY;
+
+    assert!(policy.should_retry_status(&Method::GET, false, status));
+    assert!(!policy.should_retry_status(&Method::POST, false, status));
+    assert!(policy.should_retry_status(&Method::POST, true, status));
+    assert!(!policy.should_retry_status(&Method::GET, false, reqwest::StatusCode::BAD_REQUEST));
+}
+
+#[test]
+fn jitter_stays_within_backoff() {
+    let policy = RetryPolicy::new(3, Duration::from_millis(100)).with_jitter(true);
+
+    let delays: Vec<_> = (0..20).map(|_| policy.backoff(2)).collect();
+
+    assert!(delays.iter().all(|d| *d <= Duration::from_millis(400)));
+    assert!(delays.iter().any(|d| *d != delays[0]));
+}
+
+#[tokio::test]
+async fn correlation_id_is_kept_across_retries() {
+    let hits = AtomicUsize::new(0);
+    let server = MockServer::start(move |_| {
+        let status = if hits.fetch_add(1, Ordering::SeqCst) == 0 {
+            503
+        } else {
+            200
+        };
+        (status, "{}")
+    });
+    let client = Client::new();
+    let policy = policy().with_correlation_id(CORRELATION_ID_HEADER);
+
+    for _ in 0..2 {
+        let request = client.get(&server.url).build().unwrap();
+        policy.execute(&client, request).await.unwrap();
+    }
+
+    let ids: Vec<_> = server
+        .requests()
+        .iter()
+        .map(|r| r.header("X-Correlation-ID").unwrap().to_string())
+        .collect();
+    assert!(ids.len() == 3);
+    assert!(ids[0] == ids[1] && ids[1] != ids[2]);
+}
+
+/// Answers `429` with `Retry-After: retry_after` once, then `200`.
+fn throttling_server(retry_after: &'static str) -> MockServer {
+    let hits = AtomicUsize::new(0);
+    MockServer::start_with_headers(move |_| {
+        if hits.fetch_add(1, Ordering::SeqCst) == 0 {
+            (429, vec![("Retry-After", retry_after.to_string())], "{}")
+        } else {
+            (200, vec![], "{}")
+        }
+    })
+}
+
+#[tokio::test]
+async fn honors_retry_after() {
+    let server = throttling_server("0");
+    let client = Client::new();
+    // The backoff alone would wait far longer than the test allows.
+    let policy = RetryPolicy::new(1, Duration::from_secs(60));
+
+    let request = client.post(&server.url).body("f=json").build().unwrap();
+    let response = tokio::time::timeout(Duration::from_secs(5), policy.execute(&client, request))
+        .await
+        .expect("Retry-After was not honored")
+        .unwrap();
+
+    assert!(response.status() == 200);
+}
+
+#[tokio::test]
+async fn gives_up_on_long_retry_after() {
+    let server = throttling_server("86400");
+    let client = Client::new();
+    let policy = policy().with_max_retry_after(Duration::from_secs(30));
+
+    let request = client.get(&server.url).build().unwrap();
+    let response = tokio::time::timeout(Duration::from_secs(5), policy.execute(&client, request))
+        .await
+        .expect("Waited for Retry-After over the maximum")
+        .unwrap();
+
+    assert!(response.status() == 429);
+    assert!(server.requests().len() == 1);
+}
+
+#[tokio::test]
+async fn classifies_network_errors() {
+    // A port nothing listens on.
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let refused = format!("http://{}", listener.local_addr().unwrap());
+    drop(listener);
+    // A server that accepts but never answers.
+    let silent = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let timeout = format!("http://{}", silent.local_addr().unwrap());
+
+    let client = Client::builder()
+        .timeout(Duration::from_millis(100))
+        .build()
+        .unwrap();
+    let refused = client.get(refused).send().await.unwrap_err();
+    let timeout = client.get(timeout).send().await.unwrap_err();
+
+    assert!(NetworkErrorKind::of(&refused) == Some(NetworkErrorKind::Connect));
+    assert!(
+        NetworkError::classify(&anyhow::Error::from(timeout)) == Some(NetworkErrorKind::Timeout)
+    );
+}
+
+#[tokio::test]
+async fn classifies_dns_and_tls_errors() {
+    // A server that answers a TLS handshake with plain HTTP.
+    let plain = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let tls = format!("https://{}", plain.local_addr().unwrap());
+    std::thread::spawn(move || {
+        let (mut stream, _) = plain.accept().unwrap();
+        let _ = stream.write_all(b"HTTP/1.1 400 Bad Request\r\n\r\n");
+    });
+
+    let client = Client::builder()
+        .timeout(Duration::from_secs(5))
+        .build()
+        .unwrap();
+    let dns = client
+        .get("http://arcgis.invalid")
+        .send()
+        .await
+        .unwrap_err();
+    let tls = client.get(tls).send().await.unwrap_err();
+
+    assert!(NetworkErrorKind::of(&dns) == Some(NetworkErrorKind::Dns));
+    assert!(NetworkErrorKind::of(&tls) == Some(NetworkErrorKind::Tls));
+}
+
+#[tokio::test]
+async fn queries_retry_through_client_policy() {
+    let hits = AtomicUsize::new(0);
+    let server = MockServer::start(move |_| {
+        if hits.fetch_add(1, Ordering::SeqCst) == 0 {
+            (503, "")
+        } else {
+            (200, r#"{"capabilities": "Query", "layers": []}"#)
+        }
+    });
+
+    let info = FeatureServiceInfo::fetch(&arcgis_client(), &server.url)
+        .await
+        .expect("Failed to fetch service info");
+
+    let requests = server.requests();
+    assert!(info.capabilities == "Query");
+    assert!(requests.len() == 2);
+    assert!(requests[0].header("X-Correlation-ID") == requests[1].header("X-Correlation-ID"));
+}
+
+#[tokio::test]
+async fn query_errors_carry_correlation_id() {
+    let server = MockServer::json(r#"{"error": {"code": 400, "message": "Invalid URL"}}"#);
+
+    let error = GeometryService::new(arcgis_client(), &server.url)
+        .project(GeometryType::Point, &[], 4326, 3857)
+        .await
+        .unwrap_err();
+
+    let id = server.requests()[0]
+        .header("X-Correlation-ID")
+        .unwrap()
+        .to_string();
+    let esri = error.downcast_ref::<EsriErrorResponse>().unwrap();
+    assert!(esri.correlation_id.as_deref() == Some(id.as_str()));
+    assert!(error.to_string().contains(&id));
+}
+
+#[tokio::test]
+async fn query_network_errors_are_typed() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let refused = format!("http://{}", listener.local_addr().unwrap());
+    drop(listener);
+
+    let error = FeatureServiceInfo::fetch(&arcgis_client(), &refused)
+        .await
+        .unwrap_err();
+
+    let network = error.downcast_ref::<NetworkError>().unwrap();
+    assert!(network.kind == NetworkErrorKind::Connect);
+    assert!(network.correlation_id.is_some());
+    assert!(NetworkError::classify(&error) == Some(NetworkErrorKind::Connect));
+}
+
+#[tokio::test]
+async fn token_requests_are_retried() {
+    let hits = AtomicUsize::new(0);
+    let server = MockServer::start(move |_| {
+        if hits.fetch_add(1, Ordering::SeqCst) == 0 {
+            (503, String::new())
+        } else {
+            let expires = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_millis()
+                + 3_600_000;
+            (
+                200,
+                format!(r#"{{"token": "tok", "expires": {}, "ssl": true}}"#, expires),
+            )
+        }
+    });
+    let provider = ArcGISProvider {
+        client: Client::new(),
+        portal: server.url.clone(),
+        username: "ann".into(),
+        password: "hunter2".into(),
+        referer: "https://example.com".into(),
+        expiration: "60".into(),
+    };
+
+    let (token, _) = provider.fetch_token().await.expect("Token fetch failed");
+
+    let requests = server.requests();
+    assert!(token == "tok");
+    assert!(requests.len() == 2);
+    assert!(requests[1].method == "POST");
+}
@@ -0,0 +1,116 @@
+mod common;
+
+use arcgis_api_rs::{
+    group::create::{SortField, SortOrder},
+    item::ItemType,
+    search::{SearchFilter, SearchQuery, SearchSort},
+};
+use common::MockServer;
+use reqwest::Client;
+
+#[test]
+fn filter_quotes_values_and_capitalizes_operators() {
+    let filter = SearchFilter::new()
+        .type_is(ItemType::FeatureService)
+        .or()
+        .type_is(ItemType::WebMap)
+        .owner_is("jdoe")
+        .build();
+
+    assert!(filter == r#"type:"Feature Service" OR type:"Web Map" AND owner:"jdoe""#);
+    assert!(
+        SearchFilter::new()
+            .type_is(ItemType::Other("Notebook".into()))
+            .build()
+            == r#"type:"Notebook""#
+    );
+}
+
+#[test]
+fn filter_escapes_quotes_and_drops_dangling_operators() {
+    let filter = SearchFilter::new().and().tag_is(r#"say "hi""#).or().build();
+
+    assert!(filter == r#"tags:"say \"hi\"""#);
+}
+
+#[test]
+fn sort_gives_each_field_its_own_order() {
+    let params = SearchSort::new()
+        .sort_by_many(vec![
+            (SortField::Title, SortOrder::Asc),
+            (SortField::Created, SortOrder::Desc),
+        ])
+        .build();
+
+    assert!(
+        serde_urlencoded::to_string(&params).unwrap()
+            == "sortField=title%2Ccreated&sortOrder=asc%2Cdesc"
+    );
+    assert!(SearchSort::new().build().is_empty());
+}
+
+/// The query parameters of a recorded request `url`.
+fn query_params(url: &str) -> Vec<(String, String)> {
+    serde_urlencoded::from_str(url.split_once('?').unwrap().1).unwrap()
+}
+
+#[tokio::test]
+async fn search_sends_query_and_filter() {
+    let server =
+        MockServer::json(r#"{"total": 0, "start": 1, "num": 20, "nextStart": -1, "results": []}"#);
+
+    let response = SearchQuery::builder(&server.url, "wells")
+        .filter(
+            SearchFilter::new()
+                .type_is(ItemType::FeatureService)
+                .owner_is("jdoe"),
+        )
+        .num(20)
+        .token("tok")
+        .build()
+        .send(&Client::new())
+        .await
+        .expect("Failed to search");
+
+    let request = &server.requests()[0];
+    let params = query_params(&request.url);
+    let param = |key: &str| {
+        params
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    };
+    assert!(request.path() == "/search");
+    assert!(param("q") == Some("wells"));
+    assert!(param("filter") == Some(r#"type:"Feature Service" AND owner:"jdoe""#));
+    assert!(param("num") == Some("20"));
+    assert!(response.next_start == -1 && response.results.is_empty());
+}
+
+#[tokio::test]
+async fn search_sends_per_field_sort() {
+    let server =
+        MockServer::json(r#"{"total": 0, "start": 1, "num": 10, "nextStart": -1, "results": []}"#);
+
+    SearchQuery::builder(&server.url, "owner:jdoe")
+        .sort(
+            SearchSort::new()
+                .by(SortField::Title, SortOrder::Asc)
+                .by(SortField::Created, SortOrder::Desc),
+        )
+        .build()
+        .send(&Client::new())
+        .await
+        .expect("Failed to search");
+
+    let params = query_params(&server.requests()[0].url);
+    let param = |key: &str| {
+        params
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    };
+    assert!(param("q") == Some("owner:jdoe"));
+    assert!(param("sortField") == Some("title,created"));
+    assert!(param("sortOrder") == Some("asc,desc"));
+}
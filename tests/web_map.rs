@@ -0,0 +1,350 @@
+mod common;
+
+use arcgis_api_rs::{
+    add_item::AddItemQuery,
+    web_map::{BaseMap, Effect, EffectValue, LayerType, OperationalLayer, WebMapBuilder},
+};
+use common::MockServer;
+
+#[test]
+fn group_layer_nests_children() {
+    let web_map = WebMapBuilder::new()
+        .add_group_layer(
+            "Boundaries",
+            vec![
+                OperationalLayer::feature_layer("https://example.com/FeatureServer/0", "States"),
+                OperationalLayer::feature_layer("https://example.com/FeatureServer/1", "Counties"),
+            ],
+        )
+        .build();
+
+    assert!(web_map.operational_layers.len() == 1);
+    let group = &web_map.operational_layers[0];
+    assert!(group.layer_type == LayerType::GroupLayer);
+    assert!(group.layers.len() == 2);
+
+    let json = serde_json::to_value(&web_map).unwrap();
+    let layers = &json["operationalLayers"][0]["layers"];
+    assert!(json["operationalLayers"][0]["layerType"] == "GroupLayer");
+    assert!(layers[0]["title"] == "States");
+    assert!(layers[1]["layerType"] == "ArcGISFeatureLayer");
+    assert!(layers[1].get("layers").is_none());
+}
+
+#[test]
+fn tables_serialize_only_when_present() {
+    let with_table = WebMapBuilder::new()
+        .add_feature_layer("https://example.com/FeatureServer/0", "Wells")
+        .add_table("https://example.com/FeatureServer/1", "Inspections")
+        .build();
+    let without = WebMapBuilder::new()
+        .add_feature_layer("https://example.com/FeatureServer/0", "Wells")
+        .build();
+
+    let json = serde_json::to_value(&with_table).unwrap();
+    assert!(json["tables"][0]["title"] == "Inspections");
+    assert!(json["operationalLayers"].as_array().unwrap().len() == 1);
+    assert!(serde_json::to_value(&without)
+        .unwrap()
+        .get("tables")
+        .is_none());
+}
+
+#[test]
+fn layer_blend_mode_and_effect_serialize() {
+    let web_map = WebMapBuilder::new()
+        .add_feature_layer("https://example.com/FeatureServer/0", "States")
+        .set_layer_blend_mode("multiply")
+        .set_layer_effect(vec![
+            Effect::new(vec![EffectValue::new("bloom")
+                .property("strength", 1.5)
+                .property("radius", "0.5px")])
+            .at_scale(2_000_000.0),
+            Effect::new(vec![EffectValue::new("drop-shadow")
+                .property("xoffset", "2px")
+                .property("yoffset", "2px")]),
+        ])
+        .build();
+
+    let json = serde_json::to_value(&web_map).unwrap();
+    let layer = &json["operationalLayers"][0];
+    assert!(layer["blendMode"] == "multiply");
+    assert!(
+        layer["effect"]
+            == serde_json::json!([
+                {
+                    "scale": 2000000.0,
+                    "value": [{ "type": "bloom", "strength": 1.5, "radius": "0.5px" }]
+                },
+                {
+                    "value": [{ "type": "drop-shadow", "xoffset": "2px", "yoffset": "2px" }]
+                }
+            ])
+    );
+    assert!(json["baseMap"]["baseMapLayers"][0]
+        .get("blendMode")
+        .is_none());
+
+    let effect: Vec<Effect> = serde_json::from_value(layer["effect"].clone()).unwrap();
+    assert!(effect == web_map.operational_layers[0].effect.clone().unwrap());
+}
+
+#[test]
+fn layer_opacity_and_visibility_by_index() {
+    let web_map = WebMapBuilder::new()
+        .add_feature_layer("https://example.com/FeatureServer/0", "States")
+        .add_feature_layer("https://example.com/FeatureServer/1", "Counties")
+        .set_layer_opacity(0.5)
+        .set_layer_opacity_at(0, 0.8)
+        .set_layer_visibility_at(0, false)
+        .set_layer_visibility_at(5, false)
+        .build();
+
+    let states = &web_map.operational_layers[0];
+    let counties = &web_map.operational_layers[1];
+    assert!(states.opacity == Some(0.8) && states.visibility == Some(false));
+    assert!(counties.opacity == Some(0.5) && counties.visibility.is_none());
+}
+
+#[tokio::test]
+async fn from_feature_service_frames_layer_extent() {
+    let server = MockServer::json(
+        r#"{"type": "Feature Layer", "name": "Wells", "fields": [],
+            "extent": {"xmin": -120.5, "ymin": 35.0, "xmax": -119.5, "ymax": 36.0,
+                       "spatialReference": {"wkid": 4326, "latestWkid": 4326}}}"#,
+    );
+    let url = format!("{}/FeatureServer/0", server.url);
+
+    let web_map = WebMapBuilder::from_feature_service(&reqwest::Client::new(), &url, "Wells")
+        .await
+        .expect("Failed to build web map")
+        .build();
+
+    let json = serde_json::to_value(&web_map).unwrap();
+    let target = &json["initialState"]["viewpoint"]["targetGeometry"];
+    assert!(json["operationalLayers"][0]["url"] == url.as_str());
+    assert!(json["baseMap"]["title"] == "Topographic");
+    assert!(target["xmin"] == -120.5);
+    assert!(target["spatialReference"]["wkid"] == 4326);
+}
+
+#[tokio::test]
+async fn from_feature_service_fails_on_missing_layer() {
+    let server = MockServer::json(r#"{"error": {"code": 400, "message": "Invalid URL"}}"#);
+
+    let result =
+        WebMapBuilder::from_feature_service(&reqwest::Client::new(), &server.url, "Wells").await;
+
+    assert!(result.is_err());
+}
+
+#[test]
+fn feature_collection_embeds_features() {
+    let features = serde_json::from_str(
+        r#"{"features": [
+            {"attributes": {"name": "Well 1", "depth": 120}, "geometry": {"x": -120.5, "y": 35.0}},
+            {"attributes": {"name": "Well 2", "depth": 80}, "geometry": {"x": -119.5, "y": 36.0}}
+        ]}"#,
+    )
+    .unwrap();
+
+    let web_map = WebMapBuilder::new()
+        .add_feature_collection("Wells", features)
+        .build();
+
+    let json = serde_json::to_value(&web_map).unwrap();
+    let layer = &json["operationalLayers"][0];
+    let collection = &layer["featureCollection"]["layers"][0];
+    let definition = &collection["layerDefinition"];
+    assert!(layer.get("url").is_none());
+    assert!(definition["geometryType"] == "esriGeometryPoint");
+    assert!(definition["drawingInfo"]["renderer"]["symbol"]["type"] == "esriSMS");
+    assert!(definition["fields"][0]["type"] == "esriFieldTypeOID");
+    assert!(definition["fields"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .any(|f| f["name"] == "depth" && f["type"] == "esriFieldTypeInteger"));
+    assert!(collection["featureSet"]["spatialReference"]["wkid"] == 4326);
+    assert!(collection["featureSet"]["features"][1]["attributes"]["OBJECTID"] == 2);
+}
+
+#[test]
+fn feature_collection_infers_fields_from_every_feature() {
+    let features = serde_json::from_str(
+        r#"{"features": [
+            {"attributes": {"OBJECTID": 5, "depth": 120, "note": null}, "geometry": {"x": -120.5, "y": 35.0}},
+            {"attributes": {"depth": 80.5, "note": "dry"}, "geometry": {"x": -119.5, "y": 36.0}},
+            {"attributes": {"status": "active"}, "geometry": {"x": -119.0, "y": 36.5}}
+        ]}"#,
+    )
+    .unwrap();
+
+    let web_map = WebMapBuilder::new()
+        .add_feature_collection("Wells", features)
+        .build();
+
+    let json = serde_json::to_value(&web_map).unwrap();
+    let collection = &json["operationalLayers"][0]["featureCollection"]["layers"][0];
+    let fields = collection["layerDefinition"]["fields"].as_array().unwrap();
+    let field_type =
+        |name: &str| fields.iter().find(|f| f["name"] == name).unwrap()["type"].clone();
+    assert!(field_type("depth") == "esriFieldTypeDouble");
+    assert!(field_type("note") == "esriFieldTypeString");
+    assert!(field_type("status") == "esriFieldTypeString");
+    let object_ids: Vec<i64> = collection["featureSet"]["features"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter_map(|f| f["attributes"]["OBJECTID"].as_i64())
+        .collect();
+    assert!(object_ids == [5, 6, 7]);
+}
+
+#[test]
+fn feature_collection_with_custom_renderer() {
+    let features = serde_json::from_str(
+        r#"{"features": [{"attributes": {"status": "active"}, "geometry": {"x": -120.5, "y": 35.0}}]}"#,
+    )
+    .unwrap();
+    let renderer = serde_json::json!({
+        "type": "uniqueValue",
+        "field1": "status",
+        "uniqueValueInfos": [{"value": "active", "symbol": {"type": "esriSMS", "size": 10}}]
+    });
+
+    let web_map = WebMapBuilder::new()
+        .add_feature_collection("Wells", features)
+        .set_layer_renderer(renderer.clone())
+        .add_feature_layer("https://example.com/FeatureServer/0", "States")
+        .set_layer_renderer(renderer.clone())
+        .build();
+
+    let json = serde_json::to_value(&web_map).unwrap();
+    let collection = &json["operationalLayers"][0]["featureCollection"]["layers"][0];
+    let service = &json["operationalLayers"][1];
+    assert!(collection["layerDefinition"]["drawingInfo"]["renderer"] == renderer);
+    assert!(collection["layerDefinition"]["geometryType"] == "esriGeometryPoint");
+    assert!(service["layerDefinition"]["drawingInfo"]["renderer"] == renderer);
+}
+
+#[test]
+fn layer_configurator_scopes_settings() {
+    let web_map = WebMapBuilder::new()
+        .layer(OperationalLayer::feature_layer(
+            "https://example.com/FeatureServer/0",
+            "States",
+        ))
+        .opacity(0.6)
+        .definition_expression("POP > 1000000")
+        .popup(serde_json::json!({"title": "{STATE_NAME}"}))
+        .done()
+        .layer(OperationalLayer::feature_layer(
+            "https://example.com/FeatureServer/1",
+            "Counties",
+        ))
+        .visibility(false)
+        .done()
+        .build();
+
+    let json = serde_json::to_value(&web_map).unwrap();
+    let states = &json["operationalLayers"][0];
+    let counties = &json["operationalLayers"][1];
+    assert!(states["opacity"] == 0.6);
+    assert!(states["layerDefinition"]["definitionExpression"] == "POP > 1000000");
+    assert!(states["popupInfo"]["title"] == "{STATE_NAME}");
+    assert!(counties["visibility"] == false);
+    assert!(counties.get("opacity").is_none());
+}
+
+#[tokio::test]
+async fn built_web_map_is_the_item_text() {
+    let server = MockServer::json(r#"{"success": true, "id": "abc", "folder": ""}"#);
+    let web_map = WebMapBuilder::new()
+        .add_feature_layer("https://example.com/FeatureServer/0", "Wells")
+        .build();
+
+    AddItemQuery::builder(&server.url, "jdoe")
+        .title("Wells")
+        .web_map(&web_map)
+        .token("tok")
+        .build()
+        .send(&reqwest::Client::new())
+        .await
+        .unwrap();
+
+    let form: std::collections::HashMap<String, String> =
+        serde_urlencoded::from_str(&server.requests()[0].body).unwrap();
+    let text: serde_json::Value = serde_json::from_str(&form["text"]).unwrap();
+    assert!(form["type"] == "Web Map");
+    assert!(text["operationalLayers"][0]["title"] == "Wells");
+    assert!(text["initialState"] == serde_json::json!({}));
+    assert!(text["spatialReference"]["wkid"] == 102100);
+}
+
+#[test]
+fn mixed_service_layers_serialize_their_layer_type() {
+    let web_map = WebMapBuilder::new()
+        .add_image_service_layer("https://example.com/ImageServer", "Elevation")
+        .add_map_service_layer("https://example.com/MapServer", "Parcels")
+        .add_vector_tile_layer(
+            "https://example.com/VectorTileServer/resources/styles/root.json",
+            "Streets",
+        )
+        .add_feature_layer("https://example.com/FeatureServer/0", "Wells")
+        .build();
+
+    let json = serde_json::to_value(&web_map).unwrap();
+    let layers = &json["operationalLayers"];
+    assert!(layers[0]["layerType"] == "ArcGISImageServiceLayer");
+    assert!(layers[1]["layerType"] == "ArcGISMapServiceLayer");
+    assert!(layers[1]["url"] == "https://example.com/MapServer");
+    assert!(layers[2]["layerType"] == "VectorTileLayer");
+    assert!(
+        layers[2]["styleUrl"] == "https://example.com/VectorTileServer/resources/styles/root.json"
+    );
+    assert!(layers[2].get("url").is_none());
+    assert!(layers[3].get("styleUrl").is_none());
+}
+
+#[test]
+fn custom_basemaps_from_item_or_url() {
+    let from_item = WebMapBuilder::new()
+        .set_basemap_from_item(
+            "0123456789abcdef0123456789abcdef",
+            LayerType::VectorTileLayer,
+            "Acme Streets",
+        )
+        .build();
+    let tiled_item = BaseMap::from_item(
+        "fedcba9876543210fedcba9876543210",
+        LayerType::ArcGISTiledMapServiceLayer,
+        "Acme Imagery",
+    );
+    let from_url = WebMapBuilder::new()
+        .set_basemap_from_url(
+            "https://example.com/arcgis/rest/services/Hillshade/MapServer",
+            LayerType::ArcGISTiledMapServiceLayer,
+            "Acme Hillshade",
+        )
+        .build();
+
+    let json = serde_json::to_value(&from_item).unwrap();
+    let layer = &json["baseMap"]["baseMapLayers"][0];
+    assert!(json["baseMap"]["title"] == "Acme Streets");
+    assert!(json["baseMap"]["baseMapLayers"].as_array().unwrap().len() == 1);
+    assert!(layer["layerType"] == "VectorTileLayer");
+    assert!(layer["itemId"] == "0123456789abcdef0123456789abcdef");
+    assert!(layer["visibility"] == true && layer["opacity"] == 1.0);
+
+    let json = serde_json::to_value(&tiled_item).unwrap();
+    let layer = &json["baseMapLayers"][0];
+    assert!(layer["layerType"] == "ArcGISTiledMapServiceLayer");
+    assert!(layer["itemId"] == "fedcba9876543210fedcba9876543210");
+
+    let json = serde_json::to_value(&from_url).unwrap();
+    let layer = &json["baseMap"]["baseMapLayers"][0];
+    assert!(layer["layerType"] == "ArcGISTiledMapServiceLayer");
+    assert!(layer["url"] == "https://example.com/arcgis/rest/services/Hillshade/MapServer");
+    assert!(layer.get("styleUrl").is_none());
+}